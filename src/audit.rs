@@ -0,0 +1,80 @@
+use poise::serenity_prelude::{ChannelId, Http, Timestamp};
+use serde::{Deserialize, Serialize};
+use shuttle_persist::PersistInstance;
+
+use crate::config::GuildConfig;
+use crate::Error;
+
+//Older entries are dropped so the log doesn't grow forever
+const MAX_ENTRIES: usize = 500;
+
+///Actor of entries the bot records itself, for actions that weren't done through it
+pub const SYSTEM: u64 = 0;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    //Unix timestamp
+    pub at: i64,
+    //u64 = UserId, `SYSTEM` for actions not done through the bot, like deleting a poll's message
+    pub user_id: u64,
+    pub action: String,
+}
+
+impl AuditEntry {
+    ///Who did the action, as a mention
+    pub fn actor(&self) -> String {
+        match self.user_id {
+            SYSTEM => "System:".to_string(),
+            user_id => format!("<@{user_id}>"),
+        }
+    }
+}
+
+fn key(guild_id: u64) -> String {
+    format!("audit_{guild_id}")
+}
+
+fn load(persist: &PersistInstance, guild_id: u64) -> Vec<AuditEntry> {
+    persist.load(&key(guild_id)).unwrap_or_default()
+}
+
+///The latest entries of a guild's audit log, newest first
+pub fn recent(persist: &PersistInstance, guild_id: u64, count: usize) -> Vec<AuditEntry> {
+    load(persist, guild_id)
+        .into_iter()
+        .rev()
+        .take(count)
+        .collect()
+}
+
+///Records a poll management action and posts it in the guild's log channel if one is set
+pub async fn record(
+    http: &Http,
+    persist: &PersistInstance,
+    guild_id: u64,
+    user_id: u64,
+    action: impl Into<String>,
+) -> Result<(), Error> {
+    let entry = AuditEntry {
+        at: Timestamp::now().unix_timestamp(),
+        user_id,
+        action: action.into(),
+    };
+
+    let mut entries = load(persist, guild_id);
+    entries.push(entry.clone());
+    if entries.len() > MAX_ENTRIES {
+        entries.drain(..entries.len() - MAX_ENTRIES);
+    }
+    persist.save(&key(guild_id), entries)?;
+
+    if let Some(channel_id) = GuildConfig::load(persist, guild_id).log_channel {
+        ChannelId(channel_id)
+            .send_message(http, |m| {
+                m.content(format!("{} {}", entry.actor(), entry.action))
+                    .allowed_mentions(|a| a.empty_parse())
+            })
+            .await?;
+    }
+    Ok(())
+}
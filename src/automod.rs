@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::Http;
+use regex::Regex;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::runtime;
+
+pub const DISCORD_API: &str = "https://discord.com/api/v10";
+
+//AutoMod's trigger type for rules with keywords and regular expressions
+const KEYWORD_TRIGGER: u8 = 1;
+//AutoMod's action type for stopping the message, rules that only alert or time out let it through
+const BLOCK_ACTION: u8 = 1;
+
+///The fields of an AutoMod rule the bot checks polls against
+#[derive(Deserialize, Clone)]
+pub struct Rule {
+    name: String,
+    enabled: bool,
+    trigger_type: u8,
+    #[serde(default)]
+    trigger_metadata: TriggerMetadata,
+    //Snowflakes are sent as strings
+    #[serde(default)]
+    exempt_roles: Vec<String>,
+    #[serde(default)]
+    exempt_channels: Vec<String>,
+    #[serde(default)]
+    actions: Vec<Action>,
+}
+
+#[derive(Deserialize, Clone)]
+struct Action {
+    #[serde(rename = "type")]
+    kind: u8,
+}
+
+#[derive(Deserialize, Clone, Default)]
+struct TriggerMetadata {
+    #[serde(default)]
+    keyword_filter: Vec<String>,
+    #[serde(default)]
+    regex_patterns: Vec<String>,
+    #[serde(default)]
+    allow_list: Vec<String>,
+}
+
+//u64 = GuildId, with when its rules were fetched
+static MIRROR: Lazy<Mutex<HashMap<u64, (Instant, Vec<Rule>)>>> = Lazy::new(Default::default);
+
+///An AutoMod keyword as a pattern. Keywords match whole words, a * at either end lets them
+///match inside a longer word on that side
+fn keyword_pattern(keyword: &str) -> String {
+    let prefix = keyword.starts_with('*');
+    let suffix = keyword.ends_with('*') && keyword.len() > 1;
+    let word = keyword.trim_matches('*');
+    format!(
+        r"(?i){}{}{}",
+        if prefix { "" } else { r"\b" },
+        regex::escape(word),
+        if suffix { "" } else { r"\b" }
+    )
+}
+
+impl Rule {
+    fn applies(&self, roles: &[u64], channel_id: u64) -> bool {
+        let exempt_role = roles
+            .iter()
+            .any(|r| self.exempt_roles.contains(&r.to_string()));
+        let exempt_channel = self.exempt_channels.contains(&channel_id.to_string());
+        let blocking = self.actions.iter().any(|a| a.kind == BLOCK_ACTION);
+        self.enabled
+            && self.trigger_type == KEYWORD_TRIGGER
+            && blocking
+            && !exempt_role
+            && !exempt_channel
+    }
+
+    ///Whether AutoMod would block the text, after taking out what its allow list lets through
+    fn blocks(&self, text: &str) -> bool {
+        let metadata = &self.trigger_metadata;
+        let mut text = text.to_string();
+        for allowed in metadata
+            .allow_list
+            .iter()
+            .filter(|k| !k.trim_matches('*').is_empty())
+        {
+            if let Ok(allowed) = Regex::new(&keyword_pattern(allowed)) {
+                text = allowed.replace_all(&text, " ").into_owned();
+            }
+        }
+
+        let keywords = metadata
+            .keyword_filter
+            .iter()
+            .filter(|k| !k.trim_matches('*').is_empty())
+            .map(|k| keyword_pattern(k));
+        //AutoMod's regular expressions are written for Rust's regex crate, so they are used as is
+        let patterns = metadata.regex_patterns.iter().cloned();
+        keywords
+            .chain(patterns)
+            .filter_map(|p| Regex::new(&p).ok())
+            .any(|r| r.is_match(&text))
+    }
+}
+
+///The Authorization header for calling Discord's API directly, for what serenity doesn't cover
+pub fn bot_token(http: &Http) -> String {
+    match http.token.starts_with("Bot ") {
+        true => http.token.clone(),
+        false => format!("Bot {}", http.token),
+    }
+}
+
+async fn fetch_rules(http: &Http, guild_id: u64) -> Result<Vec<Rule>, reqwest::Error> {
+    reqwest::Client::new()
+        .get(format!(
+            "{DISCORD_API}/guilds/{guild_id}/auto-moderation/rules"
+        ))
+        .header("Authorization", bot_token(http))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+///A guild's AutoMod rules, fetched again once the mirrored copy is a few minutes old
+async fn rules(http: &Http, guild_id: u64) -> Vec<Rule> {
+    if let Some((fetched, rules)) = MIRROR.lock().await.get(&guild_id) {
+        //Rules are mirrored for a while instead of fetched for every poll
+        if fetched.elapsed() < runtime::automod_cache_duration() {
+            return rules.clone();
+        }
+    }
+
+    //Fetched without the lock, so one slow guild doesn't hold up polls in every other.
+    //Reading the rules needs Manage Server, without it polls are only checked by the bot's filter
+    let rules = fetch_rules(http, guild_id).await.unwrap_or_else(|e| {
+        tracing::debug!("Failed to fetch the AutoMod rules of {guild_id}: {e}");
+        Vec::new()
+    });
+    MIRROR
+        .lock()
+        .await
+        .insert(guild_id, (Instant::now(), rules.clone()));
+    rules
+}
+
+///The name of the guild's AutoMod rule that would stop the member posting any of the texts
+///themselves, so polls can't be used to get around it
+pub async fn blocking_rule(
+    http: &Http,
+    guild_id: u64,
+    roles: &[u64],
+    channel_id: u64,
+    texts: &[&str],
+) -> Option<String> {
+    rules(http, guild_id)
+        .await
+        .into_iter()
+        .filter(|r| r.applies(roles, channel_id))
+        .find(|r| texts.iter().any(|t| r.blocks(t)))
+        .map(|r| r.name)
+}
@@ -0,0 +1,1414 @@
+use std::borrow::Cow;
+use std::time::Duration;
+
+use poise::serenity_prelude::{
+    self as serenity, AttachmentType, ButtonStyle, ChannelId, InteractionResponseType, Timestamp,
+};
+
+use crate::config::{can_create_polls, is_moderator, GuildConfig, VoteMessage};
+use crate::handler::results_text;
+use crate::import::import;
+use crate::poll::{
+    ButtonColor, Choice, Poll, PollButton, PollEdit, PollEvent, PollMessage, TieBreak,
+    TieResolution, Visibility, ANONYMOUS_REASON_MINIMUM,
+};
+use crate::scheduler::{self, Job};
+use crate::search::search;
+use crate::{
+    audit, context, filter, ical, lifecycle, native, queue, receipt, store, validate, vote, voters,
+};
+use crate::{ApplicationContext, Context, Error};
+
+///Replies to a command with ephemeral text
+pub async fn eph_reply(ctx: Context<'_>, text: impl Into<String>) -> Result<(), Error> {
+    ctx.send(|r| r.ephemeral(true).content(text.into())).await?;
+    Ok(())
+}
+
+//Parent command, only its subcommands can be invoked
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands(
+        "create",
+        "edit",
+        "history",
+        "breakdown",
+        "reasons",
+        "search",
+        "end",
+        "closeall",
+        "reopen",
+        "lock",
+        "delete",
+        "decide",
+        "crosspost",
+        "federate",
+        "receipt",
+        "notify",
+        "buttons",
+        "import"
+    )
+)]
+pub async fn poll(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+//Creates a poll
+#[poise::command(slash_command, prefix_command, check = "can_create_polls")]
+async fn create(
+    ctx: Context<'_>,
+    title: String,
+    #[description = "Details, {deadline}, {quorum}, {creator} and {eligible_count} are filled in"]
+    description: String,
+    #[description = "Why to vote yes, leave empty for a quick poll"] reason_to_vote_yes: Option<
+        String,
+    >,
+    #[description = "Why to vote no, leave empty for a quick poll"] reason_to_vote_no: Option<
+        String,
+    >,
+    #[description = "Start time of an event to schedule if the poll passes (e.g. 2024-01-31T18:00:00Z)"]
+    event_start: Option<String>,
+    #[description = "Length of the scheduled event in hours (default 1)"]
+    #[min = 1]
+    event_hours: Option<u32>,
+    #[description = "Location of the event (default Discord)"] event_location: Option<String>,
+    #[description = "Role given to members who vote yes"] yes_role: Option<serenity::Role>,
+    #[description = "Role given to members who vote no"] no_role: Option<serenity::Role>,
+    #[description = "Let members change or retract their vote"] allow_vote_changes: Option<bool>,
+    #[description = "Close the poll automatically after this many hours"]
+    #[min = 1]
+    duration_hours: Option<u32>,
+    #[description = "How to settle the poll if it ends in a tie"] tie_break: Option<TieBreak>,
+    #[description = "Who can see who voted for what"] visibility: Option<Visibility>,
+    #[description = "Only this role can view results until the poll closes"] results_role: Option<
+        serenity::Role,
+    >,
+    #[description = "Also count ✅ and ❌ reactions as votes, for clients without buttons"]
+    reaction_votes: Option<bool>,
+    #[description = "Bump the poll in its channel every this many hours while it is open"]
+    #[min = 1]
+    remind_every_hours: Option<u32>,
+    #[description = "Warn this many minutes before the poll closes"]
+    #[min = 1]
+    warn_minutes_before: Option<u32>,
+    #[description = "Also DM the warning to members who haven't voted"] warn_non_voters: Option<
+        bool,
+    >,
+    #[description = "Post a pinned message showing live results, keeping the poll itself clean"]
+    results_message: Option<bool>,
+    #[description = "Attach every vote as CSV to the summary DMed to you when the poll closes"]
+    summary_csv: Option<bool>,
+    #[description = "Ask voters why in a popup, reasons are shown in the results and the CSV"]
+    ask_why: Option<bool>,
+    #[description = "Link to the message the poll is about, quoted in the poll"] context: Option<
+        String,
+    >,
+    #[description = "Only show the poll to you first, with a button to publish it"] preview: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    if (yes_role.is_some() || no_role.is_some()) && ctx.guild_id().is_none() {
+        return eph_reply(ctx, "Vote roles can only be given in a server").await;
+    }
+    for role in yes_role.iter().chain(&no_role) {
+        if let Some(refusal) = vote_role_refusal(ctx, role).await {
+            return eph_reply(ctx, refusal).await;
+        }
+    }
+    if results_role.is_some() && ctx.guild_id().is_none() {
+        return eph_reply(ctx, "Results can only be restricted to a role in a server").await;
+    }
+    let results_message = results_message.unwrap_or(false);
+    if results_role.is_some() && results_message {
+        return eph_reply(
+            ctx,
+            "Restricted results can't be shown in a results message",
+        )
+        .await;
+    }
+    //DMs go to members who never chose to hear from the bot, so only moderators can send them
+    let warn_non_voters = warn_non_voters.unwrap_or(false);
+    if warn_non_voters && !is_moderator(ctx).await {
+        return eph_reply(ctx, "Only moderators can DM the warning to members").await;
+    }
+    //Anyone can see who reacted to a message, so reactions would give away private votes
+    let reaction_votes = reaction_votes.unwrap_or(false);
+    if reaction_votes && visibility.unwrap_or_default() != Visibility::Public {
+        return eph_reply(
+            ctx,
+            "Reactions show who voted, so reaction votes need everyone to see who voted",
+        )
+        .await;
+    }
+
+    let event = match event_start {
+        Some(start) => {
+            if ctx.guild_id().is_none() {
+                return eph_reply(ctx, "Scheduled events can only be created in a server").await;
+            }
+            let Ok(start) = Timestamp::parse(&start) else {
+                return eph_reply(ctx, "The event start must look like 2024-01-31T18:00:00Z").await;
+            };
+            let end = start.unix_timestamp() + i64::from(event_hours.unwrap_or(1)) * 3600;
+            Some(PollEvent {
+                start,
+                end: Timestamp::from_unix_timestamp(end)?,
+                location: event_location
+                    .map(|l| validate::strip_mass_mentions(&l))
+                    .unwrap_or_else(|| "Discord".to_string()),
+            })
+        }
+        None => None,
+    };
+
+    let context = match context {
+        Some(link) => match context::fetch(ctx, &link).await {
+            Ok(context) => Some(context),
+            Err(problem) => return eph_reply(ctx, problem).await,
+        },
+        None => None,
+    };
+
+    let texts = validate::poll_text(
+        &title,
+        &description,
+        reason_to_vote_yes.as_deref().unwrap_or_default(),
+        reason_to_vote_no.as_deref().unwrap_or_default(),
+    );
+    let [title, description, reason_to_vote_yes, reason_to_vote_no] = match texts {
+        Ok(texts) => texts,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+
+    let mut poll = Poll::new(
+        title,
+        description,
+        reason_to_vote_yes,
+        reason_to_vote_no,
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        ctx.channel_id().0,
+    );
+    from_guild(ctx, &mut poll);
+    poll.event = event;
+    poll.context = context;
+    poll.allow_changes = allow_vote_changes.unwrap_or(false);
+    poll.yes_role = yes_role.map(|r| r.id.0);
+    poll.no_role = no_role.map(|r| r.id.0);
+    poll.deadline =
+        duration_hours.map(|hours| Timestamp::now().unix_timestamp() + i64::from(hours) * 3600);
+    poll.tie_break = tie_break.unwrap_or_default();
+    poll.visibility = visibility.unwrap_or_default();
+    poll.results_roles = results_role.map(|r| r.id.0).into_iter().collect();
+    poll.reactions = reaction_votes;
+    poll.summary_csv = summary_csv.unwrap_or(false);
+    poll.ask_reasons = ask_why.unwrap_or(false);
+    poll.remind_hours = remind_every_hours;
+    poll.warn_minutes = warn_minutes_before;
+    poll.warn_dm = warn_non_voters;
+
+    if preview.unwrap_or(false) {
+        return preview_poll(ctx, poll, results_message).await;
+    }
+    publish_poll(ctx, poll, results_message, false).await
+}
+
+//Votes on a yes/no poll or signs a petition, like clicking its button
+#[poise::command(slash_command, prefix_command)]
+pub async fn vote(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+    #[description = "What to vote"] choice: Choice,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let Some((poll_id, poll)) = load_guild_poll(ctx, &poll_id) else {
+        return eph_reply(ctx, "No poll exists with that ID").await;
+    };
+    if !poll.options().is_empty() {
+        return eph_reply(
+            ctx,
+            "Polls with named options can only be voted on with their buttons",
+        )
+        .await;
+    }
+    if is_observer(ctx, &poll, &author_roles(ctx).await) {
+        return eph_reply(
+            ctx,
+            "Observers can view the results of polls here, but can't vote on them",
+        )
+        .await;
+    }
+
+    let user_id = ctx.author().id.0;
+    let click = vote::Click {
+        message_id: poll_id,
+        custom_id: match choice {
+            Choice::Yes => "poll_yes",
+            Choice::No => "poll_no",
+        },
+        user_id,
+        guild_id: ctx.guild_id().map(|g| g.0),
+    };
+    let (poll_id, poll, change) = match vote::click(&persist, &click)? {
+        vote::Cast::Voted {
+            poll_id,
+            poll,
+            change,
+        } => (poll_id, poll, change),
+        vote::Cast::Refused(refusal) => {
+            let text = vote::already_voted_text(&persist, &poll, user_id).unwrap_or(refusal);
+            return eph_reply(ctx, text).await;
+        }
+    };
+    let text = match (change.previous, change.new) {
+        (None, Some(new)) => vote::custom_text(&persist, &poll, VoteMessage::Voted, new.as_str()),
+        _ => None,
+    }
+    .unwrap_or_else(|| change.text.clone());
+    let text = text + &receipt::note(change.receipt.as_deref());
+    eph_reply(ctx, text).await?;
+
+    let http = &ctx.serenity_context().http;
+    vote::after(
+        http,
+        &persist,
+        poll_id,
+        &poll,
+        user_id,
+        &change,
+        ctx.channel_id(),
+    )
+    .await
+}
+
+//Shows the results of a poll, like its View Results button
+#[poise::command(slash_command, prefix_command)]
+pub async fn results(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+) -> Result<(), Error> {
+    let Some((message_id, mut poll)) = load_guild_poll(ctx, &poll_id) else {
+        return eph_reply(ctx, "No poll exists with that ID").await;
+    };
+    //Votes on Discord's own polls and reaction votes are only copied in when someone looks
+    if poll.synced() && !poll.closed {
+        let http = &ctx.serenity_context().http;
+        if let Err(e) = native::sync(http, &ctx.data().persist, message_id, &mut poll).await {
+            tracing::warn!("Failed to copy the votes of poll {message_id}: {e}");
+        }
+    }
+
+    let roles = author_roles(ctx).await;
+    if !is_observer(ctx, &poll, &roles) && !poll.can_view_results(ctx.author().id.0, &roles) {
+        return eph_reply(
+            ctx,
+            "The results of this poll are restricted until it closes",
+        )
+        .await;
+    }
+    let text = results_text(
+        ctx.serenity_context(),
+        &ctx.data().persist,
+        &poll,
+        ctx.author().id.0,
+    );
+    eph_reply(ctx, text).await
+}
+
+///Why the author can't have the bot give a role to voters. Voting hands the role to anyone, so
+///only members who could give it out themselves may pick it
+async fn vote_role_refusal(ctx: Context<'_>, role: &serenity::Role) -> Option<String> {
+    if ctx.guild_id().map(|g| g.0) == Some(role.id.0) {
+        return Some("@everyone can't be given to voters".to_string());
+    }
+    if role.managed {
+        return Some(format!(
+            "{} is managed by an integration and can't be given",
+            role.name
+        ));
+    }
+    if ctx.guild().map_or(false, |g| g.owner_id == ctx.author().id) {
+        return None;
+    }
+
+    let Some(member) = ctx.author_member().await else {
+        return Some("Couldn't check your roles, try again".to_string());
+    };
+    //Interaction members come with their permissions, otherwise they are worked out from the cache
+    let manage_roles = member
+        .permissions
+        .or_else(|| member.permissions(ctx.serenity_context()).ok())
+        .map_or(false, |p| p.manage_roles());
+    if !manage_roles {
+        return Some("You need Manage Roles to give roles to voters".to_string());
+    }
+    let highest = member
+        .highest_role_info(ctx.serenity_context())
+        .map_or(0, |(_, position)| position);
+    if role.position >= highest {
+        return Some(format!(
+            "{} must be below your highest role to be given to voters",
+            role.name
+        ));
+    }
+    None
+}
+
+///Loads a poll by its ID, only if it was posted in the guild the command is used in
+fn load_guild_poll(ctx: Context<'_>, poll_id: &str) -> Option<(u64, Poll)> {
+    let (message_id, poll) = store::load_poll(&ctx.data().persist, poll_id.parse().ok()?).ok()?;
+    (poll.guild_id == ctx.guild_id().map(|g| g.0)).then_some((message_id, poll))
+}
+
+async fn author_roles(ctx: Context<'_>) -> Vec<u64> {
+    ctx.author_member()
+        .await
+        .map(|m| m.roles.iter().map(|r| r.0).collect())
+        .unwrap_or_default()
+}
+
+///Whether the roles hold an observer role of the poll's guild, which can view but not vote
+fn is_observer(ctx: Context<'_>, poll: &Poll, roles: &[u64]) -> bool {
+    poll.guild_id.map_or(false, |g| {
+        let config = GuildConfig::load(&ctx.data().persist, g);
+        roles.iter().any(|r| config.observer_roles.contains(r))
+    })
+}
+
+//Message context menu that tells a member what they voted on a poll
+#[poise::command(context_menu_command = "My vote")]
+pub async fn my_vote(ctx: Context<'_>, message: serenity::Message) -> Result<(), Error> {
+    let Ok((_, poll)) = store::load_poll(&ctx.data().persist, message.id.0) else {
+        return eph_reply(ctx, "That message isn't a poll").await;
+    };
+
+    let user_id = ctx.author().id.0;
+    let picks: Vec<&str> = poll
+        .options_of(user_id)
+        .into_iter()
+        .map(|i| poll.options()[i].label.as_str())
+        .chain(poll.vote_of(user_id).map(Choice::as_str))
+        .collect();
+    let picked = (!picks.is_empty()).then(|| picks.join(", "));
+    let text = match picked {
+        Some(picked) => format!("You voted {picked} on \"{}\"", poll.title),
+        None => format!("You haven't voted on \"{}\"", poll.title),
+    };
+    eph_reply(ctx, text).await
+}
+
+///Fills in what a new poll takes from the guild it is created in, its locale and member count
+pub fn from_guild(ctx: Context<'_>, poll: &mut Poll) {
+    if let Some(guild) = ctx.guild() {
+        //Everyone sees the same buttons on a message, so they can't follow each viewer's locale
+        poll.locale = Some(guild.preferred_locale);
+        poll.eligible_count = Some(guild.member_count);
+    }
+}
+
+///Posts a poll for everyone and starts tracking it, with its live results message and option
+///threads if asked for. Polls by members of guilds that review polls go to the moderators instead
+pub async fn publish_poll(
+    ctx: Context<'_>,
+    mut poll: Poll,
+    results_message: bool,
+    option_threads: bool,
+) -> Result<(), Error> {
+    if !filter::screen(ctx, &poll).await? {
+        return Ok(());
+    }
+    if queue::needs_approval(ctx).await {
+        return queue::submit(ctx, poll, results_message, option_threads).await;
+    }
+    let persist = ctx.data().clone().persist;
+    lifecycle::draw_tie_seed(&mut poll);
+
+    let reply = ctx
+        .send(|r| {
+            r.embed(|e| poll.embed(e))
+                .components(|c| c.set_action_rows(poll.buttons()))
+        })
+        .await?;
+
+    let message = reply.message().await?;
+    let http = &ctx.serenity_context().http;
+    lifecycle::start_poll(
+        http,
+        &persist,
+        &message,
+        poll,
+        results_message,
+        option_threads,
+    )
+    .await
+}
+
+///Shows a poll only to its creator until they press publish
+async fn preview_poll(
+    ctx: Context<'_>,
+    mut poll: Poll,
+    results_message: bool,
+) -> Result<(), Error> {
+    let publish_id = format!("preview_publish_{}", ctx.id());
+
+    ctx.send(|r| {
+        r.ephemeral(true)
+            .content("Preview, only you can see this poll")
+            .embed(|e| poll.embed(e))
+            .components(|c| {
+                c.set_action_rows(poll.buttons()).create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.custom_id(&publish_id)
+                            .label("Publish")
+                            .style(ButtonStyle::Success)
+                    })
+                })
+            })
+    })
+    .await?;
+
+    let started = Timestamp::now().unix_timestamp();
+    let Some(interaction) = serenity::CollectComponentInteraction::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .filter(move |i| i.data.custom_id == publish_id)
+        .timeout(Duration::from_secs(600))
+        .await
+    else {
+        return Ok(());
+    };
+
+    interaction
+        .create_interaction_response(ctx.serenity_context(), |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.content("Published!")
+                        .set_embeds(Vec::new())
+                        .components(|c| c)
+                })
+        })
+        .await?;
+
+    //The deadline counts from publishing, not from when the preview was shown
+    let waited = Timestamp::now().unix_timestamp() - started;
+    poll.deadline = poll.deadline.map(|d| d + waited);
+    publish_poll(ctx, poll, results_message, false).await
+}
+
+///Suggests the guild's polls matching what was typed, open polls first. The value is the message ID
+pub async fn autocomplete_poll(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<poise::AutocompleteChoice<String>> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Vec::new();
+    };
+    let partial = partial.to_lowercase();
+
+    let mut polls: Vec<_> = store::guild_polls(&ctx.data().persist, guild_id.0)
+        .into_iter()
+        .rev()
+        .filter(|p| {
+            p.title.to_lowercase().contains(&partial)
+                || p.message_id.to_string().starts_with(&partial)
+        })
+        .collect();
+    polls.sort_by_key(|p| p.closed);
+
+    polls
+        .into_iter()
+        //Discord shows at most 25 choices, with names of at most 100 characters
+        .take(25)
+        .map(|p| {
+            let state = if p.closed { "ended" } else { "open" };
+            let title: String = p.title.chars().take(80).collect();
+            poise::AutocompleteChoice {
+                name: format!("{title} ({state})"),
+                value: p.message_id.to_string(),
+            }
+        })
+        .collect()
+}
+
+///Loads a poll by its message ID for its creator, or for the guild's moderators when allowed.
+///Replies with the reason if that fails
+pub async fn load_own_poll(
+    ctx: Context<'_>,
+    poll_id: &str,
+    moderators: bool,
+) -> Result<Option<(u64, Poll)>, Error> {
+    let Ok(message_id) = poll_id.parse::<u64>() else {
+        eph_reply(ctx, "That is not a valid message ID").await?;
+        return Ok(None);
+    };
+    let Ok((message_id, poll)) = store::load_poll(&ctx.data().persist, message_id) else {
+        eph_reply(ctx, "No poll exists with that ID").await?;
+        return Ok(None);
+    };
+
+    if poll.creator_id == ctx.author().id.0 {
+        return Ok(Some((message_id, poll)));
+    }
+    let in_guild = poll.guild_id.is_some() && poll.guild_id == ctx.guild_id().map(|g| g.0);
+    if moderators && in_guild && is_moderator(ctx).await {
+        return Ok(Some((message_id, poll)));
+    }
+
+    let text = if moderators {
+        "Only the creator of a poll or a moderator can do that"
+    } else {
+        "Only the creator of a poll can do that"
+    };
+    eph_reply(ctx, text).await?;
+    Ok(None)
+}
+
+///Records a management action on a guild poll in the audit log
+async fn audit_poll(ctx: Context<'_>, message_id: u64, poll: &Poll, action: &str) {
+    let Some(guild_id) = poll.guild_id else {
+        return;
+    };
+    let mut action = format!("{action} poll \"{}\" ({message_id})", poll.title);
+    if let Some(link) = poll.link(message_id) {
+        action.push_str(&format!(" {link}"));
+    }
+    let http = &ctx.serenity_context().http;
+
+    if let Err(e) = audit::record(
+        http,
+        &ctx.data().persist,
+        guild_id,
+        ctx.author().id.0,
+        action,
+    )
+    .await
+    {
+        tracing::warn!("Failed to record poll action in the audit log: {e}");
+    }
+}
+
+//Ends a poll, scheduling its event if it passed
+#[poise::command(slash_command, prefix_command)]
+async fn end(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+) -> Result<(), Error> {
+    let Some((message_id, mut poll)) = load_own_poll(ctx, &poll_id, true).await? else {
+        return Ok(());
+    };
+    if poll.closed {
+        return eph_reply(ctx, "That poll has already ended").await;
+    }
+
+    //Polls from before channels were recorded can only be ended from their own channel
+    poll.channel_id.get_or_insert(ctx.channel_id().0);
+    let http = &ctx.serenity_context().http;
+    let summary = lifecycle::close_poll(http, &ctx.data().persist, message_id, &mut poll).await?;
+    audit_poll(ctx, message_id, &poll, "ended").await;
+    //The reply is only seen by whoever ended the poll, so the winning time is announced to everyone
+    if let (Some(file), Some((start, _)), Some(channel_id)) = (
+        ical::attachment(&poll, message_id),
+        ical::winning_slot(&poll),
+        poll.channel_id,
+    ) {
+        ChannelId(channel_id)
+            .send_message(http, |m| {
+                m.content(format!("\"{}\" is happening <t:{start}:F>", poll.title))
+                    .allowed_mentions(|a| a.empty_parse())
+                    .add_file(file)
+            })
+            .await?;
+    }
+    eph_reply(ctx, format!("Ended poll {summary}")).await
+}
+
+//Ends every open poll in a channel, or in the whole server, after confirming
+#[poise::command(slash_command, guild_only)]
+async fn closeall(
+    ctx: Context<'_>,
+    #[description = "Only end polls in this channel"] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    if !is_moderator(ctx).await {
+        return eph_reply(ctx, "Only moderators can end every poll").await;
+    }
+    let persist = ctx.data().clone().persist;
+    let channel_id = channel.map(|c| c.id.0);
+
+    let polls: Vec<_> = store::guild_polls(&persist, ctx.guild_id().unwrap().0)
+        .into_iter()
+        .filter(|p| !p.closed && channel_id.map_or(true, |c| p.channel_id == Some(c)))
+        .collect();
+    if polls.is_empty() {
+        return eph_reply(ctx, "There are no open polls to end").await;
+    }
+
+    let scope = match channel_id {
+        Some(channel_id) => format!("<#{channel_id}>"),
+        None => "this server".to_string(),
+    };
+    let confirm_id = format!("closeall_confirm_{}", ctx.id());
+    let cancel_id = format!("closeall_cancel_{}", ctx.id());
+    ctx.send(|r| {
+        r.ephemeral(true)
+            .content(format!("End all {} open polls in {scope}?", polls.len()))
+            .components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.custom_id(&confirm_id)
+                            .label("End them")
+                            .style(ButtonStyle::Danger)
+                    })
+                    .create_button(|b| {
+                        b.custom_id(&cancel_id)
+                            .label("Cancel")
+                            .style(ButtonStyle::Secondary)
+                    })
+                })
+            })
+    })
+    .await?;
+
+    let ids = [confirm_id.clone(), cancel_id];
+    let Some(interaction) = serenity::CollectComponentInteraction::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .filter(move |i| ids.contains(&i.data.custom_id))
+        .timeout(Duration::from_secs(120))
+        .await
+    else {
+        return Ok(());
+    };
+    let confirmed = interaction.data.custom_id == confirm_id;
+    interaction
+        .create_interaction_response(ctx.serenity_context(), |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.content(if confirmed {
+                        "Ending polls..."
+                    } else {
+                        "Cancelled"
+                    })
+                    .components(|c| c)
+                })
+        })
+        .await?;
+    if !confirmed {
+        return Ok(());
+    }
+
+    let http = &ctx.serenity_context().http;
+    let mut report = Vec::new();
+    for indexed in polls {
+        let result = match store::load_poll(&persist, indexed.message_id) {
+            Ok((message_id, mut poll)) => {
+                let summary = lifecycle::close_poll(http, &persist, message_id, &mut poll).await;
+                if summary.is_ok() {
+                    audit_poll(ctx, message_id, &poll, "ended").await;
+                }
+                summary
+            }
+            Err(e) => Err(e),
+        };
+        report.push(match result {
+            Ok(summary) => format!("Ended {summary}"),
+            Err(e) => format!("Could not end \"{}\": {e}", indexed.title),
+        });
+    }
+
+    //Long reports are cut short to fit in a single message
+    let report = report.join("\n").chars().take(2000).collect::<String>();
+    eph_reply(ctx, report).await
+}
+
+//Lets a poll that was closed by mistake be voted on again
+#[poise::command(slash_command)]
+async fn reopen(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+    #[description = "Hours until it closes again, it stays open without"]
+    #[min = 1]
+    new_deadline: Option<u32>,
+) -> Result<(), Error> {
+    let Some((message_id, mut poll)) = load_own_poll(ctx, &poll_id, true).await? else {
+        return Ok(());
+    };
+    if !poll.closed {
+        return eph_reply(ctx, "That poll is still open").await;
+    }
+    if poll.native {
+        return eph_reply(ctx, "Discord's own polls can't be reopened once they end").await;
+    }
+
+    poll.channel_id.get_or_insert(ctx.channel_id().0);
+    let deadline =
+        new_deadline.map(|hours| Timestamp::now().unix_timestamp() + i64::from(hours) * 3600);
+    let http = &ctx.serenity_context().http;
+    lifecycle::reopen_poll(http, &ctx.data().persist, message_id, &mut poll, deadline).await?;
+    audit_poll(ctx, message_id, &poll, "reopened").await;
+    eph_reply(ctx, "The poll was reopened").await
+}
+
+//Lists who voted for what on your poll, unless it is anonymous
+#[poise::command(slash_command)]
+async fn breakdown(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+) -> Result<(), Error> {
+    let Some((message_id, poll)) = load_own_poll(ctx, &poll_id, false).await? else {
+        return Ok(());
+    };
+    if !poll.can_see_voters(ctx.author().id.0) {
+        return eph_reply(ctx, "That poll is anonymous, its voters can't be shown").await;
+    }
+
+    audit_poll(ctx, message_id, &poll, "viewed the voters of").await;
+    let (text, row) = voters::page(&poll, message_id, 0);
+    ctx.send(|r| {
+        r.ephemeral(true)
+            .content(text)
+            .allowed_mentions(|m| m.empty_parse())
+            .components(|c| c.add_action_row(row))
+    })
+    .await?;
+    Ok(())
+}
+
+//Lists the reasons voters gave on your poll for each choice, without who gave them
+#[poise::command(slash_command)]
+async fn reasons(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+) -> Result<(), Error> {
+    let Some((_, poll)) = load_own_poll(ctx, &poll_id, false).await? else {
+        return Ok(());
+    };
+    let digest = poll.reason_digest();
+    if digest.is_empty() {
+        let text = match poll.visibility {
+            Visibility::Anonymous => "Not enough reasons were given to show any anonymously",
+            _ => "No reasons were given on that poll",
+        };
+        return eph_reply(ctx, text).await;
+    }
+
+    let mut text = format!("Why people voted on \"{}\"", poll.title);
+    for (choice, reasons) in digest {
+        text.push_str(&format!("\n\n**{choice}** ({})", reasons.len()));
+        for reason in reasons {
+            text.push_str(&format!("\n- {}", reason.replace('\n', " ")));
+        }
+    }
+    if poll.visibility == Visibility::Anonymous {
+        text.push_str(&format!(
+            "\n\nChoices with fewer than {ANONYMOUS_REASON_MINIMUM} reasons are left out to keep \
+             voters anonymous"
+        ));
+    }
+
+    //Busy polls don't fit in a message, so their digest is attached instead
+    if text.chars().count() <= 2000 {
+        return eph_reply(ctx, text).await;
+    }
+    ctx.send(|r| {
+        r.ephemeral(true)
+            .content(format!("Why people voted on \"{}\"", poll.title))
+            .attachment(AttachmentType::Bytes {
+                data: Cow::Owned(text.into_bytes()),
+                filename: "reasons.md".to_string(),
+            })
+    })
+    .await?;
+    Ok(())
+}
+
+#[derive(poise::Modal)]
+#[name = "Edit poll"]
+struct EditModal {
+    #[name = "Title"]
+    #[max_length = 256]
+    title: String,
+    #[name = "Description"]
+    #[paragraph]
+    #[max_length = 4000]
+    description: String,
+    #[name = "Hours until the poll closes, blank for none"]
+    hours_left: Option<String>,
+}
+
+//Edits an open poll's title, description or deadline, keeping a history of the changes
+#[poise::command(slash_command)]
+async fn edit(
+    app_ctx: ApplicationContext<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+) -> Result<(), Error> {
+    let ctx = Context::Application(app_ctx);
+    let Some((message_id, poll)) = load_own_poll(ctx, &poll_id, false).await? else {
+        return Ok(());
+    };
+    if poll.closed {
+        return eph_reply(ctx, "Only open polls can be edited").await;
+    }
+    if poll.native {
+        return eph_reply(ctx, "Discord doesn't allow its own polls to be edited").await;
+    }
+
+    let now = Timestamp::now().unix_timestamp();
+    //Rounded up so an unchanged form doesn't move the deadline earlier
+    let shown_hours = poll
+        .deadline
+        .map(|d| (((d - now).max(0) + 3599) / 3600).to_string());
+    let defaults = EditModal {
+        title: poll.title.clone(),
+        description: poll.description.clone(),
+        hours_left: shown_hours.clone(),
+    };
+    let Some(form) = EditModal::execute_with_defaults(app_ctx, defaults).await? else {
+        return Ok(());
+    };
+
+    let texts = validate::text("title", &form.title, validate::TITLE_LIMIT).and_then(|title| {
+        validate::text(
+            "description",
+            &form.description,
+            validate::DESCRIPTION_LIMIT,
+        )
+        .map(|description| (title, description))
+    });
+    let (title, description) = match texts {
+        Ok(texts) => texts,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    let hours_left = form
+        .hours_left
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty());
+    let deadline = match hours_left {
+        //Left as shown, so the exact deadline is kept
+        _ if hours_left == shown_hours => poll.deadline,
+        None => None,
+        Some(hours) => match hours.parse::<u32>() {
+            Ok(hours) if hours > 0 => Some(now + i64::from(hours) * 3600),
+            _ => {
+                return eph_reply(
+                    ctx,
+                    "The hours until the poll closes must be a whole number above 0",
+                )
+                .await
+            }
+        },
+    };
+
+    //The new text has to get past the same AutoMod rules and filter as a new poll
+    let mut edited = poll.clone();
+    edited.title = title.clone();
+    edited.description = description.clone();
+    if !filter::screen_edit(ctx, &edited).await? {
+        return Ok(());
+    }
+
+    //The modal can stay open for minutes, so the edit is applied to the poll as it is now
+    //instead of the copy loaded before, keeping the votes cast in the meantime
+    let editor_id = ctx.author().id.0;
+    let channel_id = ctx.channel_id().0;
+    let persist = ctx.data().clone().persist;
+    let updated = store::update(&persist, message_id, |poll| {
+        if poll.closed {
+            return Err("The poll ended while you were editing it".to_string());
+        }
+        if title == poll.title && description == poll.description && deadline == poll.deadline {
+            return Err("Nothing was changed".to_string());
+        }
+        let old_deadline = poll.deadline;
+        poll.edits.push(PollEdit {
+            at: now,
+            editor_id,
+            old_title: std::mem::replace(&mut poll.title, title),
+            old_description: std::mem::replace(&mut poll.description, description),
+            old_deadline,
+        });
+        poll.deadline = deadline;
+        poll.channel_id.get_or_insert(channel_id);
+        Ok(old_deadline)
+    })?;
+    let (poll, old_deadline) = match updated {
+        store::Updated::Saved { poll, value, .. } => (poll, value),
+        store::Updated::Refused(refusal) => return eph_reply(ctx, refusal).await,
+    };
+
+    if deadline != old_deadline {
+        scheduler::cancel(&persist, &Job::ClosePoll { message_id })?;
+        scheduler::cancel(&persist, &Job::Warn { message_id })?;
+        if let Some(deadline) = deadline {
+            scheduler::schedule(&persist, deadline, Job::ClosePoll { message_id })?;
+        }
+        scheduler::schedule_warning(&persist, message_id, &poll, now)?;
+    }
+    poll.update_messages(&ctx.serenity_context().http, message_id)
+        .await?;
+    eph_reply(ctx, "The poll was edited").await
+}
+
+//Shows how a poll was changed since it was created
+#[poise::command(slash_command)]
+async fn history(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+) -> Result<(), Error> {
+    //Titles and descriptions before an edit may have been taken down on purpose
+    let Some((_, poll)) = load_own_poll(ctx, &poll_id, true).await? else {
+        return Ok(());
+    };
+    if poll.edits.is_empty() {
+        return eph_reply(ctx, "That poll has never been edited").await;
+    }
+
+    let describe_deadline = |deadline: Option<i64>| match deadline {
+        Some(deadline) => format!("<t:{deadline}:f>"),
+        None => "none".to_string(),
+    };
+    let text = poll
+        .edits
+        .iter()
+        .map(|edit| {
+            format!(
+                "<t:{}:f> <@{}> edited it, before it was titled \"{}\" with deadline {}",
+                edit.at,
+                edit.editor_id,
+                edit.old_title,
+                describe_deadline(edit.old_deadline)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    //Long histories are cut short to fit in a single message
+    eph_reply(ctx, text.chars().take(2000).collect::<String>()).await
+}
+
+//Stops or resumes voting on a poll without ending it
+#[poise::command(slash_command)]
+async fn lock(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+) -> Result<(), Error> {
+    let Some((message_id, _)) = load_own_poll(ctx, &poll_id, true).await? else {
+        return Ok(());
+    };
+
+    let channel_id = ctx.channel_id().0;
+    let updated = store::update(&ctx.data().persist, message_id, |poll| {
+        if poll.closed {
+            return Err("That poll has already ended".to_string());
+        }
+        poll.locked = !poll.locked;
+        poll.channel_id.get_or_insert(channel_id);
+        Ok(())
+    })?;
+    let poll = match updated {
+        store::Updated::Saved { poll, .. } => poll,
+        store::Updated::Refused(refusal) => return eph_reply(ctx, refusal).await,
+    };
+    poll.update_messages(&ctx.serenity_context().http, message_id)
+        .await?;
+
+    let action = if poll.locked { "locked" } else { "unlocked" };
+    audit_poll(ctx, message_id, &poll, action).await;
+    eph_reply(ctx, format!("The poll is now {action}")).await
+}
+
+//Rearranges and recolours a poll's buttons, or hides its results button
+#[poise::command(slash_command)]
+async fn buttons(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+    #[description = "Order of a yes/no poll's buttons, like no, yes, results"] order: Option<
+        String,
+    >,
+    #[description = "Colour of the yes button"] yes_color: Option<ButtonColor>,
+    #[description = "Colour of the no button"] no_color: Option<ButtonColor>,
+    #[description = "Colour of the results button"] results_color: Option<ButtonColor>,
+    #[description = "Show the View Results button, members can use /results either way"]
+    results_button: Option<bool>,
+) -> Result<(), Error> {
+    let Some((message_id, _)) = load_own_poll(ctx, &poll_id, false).await? else {
+        return Ok(());
+    };
+    let order = match order.map(|o| PollButton::parse_order(&o)).transpose() {
+        Ok(order) => order,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+
+    let channel_id = ctx.channel_id().0;
+    let update = |poll: &mut Poll| {
+        let layout = &mut poll.layout;
+        if let Some(order) = order {
+            layout.order = order;
+        }
+        layout.yes = yes_color.unwrap_or(layout.yes);
+        layout.no = no_color.unwrap_or(layout.no);
+        layout.results = results_color.or(layout.results);
+        layout.show_results = results_button.unwrap_or(layout.show_results);
+        poll.channel_id.get_or_insert(channel_id);
+        Ok(())
+    };
+    let (_, poll, _) = store::update(&ctx.data().persist, message_id, update)?.saved()?;
+    poll.update_messages(&ctx.serenity_context().http, message_id)
+        .await?;
+
+    let text = match poll.layout.show_results {
+        true => "Updated the poll's buttons",
+        false => "Updated the poll's buttons, members can see its results with /results",
+    };
+    eph_reply(ctx, text).await
+}
+
+//Sets what you are told about while your poll runs, leave everything empty to stop being told
+#[poise::command(slash_command)]
+async fn notify(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+    #[description = "Voters the poll needs"]
+    #[min = 1]
+    quorum: Option<u32>,
+    #[description = "Voter counts to be told about, like 10, 50, 100"] milestones: Option<String>,
+    #[description = "Be told when a different choice takes the lead"] leader_changes: Option<bool>,
+    #[description = "Mention you in the poll's channel instead of DMing you"] in_channel: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let Some((message_id, _)) = load_own_poll(ctx, &poll_id, false).await? else {
+        return Ok(());
+    };
+
+    let mut counts = Vec::new();
+    for count in milestones.iter().flat_map(|m| m.split(',')) {
+        match count.trim().parse::<u32>() {
+            Ok(count) if count > 0 => counts.push(count),
+            _ => return eph_reply(ctx, "Milestones must be vote counts like 10, 50, 100").await,
+        }
+    }
+    counts.sort_unstable();
+    counts.dedup();
+
+    let updated = store::update(&ctx.data().persist, message_id, |poll| {
+        if poll.closed {
+            return Err("That poll has already ended".to_string());
+        }
+        let mut settings = poll.milestones.clone();
+        settings.quorum = quorum;
+        settings.counts = counts;
+        settings.leader_changes = leader_changes.unwrap_or(false);
+        settings.in_channel = in_channel.unwrap_or(false);
+        //Only what the poll reaches from now on is announced
+        settings.catch_up(poll);
+        poll.milestones = settings;
+        Ok(())
+    })?;
+    let poll = match updated {
+        store::Updated::Saved { poll, .. } => poll,
+        store::Updated::Refused(refusal) => return eph_reply(ctx, refusal).await,
+    };
+    if poll.description.contains("{quorum}") {
+        poll.update_messages(&ctx.serenity_context().http, message_id)
+            .await?;
+    }
+
+    let mut watched = Vec::new();
+    if let Some(quorum) = quorum {
+        watched.push(format!("a quorum of {quorum}"));
+    }
+    if !poll.milestones.counts.is_empty() {
+        let counts: Vec<String> = poll
+            .milestones
+            .counts
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        watched.push(format!("{} voters", counts.join(", ")));
+    }
+    if poll.milestones.leader_changes {
+        watched.push("leader changes".to_string());
+    }
+    match watched.is_empty() {
+        true => eph_reply(ctx, "You won't be told about this poll's progress").await,
+        false => {
+            let place = if poll.milestones.in_channel {
+                "in its channel"
+            } else {
+                "by DM"
+            };
+            eph_reply(
+                ctx,
+                format!("You will be told about {} {place}", watched.join(", ")),
+            )
+            .await
+        }
+    }
+}
+
+//Deletes a poll and every copy of it
+#[poise::command(slash_command)]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+) -> Result<(), Error> {
+    let Some((message_id, mut poll)) = load_own_poll(ctx, &poll_id, true).await? else {
+        return Ok(());
+    };
+
+    //The records go first, so deleting the messages isn't also handled as a deletion by hand
+    let persist = ctx.data().clone().persist;
+    scheduler::cancel_poll(&persist, message_id)?;
+    store::delete_poll(&persist, message_id, &poll)?;
+
+    poll.channel_id.get_or_insert(ctx.channel_id().0);
+    let http = &ctx.serenity_context().http;
+    let results = poll.results_message.clone();
+    for message in poll.messages(message_id).into_iter().chain(results) {
+        //Messages someone already deleted by hand are fine to skip
+        let _ = ChannelId(message.channel_id)
+            .delete_message(http, message.message_id)
+            .await;
+    }
+
+    audit_poll(ctx, message_id, &poll, "deleted").await;
+    eph_reply(ctx, "Deleted the poll").await
+}
+
+//Picks the winner of a tied poll that was left for its creator to decide
+#[poise::command(slash_command)]
+async fn decide(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+    #[description = "The winning choice"] winner: Choice,
+) -> Result<(), Error> {
+    let Some((message_id, _)) = load_own_poll(ctx, &poll_id, false).await? else {
+        return Ok(());
+    };
+    let updated = store::update(&ctx.data().persist, message_id, |poll| {
+        if !matches!(
+            poll.tie_resolution,
+            Some(TieResolution::Creator { winner: None })
+        ) {
+            return Err("That poll is not waiting for you to break a tie".to_string());
+        }
+        poll.tie_resolution = Some(TieResolution::Creator {
+            winner: Some(winner),
+        });
+        Ok(())
+    })?;
+    let poll = match updated {
+        store::Updated::Saved { poll, .. } => poll,
+        store::Updated::Refused(refusal) => return eph_reply(ctx, refusal).await,
+    };
+    let http = &ctx.serenity_context().http;
+    poll.update_messages(http, message_id).await?;
+
+    let note = lifecycle::schedule_event(http, &poll).await;
+    eph_reply(ctx, format!("{} wins the tie{note}", winner.as_str())).await
+}
+
+//Posts a copy of a poll in another channel, sharing the original's votes
+#[poise::command(slash_command)]
+async fn crosspost(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+    #[description = "Channel to post the copy in"] channel: serenity::GuildChannel,
+) -> Result<(), Error> {
+    let Some((message_id, mut poll)) = load_own_poll(ctx, &poll_id, false).await? else {
+        return Ok(());
+    };
+    if poll.closed {
+        return eph_reply(ctx, "That poll has already ended").await;
+    }
+    //Copies would take votes on the bot's buttons, which Discord's tally never sees
+    if poll.synced() {
+        return eph_reply(ctx, "Polls voted on outside the bot can't be crossposted").await;
+    }
+    if ctx.guild_id() != Some(channel.guild_id) {
+        let text = "Copies can only be posted in this server, use /poll federate for others";
+        return eph_reply(ctx, text).await;
+    }
+    let config = GuildConfig::load(&ctx.data().persist, channel.guild_id.0);
+    if let Some(refusal) = config.channel_refusal(channel.id.0) {
+        return eph_reply(ctx, refusal).await;
+    }
+    let can_post = author_permissions_in(ctx, &channel)
+        .await
+        .map_or(false, |(_, p)| p.view_channel() && p.send_messages());
+    if !can_post {
+        return eph_reply(ctx, "You can't send messages in that channel").await;
+    }
+
+    post_copy(ctx, message_id, &mut poll, channel.id).await?;
+    eph_reply(
+        ctx,
+        format!("Posted a copy of the poll in <#{}>", channel.id),
+    )
+    .await
+}
+
+///The author's member and permissions in a channel, which may be in another guild than the command
+async fn author_permissions_in(
+    ctx: Context<'_>,
+    channel: &serenity::GuildChannel,
+) -> Option<(serenity::Member, serenity::Permissions)> {
+    let member = channel
+        .guild_id
+        .member(ctx.serenity_context(), ctx.author().id)
+        .await
+        .ok()?;
+    let guild = ctx.serenity_context().cache.guild(channel.guild_id)?;
+    let permissions = guild.user_permissions_in(channel, &member).ok()?;
+    Some((member, permissions))
+}
+
+///Posts a copy of a poll that shares its votes, then shows the merged tally everywhere
+async fn post_copy(
+    ctx: Context<'_>,
+    message_id: u64,
+    poll: &mut Poll,
+    channel_id: ChannelId,
+) -> Result<(), Error> {
+    let copy = channel_id
+        .send_message(ctx.serenity_context(), |m| {
+            m.embed(|e| poll.embed(e))
+                .components(|c| c.set_action_rows(poll.buttons()))
+        })
+        .await?;
+
+    let persist = ctx.data().clone().persist;
+    store::save_copy(&persist, copy.id.0, message_id)?;
+    let federated = poll.federated;
+    let update = |poll: &mut Poll| {
+        poll.federated |= federated;
+        poll.copies.push(PollMessage {
+            channel_id: channel_id.0,
+            message_id: copy.id.0,
+        });
+        Ok(())
+    };
+    *poll = store::update(&persist, message_id, update)?.saved()?.1;
+
+    poll.update_messages(&ctx.serenity_context().http, message_id)
+        .await
+}
+
+//Mirrors a poll into a channel of a partner server, with a per-server breakdown of the shared tally
+#[poise::command(slash_command)]
+async fn federate(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+    #[description = "ID of the channel in the partner server"] channel_id: String,
+) -> Result<(), Error> {
+    let Some((message_id, mut poll)) = load_own_poll(ctx, &poll_id, false).await? else {
+        return Ok(());
+    };
+    if poll.closed {
+        return eph_reply(ctx, "That poll has already ended").await;
+    }
+    if poll.synced() {
+        return eph_reply(ctx, "Polls voted on outside the bot can't be federated").await;
+    }
+
+    let channel = match channel_id.parse::<u64>().map(ChannelId) {
+        Ok(channel_id) => channel_id.to_channel(ctx.serenity_context()).await.ok(),
+        Err(_) => None,
+    };
+    let Some(channel) = channel.and_then(|c| c.guild()) else {
+        return eph_reply(ctx, "I can't see a server channel with that ID").await;
+    };
+    let Some((member, permissions)) = author_permissions_in(ctx, &channel).await else {
+        return eph_reply(ctx, "You need Manage Server in the partner server").await;
+    };
+    if !member
+        .permissions(ctx.serenity_context())
+        .map_or(false, |p| p.manage_guild())
+    {
+        return eph_reply(ctx, "You need Manage Server in the partner server").await;
+    }
+    if !permissions.view_channel() || !permissions.send_messages() {
+        return eph_reply(ctx, "You can't send messages in that channel").await;
+    }
+
+    //The copy is a new poll for the partner server, its own channel rules, queue and filter apply
+    let config = GuildConfig::load(&ctx.data().persist, channel.guild_id.0);
+    if let Some(refusal) = config.channel_refusal(channel.id.0) {
+        return eph_reply(ctx, refusal).await;
+    }
+    if config.approval && !config.moderates(&member, permissions) {
+        let text = "The partner server approves polls first, ask one of its moderators to federate";
+        return eph_reply(ctx, text).await;
+    }
+    let roles: Vec<u64> = member.roles.iter().map(|r| r.0).collect();
+    if !filter::screen_copy(ctx, &poll, &channel, &roles).await? {
+        return Ok(());
+    }
+
+    //Votes cast before federation count towards the poll's own guild
+    poll.federated = true;
+    post_copy(ctx, message_id, &mut poll, channel.id).await?;
+    eph_reply(ctx, format!("Mirrored the poll into <#{}>", channel.id)).await
+}
+
+//Checks a receipt from an anonymous poll, telling its holder which choice it was counted for
+#[poise::command(slash_command)]
+async fn receipt(
+    ctx: Context<'_>,
+    #[description = "The poll"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+    #[description = "Receipt code you got when voting"] code: String,
+) -> Result<(), Error> {
+    let Some((_, poll)) = poll_id
+        .parse()
+        .ok()
+        .and_then(|id| store::load_poll(&ctx.data().persist, id).ok())
+    else {
+        return eph_reply(ctx, "No poll exists with that ID").await;
+    };
+    if poll.visibility != Visibility::Anonymous {
+        return eph_reply(ctx, "Only anonymous polls hand out receipts").await;
+    }
+
+    match receipt::counted_choice(&poll, &code) {
+        Some(choice) => eph_reply(ctx, format!("Your vote for {choice} was counted")).await,
+        None => {
+            eph_reply(
+                ctx,
+                "No counted vote matches that receipt, it may have been changed or retracted",
+            )
+            .await
+        }
+    }
+}
@@ -0,0 +1,703 @@
+use poise::serenity_prelude::{self as serenity, RoleId};
+use serde::{Deserialize, Serialize};
+use shuttle_persist::PersistInstance;
+
+use crate::commands::eph_reply;
+use crate::filter::{ContentFilter, FilterAction};
+use crate::prefs::ResultsFormat;
+use crate::{audit, sheets, Context, Error};
+
+///Per guild settings, changed with /pollconfig
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct GuildConfig {
+    //u64 = RoleId, members with it can manage every poll in the guild
+    #[serde(default)]
+    pub moderator_role: Option<u64>,
+    //u64 = ChannelId, poll moderation is logged here
+    #[serde(default)]
+    pub log_channel: Option<u64>,
+    #[serde(default)]
+    pub creators: CreatorPolicy,
+    //u64 = RoleId, used by CreatorPolicy::Roles
+    #[serde(default)]
+    pub creator_roles: Vec<u64>,
+    //u64 = ChannelId, when not empty polls can only be created in these channels
+    #[serde(default)]
+    pub allowed_channels: Vec<u64>,
+    //u64 = ChannelId
+    #[serde(default)]
+    pub blocked_channels: Vec<u64>,
+    //Seconds a voter can undo their vote for on polls that don't allow changes, unset uses the default
+    #[serde(default)]
+    pub undo_seconds: Option<u32>,
+    //u64 = RoleId, members with these roles can view results but never vote
+    #[serde(default)]
+    pub observer_roles: Vec<u64>,
+    //Prefix for text commands, unset uses the default
+    #[serde(default)]
+    pub prefix: Option<String>,
+    //Templates replacing the default vote confirmation texts, unset uses the defaults
+    #[serde(default)]
+    pub voted_message: Option<String>,
+    #[serde(default)]
+    pub already_voted_message: Option<String>,
+    //Members can pick their own format with /preferences
+    #[serde(default)]
+    pub results_format: ResultsFormat,
+    //Google spreadsheet ID closed polls are logged to
+    #[serde(default)]
+    pub spreadsheet: Option<String>,
+    //u64 = ChannelId, polls the bot's owners run across every guild are posted here
+    #[serde(default)]
+    pub announcement_channel: Option<u64>,
+    //Polls by members that aren't moderators wait in /pollqueue until a moderator approves them
+    #[serde(default)]
+    pub approval: bool,
+    #[serde(default)]
+    pub filter: ContentFilter,
+    //u64 = ChannelId, forum every closed poll is posted to with its results
+    #[serde(default)]
+    pub archive_forum: Option<u64>,
+}
+
+pub const DEFAULT_PREFIX: &str = "!";
+
+const DEFAULT_UNDO_SECONDS: u32 = 60;
+
+#[derive(poise::ChoiceParameter)]
+pub enum ChannelRule {
+    #[name = "Allow polls in the channel"]
+    Allow,
+    #[name = "Block polls in the channel"]
+    Block,
+    #[name = "Clear every channel rule"]
+    Clear,
+}
+
+///A vote confirmation text a guild can replace with its own
+#[derive(Clone, Copy, poise::ChoiceParameter)]
+pub enum VoteMessage {
+    #[name = "Sent after voting"]
+    Voted,
+    #[name = "Sent when voting again on a poll that doesn't allow changes"]
+    AlreadyVoted,
+}
+
+///Who may create polls in a guild
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum CreatorPolicy {
+    #[default]
+    Everyone,
+    #[name = "Members with a creator role"]
+    Roles,
+    #[name = "Moderators only"]
+    Moderators,
+}
+
+fn key(guild_id: u64) -> String {
+    format!("config_{guild_id}")
+}
+
+impl GuildConfig {
+    ///Why polls can't be created in a channel, if they can't
+    pub fn channel_refusal(&self, channel_id: u64) -> Option<String> {
+        let blocked = self.blocked_channels.contains(&channel_id);
+        let not_allowed =
+            !self.allowed_channels.is_empty() && !self.allowed_channels.contains(&channel_id);
+        if !blocked && !not_allowed {
+            return None;
+        }
+
+        let mut text = "Polls can't be created in this channel".to_string();
+        if !self.allowed_channels.is_empty() {
+            text.push_str(", try ");
+            text.push_str(&mention_channels(&self.allowed_channels));
+        }
+        Some(text)
+    }
+
+    ///Whether a member of the guild has its moderator role or Manage Messages
+    pub fn moderates(&self, member: &serenity::Member, permissions: serenity::Permissions) -> bool {
+        let has_role = self
+            .moderator_role
+            .map_or(false, |role| member.roles.contains(&RoleId(role)));
+        has_role || permissions.manage_messages()
+    }
+
+    ///Seconds a vote can be undone for, 0 when undoing is turned off
+    pub fn undo_window(&self) -> u32 {
+        self.undo_seconds.unwrap_or(DEFAULT_UNDO_SECONDS)
+    }
+
+    ///The guild's template for a vote message, if it set one
+    pub fn vote_message(&self, message: VoteMessage) -> Option<&str> {
+        match message {
+            VoteMessage::Voted => self.voted_message.as_deref(),
+            VoteMessage::AlreadyVoted => self.already_voted_message.as_deref(),
+        }
+    }
+
+    ///The guild's settings, or the defaults if it never changed any
+    pub fn load(persist: &PersistInstance, guild_id: u64) -> Self {
+        persist.load(&key(guild_id)).unwrap_or_default()
+    }
+
+    pub fn save(&self, persist: &PersistInstance, guild_id: u64) -> Result<(), Error> {
+        persist.save(&key(guild_id), self)?;
+        Ok(())
+    }
+}
+
+///Whether the author can manage every poll in the guild, through Manage Messages or the moderator role
+pub async fn is_moderator(ctx: Context<'_>) -> bool {
+    let (Some(guild_id), Some(member)) = (ctx.guild_id(), ctx.author_member().await) else {
+        return false;
+    };
+
+    let config = GuildConfig::load(&ctx.data().persist, guild_id.0);
+    //Interaction members come with their permissions, otherwise they are worked out from the cache
+    let permissions = member
+        .permissions
+        .or_else(|| member.permissions(ctx.serenity_context()).ok());
+    config.moderates(
+        &member,
+        permissions.unwrap_or_else(serenity::Permissions::empty),
+    )
+}
+
+///Command check letting only the members the guild allows create polls
+pub async fn can_create_polls(ctx: Context<'_>) -> Result<bool, Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(true);
+    };
+    let config = GuildConfig::load(&ctx.data().persist, guild_id.0);
+
+    let allowed = match config.creators {
+        CreatorPolicy::Everyone => true,
+        CreatorPolicy::Moderators => is_moderator(ctx).await,
+        CreatorPolicy::Roles => {
+            let has_role = ctx.author_member().await.map_or(false, |m| {
+                m.roles.iter().any(|r| config.creator_roles.contains(&r.0))
+            });
+            has_role || is_moderator(ctx).await
+        }
+    };
+    if !allowed {
+        eph_reply(ctx, "You are not allowed to create polls in this server").await?;
+        return Ok(false);
+    }
+
+    if let Some(refusal) = config.channel_refusal(ctx.channel_id().0) {
+        eph_reply(ctx, refusal).await?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+///Fills in a vote message template's {choice}, {title} and {count}
+pub fn fill_template(template: &str, choice: &str, title: &str, count: usize) -> String {
+    template
+        .replace("{choice}", choice)
+        .replace("{title}", title)
+        .replace("{count}", &count.to_string())
+}
+
+fn mention_channels(channels: &[u64]) -> String {
+    channels
+        .iter()
+        .map(|c| format!("<#{c}>"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+//Parent command, only its subcommands can be invoked
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands(
+        "moderators",
+        "logs",
+        "creators",
+        "creatorrole",
+        "channels",
+        "observerrole",
+        "undo",
+        "prefix",
+        "messages",
+        "results",
+        "sheet",
+        "announcements",
+        "approval",
+        "filter",
+        "archive",
+        "audit"
+    )
+)]
+pub async fn pollconfig(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+//Sets the role that can manage every poll, leave empty to only allow Manage Messages
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn moderators(
+    ctx: Context<'_>,
+    #[description = "Role that can manage every poll"] role: Option<serenity::Role>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.moderator_role = role.as_ref().map(|r| r.id.0);
+    config.save(&persist, guild_id)?;
+
+    match role {
+        Some(role) => eph_reply(ctx, format!("{} can now manage every poll", role.name)).await,
+        None => eph_reply(ctx, "Only Manage Messages can manage every poll now").await,
+    }
+}
+
+//Sets the channel poll moderation is logged in, leave empty to stop logging to a channel
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn logs(
+    ctx: Context<'_>,
+    #[description = "Channel to log poll moderation in"] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.log_channel = channel.as_ref().map(|c| c.id.0);
+    config.save(&persist, guild_id)?;
+
+    match channel {
+        Some(channel) => {
+            eph_reply(
+                ctx,
+                format!("Poll moderation is logged in <#{}>", channel.id),
+            )
+            .await
+        }
+        None => eph_reply(ctx, "Poll moderation is no longer logged to a channel").await,
+    }
+}
+
+//Sets who may create polls
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn creators(
+    ctx: Context<'_>,
+    #[description = "Who may create polls"] policy: CreatorPolicy,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.creators = policy;
+    config.save(&persist, guild_id)?;
+
+    let text = match policy {
+        CreatorPolicy::Everyone => "Everyone can create polls",
+        CreatorPolicy::Roles => "Members with a creator role and moderators can create polls",
+        CreatorPolicy::Moderators => "Only moderators can create polls",
+    };
+    eph_reply(ctx, text).await
+}
+
+//Adds a creator role, or removes it if it already is one
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn creatorrole(
+    ctx: Context<'_>,
+    #[description = "Role that may create polls"] role: serenity::Role,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    let added = if config.creator_roles.contains(&role.id.0) {
+        config.creator_roles.retain(|r| *r != role.id.0);
+        false
+    } else {
+        config.creator_roles.push(role.id.0);
+        true
+    };
+    config.save(&persist, guild_id)?;
+
+    let mut text = if added {
+        format!("{} can now create polls", role.name)
+    } else {
+        format!("{} can no longer create polls", role.name)
+    };
+    if config.creators != CreatorPolicy::Roles {
+        text.push_str(", once creation is limited to creator roles with /pollconfig creators");
+    }
+    eph_reply(ctx, text).await
+}
+
+//Makes a role an observer that can view results but not vote, or stops it being one
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn observerrole(
+    ctx: Context<'_>,
+    #[description = "Role that may not vote"] role: serenity::Role,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    let added = if config.observer_roles.contains(&role.id.0) {
+        config.observer_roles.retain(|r| *r != role.id.0);
+        false
+    } else {
+        config.observer_roles.push(role.id.0);
+        true
+    };
+    config.save(&persist, guild_id)?;
+
+    let text = if added {
+        format!("{} can now only observe polls", role.name)
+    } else {
+        format!("{} can vote on polls again", role.name)
+    };
+    eph_reply(ctx, text).await
+}
+
+//Allows or blocks poll creation in a channel, using a rule again on the same channel undoes it
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn channels(
+    ctx: Context<'_>,
+    #[description = "What to do"] rule: ChannelRule,
+    #[description = "Channel the rule is for"] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+    let mut config = GuildConfig::load(&persist, guild_id);
+
+    let list = match rule {
+        ChannelRule::Allow => &mut config.allowed_channels,
+        ChannelRule::Block => &mut config.blocked_channels,
+        ChannelRule::Clear => {
+            config.allowed_channels.clear();
+            config.blocked_channels.clear();
+            config.save(&persist, guild_id)?;
+            return eph_reply(ctx, "Polls can be created in every channel again").await;
+        }
+    };
+    let Some(channel) = channel else {
+        return eph_reply(ctx, "Pick the channel the rule is for").await;
+    };
+    if list.contains(&channel.id.0) {
+        list.retain(|c| *c != channel.id.0);
+    } else {
+        list.push(channel.id.0);
+    }
+    config.save(&persist, guild_id)?;
+
+    let describe = |channels: &[u64]| {
+        if channels.is_empty() {
+            "none".to_string()
+        } else {
+            mention_channels(channels)
+        }
+    };
+    eph_reply(
+        ctx,
+        format!(
+            "Allowed channels: {}\nBlocked channels: {}",
+            describe(&config.allowed_channels),
+            describe(&config.blocked_channels)
+        ),
+    )
+    .await
+}
+
+//Sets how long voters can undo a vote on polls that don't allow changes, 0 turns undoing off
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn undo(
+    ctx: Context<'_>,
+    #[description = "Seconds to allow undoing for"]
+    #[max = 600]
+    seconds: u32,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.undo_seconds = Some(seconds);
+    config.save(&persist, guild_id)?;
+
+    match seconds {
+        0 => eph_reply(ctx, "Votes can no longer be undone").await,
+        _ => eph_reply(ctx, format!("Votes can be undone for {seconds} seconds")).await,
+    }
+}
+
+//Sets the prefix of text commands like !vote, leave empty to use the default
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn prefix(
+    ctx: Context<'_>,
+    #[description = "Prefix of text commands"]
+    #[max_length = 5]
+    prefix: Option<String>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let prefix = prefix
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.prefix = prefix.clone();
+    config.save(&persist, guild_id)?;
+
+    let prefix = prefix.as_deref().unwrap_or(DEFAULT_PREFIX);
+    eph_reply(
+        ctx,
+        format!("Text commands now start with {prefix}, like {prefix}vote"),
+    )
+    .await
+}
+
+//Replaces a vote confirmation text, {choice}, {title} and {count} are filled in. Leave empty to use the default
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn messages(
+    ctx: Context<'_>,
+    #[description = "Text to replace"] message: VoteMessage,
+    #[description = "New text, can use {choice}, {title} and {count}"]
+    #[max_length = 200]
+    text: Option<String>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let text = text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    let mut config = GuildConfig::load(&persist, guild_id);
+    match message {
+        VoteMessage::Voted => config.voted_message = text.clone(),
+        VoteMessage::AlreadyVoted => config.already_voted_message = text.clone(),
+    }
+    config.save(&persist, guild_id)?;
+
+    match text {
+        Some(text) => {
+            let example = fill_template(&text, "Yes", "Pizza on Fridays?", 12);
+            eph_reply(
+                ctx,
+                format!("Voters will now see messages like:\n{example}"),
+            )
+            .await
+        }
+        None => eph_reply(ctx, "Voters will see the default message again").await,
+    }
+}
+
+//Sets how poll results are written out for members who haven't picked a format
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn results(
+    ctx: Context<'_>,
+    #[description = "How to write out results"] format: ResultsFormat,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.results_format = format;
+    config.save(&persist, guild_id)?;
+
+    let text = match format {
+        ResultsFormat::Compact => "Results are compact by default",
+        ResultsFormat::PlainText => "Results are written out as plain text by default",
+    };
+    eph_reply(ctx, text).await
+}
+
+//Logs the result of every poll closing to a Google spreadsheet, leave empty to stop
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn sheet(
+    ctx: Context<'_>,
+    #[description = "Link to the spreadsheet"] link: Option<String>,
+) -> Result<(), Error> {
+    let Some(email) = sheets::service_email() else {
+        return eph_reply(ctx, "Google Sheets logging isn't set up for this bot").await;
+    };
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let spreadsheet = match link.as_deref().map(sheets::spreadsheet_id) {
+        Some(Some(id)) => Some(id),
+        Some(None) => return eph_reply(ctx, "That isn't a link to a Google spreadsheet").await,
+        None => None,
+    };
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.spreadsheet = spreadsheet.clone();
+    config.save(&persist, guild_id)?;
+
+    match spreadsheet {
+        Some(_) => {
+            eph_reply(
+                ctx,
+                format!(
+                    "Results are now logged to the spreadsheet, share it with {email} as an editor"
+                ),
+            )
+            .await
+        }
+        None => eph_reply(ctx, "Results are no longer logged to a spreadsheet").await,
+    }
+}
+
+//Sets the channel polls from the bot's developers are posted in, leave empty to not receive them
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn announcements(
+    ctx: Context<'_>,
+    #[description = "Channel to post polls about the bot in"] channel: Option<
+        serenity::GuildChannel,
+    >,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.announcement_channel = channel.as_ref().map(|c| c.id.0);
+    config.save(&persist, guild_id)?;
+
+    match channel {
+        Some(channel) => {
+            eph_reply(
+                ctx,
+                format!("Polls about the bot will be posted in <#{}>", channel.id),
+            )
+            .await
+        }
+        None => eph_reply(ctx, "Polls about the bot will no longer be posted here").await,
+    }
+}
+
+//Makes polls by members that aren't moderators wait for a moderator's approval in /pollqueue
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn approval(
+    ctx: Context<'_>,
+    #[description = "Whether polls need approval"] required: bool,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.approval = required;
+    config.save(&persist, guild_id)?;
+
+    match required {
+        true => eph_reply(ctx, "Polls by members now need a moderator's approval").await,
+        //Polls already waiting stay in the queue until a moderator reviews them
+        false => eph_reply(ctx, "Polls are now posted right away").await,
+    }
+}
+
+///Adds an entry to a list, or removes it if it is already there
+fn toggle(list: &mut Vec<String>, entry: String) {
+    if list.contains(&entry) {
+        list.retain(|e| *e != entry);
+    } else {
+        list.push(entry);
+    }
+}
+
+//Bans a word or regular expression from poll text, using one again unbans it. Shows the filter
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn filter(
+    ctx: Context<'_>,
+    #[description = "Word to ban, or unban if it already is"] word: Option<String>,
+    #[description = "Regular expression to ban, or unban if it already is"] pattern: Option<String>,
+    #[description = "What happens to polls that match"] action: Option<FilterAction>,
+    #[description = "Ping the moderator role in the log channel when a poll matches"]
+    notify_moderators: Option<bool>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+    if let Some(Err(e)) = pattern.as_deref().map(regex::Regex::new) {
+        return eph_reply(ctx, format!("That isn't a valid regular expression: {e}")).await;
+    }
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    let filter = &mut config.filter;
+    if let Some(word) = word {
+        toggle(&mut filter.words, word.trim().to_lowercase());
+    }
+    if let Some(pattern) = pattern {
+        toggle(&mut filter.patterns, pattern);
+    }
+    if let Some(action) = action {
+        filter.action = action;
+    }
+    if let Some(notify_moderators) = notify_moderators {
+        filter.notify_moderators = notify_moderators;
+    }
+    config.save(&persist, guild_id)?;
+
+    let filter = &config.filter;
+    if filter.is_empty() {
+        return eph_reply(ctx, "Nothing is filtered from polls").await;
+    }
+    let list = |entries: &[String]| match entries.is_empty() {
+        true => "None".to_string(),
+        false => entries
+            .iter()
+            .map(|e| format!("`{e}`"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let outcome = match filter.action {
+        FilterAction::Reject => "refused",
+        FilterAction::Flag => "posted and flagged in the log",
+    };
+    let mut text = format!(
+        "Banned words: {}\nBanned patterns: {}\nPolls that match are {outcome}",
+        list(&filter.words),
+        list(&filter.patterns)
+    );
+    if filter.notify_moderators {
+        text.push_str(", pinging the moderator role");
+    }
+    eph_reply(ctx, text).await
+}
+
+//Posts every closed poll with its results to a forum channel, leave empty to stop
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn archive(
+    ctx: Context<'_>,
+    #[description = "Forum channel to post closed polls in"]
+    #[channel_types("Forum")]
+    channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    let mut config = GuildConfig::load(&persist, guild_id);
+    config.archive_forum = channel.as_ref().map(|c| c.id.0);
+    config.save(&persist, guild_id)?;
+
+    match channel {
+        Some(channel) => {
+            eph_reply(
+                ctx,
+                format!("Closed polls will be posted in <#{}>", channel.id),
+            )
+            .await
+        }
+        None => eph_reply(ctx, "Closed polls will no longer be archived").await,
+    }
+}
+
+//Shows the most recent poll moderation
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn audit(ctx: Context<'_>) -> Result<(), Error> {
+    let entries = audit::recent(&ctx.data().persist, ctx.guild_id().unwrap().0, 10);
+    if entries.is_empty() {
+        return eph_reply(ctx, "Nothing has been logged yet").await;
+    }
+
+    let text = entries
+        .iter()
+        .map(|e| format!("<t:{}:f> {} {}", e.at, e.actor(), e.action))
+        .collect::<Vec<_>>()
+        .join("\n");
+    eph_reply(ctx, text).await
+}
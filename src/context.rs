@@ -0,0 +1,72 @@
+use poise::serenity_prelude::{ChannelId, Permissions};
+
+use crate::poll::{message_link, PollContext};
+use crate::{validate, Context};
+
+//Long messages are cut short so the quote doesn't crowd out the poll
+const QUOTE_LIMIT: usize = 300;
+
+///The guild, channel and message IDs in a link like https://discord.com/channels/1/2/3
+fn parse_link(link: &str) -> Option<(u64, u64, u64)> {
+    let link = link.trim();
+    let path = [
+        "discord.com",
+        "ptb.discord.com",
+        "canary.discord.com",
+        "discordapp.com",
+    ]
+    .iter()
+    .find_map(|host| link.strip_prefix(&format!("https://{host}/channels/")))?;
+    let mut ids = path.split('/').map(|id| id.parse().ok());
+    match (ids.next()?, ids.next()?, ids.next()?, ids.next()) {
+        (Some(guild), Some(channel), Some(message), None) => Some((guild, channel, message)),
+        _ => None,
+    }
+}
+
+///Quotes the start of a message, line by line
+fn quote(content: &str) -> String {
+    let mut text: String = content.chars().take(QUOTE_LIMIT).collect();
+    if content.chars().count() > QUOTE_LIMIT {
+        text.push('…');
+    }
+    validate::strip_mass_mentions(&text)
+        .lines()
+        .map(|line| format!("> {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+///Looks up the message a poll is about, or explains why it can't be used.
+///Only messages the author can read in the poll's own server are quoted
+pub async fn fetch(ctx: Context<'_>, link: &str) -> Result<PollContext, String> {
+    let Some((guild_id, channel_id, message_id)) = parse_link(link) else {
+        return Err("The context must be a message link, from Copy Message Link".to_string());
+    };
+    if ctx.guild_id().map(|g| g.0) != Some(guild_id) {
+        return Err("The context message has to be in this server".to_string());
+    }
+
+    let serenity_ctx = ctx.serenity_context();
+    let channel = ChannelId(channel_id)
+        .to_channel(serenity_ctx)
+        .await
+        .ok()
+        .and_then(|c| c.guild());
+    let readable = channel.and_then(|c| c.permissions_for_user(serenity_ctx, ctx.author().id).ok());
+    if !readable.map_or(false, |p| {
+        p.contains(Permissions::VIEW_CHANNEL | Permissions::READ_MESSAGE_HISTORY)
+    }) {
+        return Err("You can't read the channel of the context message".to_string());
+    }
+
+    let message = ChannelId(channel_id)
+        .message(serenity_ctx, message_id)
+        .await
+        .map_err(|_| "I can't find the context message".to_string())?;
+    Ok(PollContext {
+        link: message_link(Some(guild_id), channel_id, message_id),
+        author_id: message.author.id.0,
+        quote: quote(&message.content),
+    })
+}
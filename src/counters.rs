@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use shuttle_persist::PersistInstance;
+
+use crate::{migrate, store, Error};
+
+const GLOBAL_KEY: &str = "counters";
+const OPEN_KEY: &str = "open_polls";
+
+fn guild_key(guild_id: u64) -> String {
+    format!("counters_{guild_id}")
+}
+
+//Held from reading counters until they are written back, so simultaneous votes all count
+static COUNTER_LOCK: Mutex<()> = Mutex::new(());
+
+///Running totals kept up as polls are created and voted on, so stats never read every poll
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+pub struct Counters {
+    pub polls: u64,
+    //One per member with a vote on a poll, changing it doesn't count again, retracting uncounts it
+    pub votes: u64,
+}
+
+///Counts the polls still stored, for deployments from before counters were kept.
+///Votes on polls that were deleted since can't be counted anymore.
+///Reading every poll is slow, so it runs without the lock and only saves if nothing else did,
+///returning whether its own count was the one saved
+fn backfill(persist: &PersistInstance) -> Result<(Counters, bool), Error> {
+    let mut global = Counters::default();
+    let mut guilds = HashMap::<u64, Counters>::new();
+    for id in migrate::stored_polls(persist)? {
+        let Ok((_, poll)) = store::load_poll(persist, id) else {
+            continue;
+        };
+        let votes = poll.voter_count() as u64;
+        global.polls += 1;
+        global.votes += votes;
+        if let Some(guild_id) = poll.guild_id {
+            let guild = guilds.entry(guild_id).or_default();
+            guild.polls += 1;
+            guild.votes += votes;
+        }
+    }
+
+    let _guard = COUNTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Ok(counters) = persist.load(GLOBAL_KEY) {
+        return Ok((counters, false));
+    }
+    for (guild_id, counters) in guilds {
+        persist.save(&guild_key(guild_id), counters)?;
+    }
+    persist.save(GLOBAL_KEY, global)?;
+    Ok((global, true))
+}
+
+///The totals of every guild together, counted from the stored polls the first time
+pub fn global(persist: &PersistInstance) -> Result<Counters, Error> {
+    let counters = {
+        let _guard = COUNTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        persist.load(GLOBAL_KEY)
+    };
+    match counters {
+        Ok(counters) => Ok(counters),
+        Err(_) => backfill(persist).map(|(counters, _)| counters),
+    }
+}
+
+pub fn guild(persist: &PersistInstance, guild_id: u64) -> Result<Counters, Error> {
+    //Guild totals are backfilled along with the global ones
+    global(persist)?;
+    Ok(persist.load(&guild_key(guild_id)).unwrap_or_default())
+}
+
+fn add(
+    persist: &PersistInstance,
+    guild_id: Option<u64>,
+    change: impl Fn(&mut Counters),
+) -> Result<(), Error> {
+    let guard = COUNTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    //The first count backfills instead, which already includes what is being counted
+    //unless another backfill that read the polls earlier was saved first
+    let Ok(mut counters) = persist.load::<Counters>(GLOBAL_KEY) else {
+        drop(guard);
+        if backfill(persist)?.1 {
+            return Ok(());
+        }
+        return add(persist, guild_id, change);
+    };
+    change(&mut counters);
+    persist.save(GLOBAL_KEY, counters)?;
+
+    if let Some(guild_id) = guild_id {
+        let mut counters: Counters = persist.load(&guild_key(guild_id)).unwrap_or_default();
+        change(&mut counters);
+        persist.save(&guild_key(guild_id), counters)?;
+    }
+    Ok(())
+}
+
+///Counts a newly posted poll, which must already be saved
+pub fn poll_created(persist: &PersistInstance, guild_id: Option<u64>) {
+    //Stats being off by one isn't worth failing the poll over
+    if let Err(e) = add(persist, guild_id, |c| c.polls += 1) {
+        tracing::warn!("Failed to count a new poll: {e}");
+    }
+}
+
+///Counts a member's first vote on a poll, which must already be saved
+pub fn vote_cast(persist: &PersistInstance, guild_id: Option<u64>) {
+    if let Err(e) = add(persist, guild_id, |c| c.votes += 1) {
+        tracing::warn!("Failed to count a vote: {e}");
+    }
+}
+
+///Takes back the count of a vote that was undone, the poll must already be saved without it
+pub fn vote_undone(persist: &PersistInstance, guild_id: Option<u64>) {
+    if let Err(e) = add(persist, guild_id, |c| c.votes = c.votes.saturating_sub(1)) {
+        tracing::warn!("Failed to uncount a vote: {e}");
+    }
+}
+
+///Counts the open polls in the guild indexes, for deployments from before they were counted.
+///Like `backfill` it reads without the lock and keeps a count something else saved first
+fn backfill_open(persist: &PersistInstance) -> Result<(u64, bool), Error> {
+    let open = store::open_poll_count(persist)? as u64;
+    let _guard = COUNTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Ok(open) = persist.load(OPEN_KEY) {
+        return Ok((open, false));
+    }
+    persist.save(OPEN_KEY, open)?;
+    Ok((open, true))
+}
+
+///How many polls are open across every guild, counted from the guild indexes the first time
+pub fn open_polls(persist: &PersistInstance) -> Result<u64, Error> {
+    let open = {
+        let _guard = COUNTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        persist.load(OPEN_KEY)
+    };
+    match open {
+        Ok(open) => Ok(open),
+        Err(_) => backfill_open(persist).map(|(open, _)| open),
+    }
+}
+
+fn change_open(persist: &PersistInstance, change: impl Fn(u64) -> u64) -> Result<(), Error> {
+    let guard = COUNTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    //The first change backfills instead, the indexes already include it unless another
+    //backfill was saved first
+    let Ok(open) = persist.load::<u64>(OPEN_KEY) else {
+        drop(guard);
+        if backfill_open(persist)?.1 {
+            return Ok(());
+        }
+        return change_open(persist, change);
+    };
+    persist.save(OPEN_KEY, change(open))?;
+    Ok(())
+}
+
+///Counts a poll that was posted or reopened, which must already be saved
+pub fn poll_opened(persist: &PersistInstance) {
+    if let Err(e) = change_open(persist, |open| open + 1) {
+        tracing::warn!("Failed to count an open poll: {e}");
+    }
+}
+
+///Counts a poll that closed or was deleted while open, which must already be saved
+pub fn poll_ended(persist: &PersistInstance) {
+    if let Err(e) = change_open(persist, |open| open.saturating_sub(1)) {
+        tracing::warn!("Failed to count a closed poll: {e}");
+    }
+}
+
+///Backfills the counters on a blocking thread at startup, before the first vote or stats need them
+pub fn spawn_backfill(persist: PersistInstance) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = global(&persist).and_then(|_| open_polls(&persist)) {
+            tracing::warn!("Failed to backfill the counters: {e}");
+        }
+    });
+}
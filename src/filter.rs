@@ -0,0 +1,206 @@
+use poise::serenity_prelude::{ChannelId, GuildChannel, GuildId};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::eph_reply;
+use crate::config::GuildConfig;
+use crate::poll::Poll;
+use crate::{audit, automod, Context, Error};
+
+///What happens to a poll containing text the guild's filter bans
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum FilterAction {
+    #[default]
+    #[name = "Refuse to post the poll"]
+    Reject,
+    #[name = "Post the poll and flag it in the log"]
+    Flag,
+}
+
+///Words and regular expressions banned from the text of a guild's polls
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ContentFilter {
+    //Matched as whole words, ignoring case
+    #[serde(default)]
+    pub words: Vec<String>,
+    //Checked to be valid when added
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub action: FilterAction,
+    //Pings the moderator role in the log channel when a poll matches
+    #[serde(default)]
+    pub notify_moderators: bool,
+}
+
+///A banned word as a pattern matching it on its own, so banning "cat" leaves "category" alone
+fn word_pattern(word: &str) -> String {
+    format!(r"(?i)(^|\W){}($|\W)", regex::escape(word))
+}
+
+impl ContentFilter {
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.patterns.is_empty()
+    }
+
+    ///The first banned word or pattern found in any of the texts
+    pub fn violation(&self, texts: &[&str]) -> Option<&str> {
+        let words = self.words.iter().map(|w| (w, word_pattern(w)));
+        let patterns = self.patterns.iter().map(|p| (p, p.clone()));
+        words.chain(patterns).find_map(|(banned, pattern)| {
+            //Patterns are checked when added, so only ones stored by hand can be invalid
+            let regex = Regex::new(&pattern).ok()?;
+            texts
+                .iter()
+                .any(|t| regex.is_match(t))
+                .then_some(banned.as_str())
+        })
+    }
+}
+
+///Every text of a poll members can write, its title, description, options and reasons
+fn poll_texts(poll: &Poll) -> Vec<&str> {
+    [
+        poll.title.as_str(),
+        &poll.description,
+        &poll.reason_to_vote_yes,
+        &poll.reason_to_vote_no,
+    ]
+    .into_iter()
+    .chain(poll.options().iter().map(|o| o.label.as_str()))
+    .chain(poll.options().iter().filter_map(|o| o.reason.as_deref()))
+    .collect()
+}
+
+///Checks a new poll against its guild's AutoMod rules and filter, logging polls that match.
+///Returns whether the poll can be posted, after explaining why not if it can't
+pub async fn screen(ctx: Context<'_>, poll: &Poll) -> Result<bool, Error> {
+    check_here(ctx, poll, Screening::Create).await
+}
+
+///Checks a poll with its edited text like `screen`, returning whether the edit can be saved
+pub async fn screen_edit(ctx: Context<'_>, poll: &Poll) -> Result<bool, Error> {
+    check_here(ctx, poll, Screening::Edit).await
+}
+
+///Checks a poll against the rules of the partner guild it's being mirrored into
+pub async fn screen_copy(
+    ctx: Context<'_>,
+    poll: &Poll,
+    channel: &GuildChannel,
+    roles: &[u64],
+) -> Result<bool, Error> {
+    check(
+        ctx,
+        poll,
+        Screening::Copy,
+        channel.guild_id,
+        channel.id,
+        roles,
+    )
+    .await
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Screening {
+    Create,
+    Edit,
+    Copy,
+}
+
+async fn check_here(ctx: Context<'_>, poll: &Poll, screening: Screening) -> Result<bool, Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(true);
+    };
+    let roles: Vec<u64> = match ctx.author_member().await {
+        Some(member) => member.roles.iter().map(|r| r.0).collect(),
+        None => Vec::new(),
+    };
+    check(ctx, poll, screening, guild_id, ctx.channel_id(), &roles).await
+}
+
+async fn check(
+    ctx: Context<'_>,
+    poll: &Poll,
+    screening: Screening,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    roles: &[u64],
+) -> Result<bool, Error> {
+    let texts = poll_texts(poll);
+    let http = &ctx.serenity_context().http;
+    let server = match screening {
+        Screening::Copy => "the partner server",
+        _ => "this server",
+    };
+
+    if let Some(rule) = automod::blocking_rule(http, guild_id.0, roles, channel_id.0, &texts).await
+    {
+        tracing::info!(
+            "Refused a poll by {} in {guild_id} matching AutoMod rule {rule}",
+            ctx.author().id
+        );
+        let text = format!("Your poll contains text blocked by {server}'s AutoMod rule \"{rule}\"");
+        eph_reply(ctx, text).await?;
+        return Ok(false);
+    }
+
+    let persist = ctx.data().clone().persist;
+    let config = GuildConfig::load(&persist, guild_id.0);
+    let Some(banned) = config.filter.violation(&texts) else {
+        return Ok(true);
+    };
+
+    let allowed = config.filter.action == FilterAction::Flag;
+    let (what, made) = match screening {
+        Screening::Create => ("poll", "created"),
+        Screening::Edit => ("an edit of poll", "edited"),
+        Screening::Copy => ("a copy of poll", "mirrored"),
+    };
+    let action = match allowed {
+        true => format!(
+            "{made} poll \"{}\" in <#{}>, which the filter flagged for \"{banned}\"",
+            poll.title, channel_id
+        ),
+        false => format!(
+            "was refused {what} \"{}\" in <#{}> by the filter for \"{banned}\"",
+            poll.title, channel_id
+        ),
+    };
+    audit::record(http, &persist, guild_id.0, ctx.author().id.0, action).await?;
+
+    if let (true, Some(log_channel), Some(role)) = (
+        config.filter.notify_moderators,
+        config.log_channel,
+        config.moderator_role,
+    ) {
+        //Names the poll itself, other log entries can land between the one above and this
+        let outcome = match allowed {
+            true => "flagged",
+            false => "refused",
+        };
+        let text = format!(
+            "<@&{role}> the filter {outcome} {what} \"{}\" by <@{}> in <#{}>",
+            poll.title,
+            ctx.author().id,
+            channel_id
+        );
+        ChannelId(log_channel)
+            .send_message(http, |m| {
+                m.content(text)
+                    .allowed_mentions(|a| a.empty_parse().roles([role]))
+            })
+            .await?;
+    }
+
+    if !allowed {
+        //The banned text isn't repeated, it may be something the member shouldn't see echoed
+        let what = match screening {
+            Screening::Edit => "edit",
+            _ => "poll",
+        };
+        let text = format!("Your {what} contains text that isn't allowed in {server}");
+        eph_reply(ctx, text).await?;
+    }
+    Ok(allowed)
+}
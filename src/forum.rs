@@ -0,0 +1,58 @@
+use poise::serenity_prelude::{ChannelId, Http};
+use reqwest::Url;
+
+use crate::poll::Poll;
+use crate::Error;
+
+//Renders charts from a Chart.js config in the URL, so Discord can show one as an embed image
+const CHART_API: &str = "https://quickchart.io/chart";
+
+//Forum post titles can be at most 100 characters
+const NAME_LIMIT: usize = 100;
+
+///A bar chart of a poll's final tally, none before anyone voted
+fn chart_url(poll: &Poll) -> Option<String> {
+    let groups = poll.vote_groups();
+    if groups.iter().all(|(_, votes)| votes.is_empty()) {
+        return None;
+    }
+    let labels: Vec<&str> = groups.iter().map(|(name, _)| *name).collect();
+    let counts: Vec<usize> = groups.iter().map(|(_, votes)| votes.len()).collect();
+    let config = serde_json::json!({
+        "type": "bar",
+        "data": {
+            "labels": labels,
+            "datasets": [{ "label": "Votes", "data": counts }],
+        },
+        "options": { "legend": { "display": false } },
+    });
+    let url = Url::parse_with_params(CHART_API, &[("c", config.to_string())]).ok()?;
+    Some(url.to_string())
+}
+
+///Posts a closed poll to the guild's archive forum with its final results, so the guild keeps
+///a browsable history of what it decided
+pub async fn archive(http: &Http, forum: u64, message_id: u64, poll: &Poll) -> Result<(), Error> {
+    let name: String = poll.title.chars().take(NAME_LIMIT).collect();
+    let chart = chart_url(poll);
+    let link = poll.link(message_id);
+    ChannelId(forum)
+        .create_forum_post(http, |p| {
+            p.name(name).message(|m| {
+                m.embed(|e| {
+                    poll.results_embed(e)
+                        .title(&poll.title)
+                        .footer(|f| f.text(format!("{} voters, closed", poll.voter_count())));
+                    if let Some(link) = link {
+                        e.url(link);
+                    }
+                    if let Some(chart) = chart {
+                        e.image(chart);
+                    }
+                    e
+                })
+            })
+        })
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,633 @@
+use poise::serenity_prelude::{
+    self as serenity, ActionRowComponent, ButtonStyle, CacheHttp, GuildId, Http, InputTextStyle,
+    Interaction, InteractionResponseType, InteractionType, MessageComponentInteraction,
+    ModalSubmitInteraction, Timestamp,
+};
+use poise::{Event, FrameworkContext};
+use shuttle_persist::PersistInstance;
+
+use crate::config::{GuildConfig, VoteMessage};
+use crate::poll::{Poll, Visibility};
+use crate::prefs::{self, ResultsFormat};
+use crate::scheduler;
+use crate::{audit, counters, milestone, reaction, receipt, refresh, runtime, store, vote, voters};
+use crate::{Data, Error};
+
+///Responds to a component interaction with ephemeral text
+pub async fn eph_text(
+    interaction: &MessageComponentInteraction,
+    text: impl Into<String>,
+    http: &Http,
+) -> Result<(), Error> {
+    interaction
+        .create_interaction_response(http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| d.ephemeral(true).content(text.into()))
+        })
+        .await?;
+    Ok(())
+}
+
+///Confirms a vote, with a button to undo a first vote if the poll's guild allows that
+///and one to give a reason for it if asked for
+async fn confirm_vote(
+    interaction: &MessageComponentInteraction,
+    text: String,
+    http: &Http,
+    persist: &PersistInstance,
+    poll: &Poll,
+    poll_id: u64,
+    first_vote: bool,
+) -> Result<(), Error> {
+    //Polls that allow changes can already be retracted by clicking the choice again
+    let undo_window = match poll.guild_id {
+        Some(guild_id) if first_vote && !poll.allow_changes => {
+            GuildConfig::load(persist, guild_id).undo_window()
+        }
+        _ => 0,
+    };
+    //Anonymous polls that ask why land here too, so their receipt code isn't lost to a modal
+    let reason_button = poll.ask_reasons && poll.has_voted(interaction.user.id.0);
+    if undo_window == 0 && !reason_button {
+        return eph_text(interaction, text, http).await;
+    }
+
+    let mut text = text;
+    if undo_window > 0 {
+        text.push_str(&format!("\nYou can undo it for {undo_window} seconds"));
+    }
+    let undo_id = format!("undo_{poll_id}_{}", Timestamp::now().unix_timestamp());
+    interaction
+        .create_interaction_response(http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.ephemeral(true).content(text).components(|c| {
+                        c.create_action_row(|row| {
+                            if undo_window > 0 {
+                                row.create_button(|b| {
+                                    b.custom_id(undo_id)
+                                        .label("Undo")
+                                        .style(ButtonStyle::Secondary)
+                                });
+                            }
+                            if reason_button {
+                                row.create_button(|b| {
+                                    b.custom_id(format!("why_{poll_id}"))
+                                        .label("Add a reason")
+                                        .style(ButtonStyle::Secondary)
+                                });
+                            }
+                            row
+                        })
+                    })
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+//Longest reason a voter can give, and the longest text Discord allows as a modal's label
+const REASON_LIMIT: u64 = 300;
+const LABEL_LIMIT: usize = 45;
+
+///Confirms a vote in a modal asking the voter why, for polls that ask for reasons.
+///The vote is already saved, so closing the modal without answering keeps it
+async fn ask_reason(
+    interaction: &MessageComponentInteraction,
+    text: String,
+    http: &Http,
+    poll_id: u64,
+) -> Result<(), Error> {
+    //Custom vote messages can be longer than a label, the plain question is shown instead
+    let label = match text.chars().count() > LABEL_LIMIT {
+        true => "Your reason".to_string(),
+        false => text,
+    };
+    interaction
+        .create_interaction_response(http, |r| {
+            r.kind(InteractionResponseType::Modal)
+                .interaction_response_data(|d| {
+                    d.custom_id(format!("reason_{poll_id}"))
+                        .title("Why?")
+                        .components(|c| {
+                            c.create_action_row(|row| {
+                                row.create_input_text(|t| {
+                                    t.custom_id("reason")
+                                        .label(label)
+                                        .placeholder("Optional")
+                                        .style(InputTextStyle::Paragraph)
+                                        .max_length(REASON_LIMIT)
+                                        .required(false)
+                                })
+                            })
+                        })
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+///Stores the reason a voter gave in the modal from `ask_reason` with their vote
+async fn save_reason(
+    ctx: &serenity::Context,
+    submission: &ModalSubmitInteraction,
+    persist: &PersistInstance,
+    poll_id: &str,
+) -> Result<(), Error> {
+    let reason = submission
+        .data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|c| match c {
+            ActionRowComponent::InputText(input) => Some(input.value.trim().to_string()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let user_id = submission.user.id.0;
+
+    let Ok(poll_id) = poll_id.parse() else {
+        return Ok(());
+    };
+    let updated = match reason.is_empty() {
+        true => None,
+        false => Some(store::update(persist, poll_id, |poll| {
+            //The vote was retracted or the poll closed while the modal was open
+            if !poll.has_voted(user_id) || poll.closed {
+                return Err(String::new());
+            }
+            poll.vote_reasons.insert(user_id, reason);
+            Ok(())
+        })?),
+    };
+    let text = match updated {
+        None => "Your vote was saved without a reason",
+        Some(store::Updated::Saved { poll_id, poll, .. }) => {
+            if poll.live_tally() {
+                refresh::live_tally(&ctx.http, persist, poll_id, &poll).await?;
+            }
+            "Thanks, your reason was saved with your vote!"
+        }
+        Some(store::Updated::Refused(_)) => {
+            "Your reason couldn't be saved, you no longer have a vote on this poll"
+        }
+    };
+    submission
+        .create_interaction_response(ctx.http(), |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| d.ephemeral(true).content(text))
+        })
+        .await?;
+    Ok(())
+}
+
+///What a vote on an options poll picks
+enum OptionPick {
+    //An option's button
+    One(usize),
+    //Everything selected in a multi-vote poll's menu
+    Many(Vec<usize>),
+}
+
+///Votes for an option of an options poll, or replaces the picks from a multi-vote menu
+async fn option_vote(
+    ctx: &serenity::Context,
+    interaction: &MessageComponentInteraction,
+    persist: &PersistInstance,
+    pick: OptionPick,
+) -> Result<(), Error> {
+    let user_id = interaction.user.id.0;
+    let updated = store::update(persist, interaction.message.id.0, |poll| {
+        let change = match pick {
+            OptionPick::One(index) => vote::apply_option(poll, user_id, index),
+            OptionPick::Many(picks) => vote::apply_picks(poll, user_id, picks),
+        };
+        change
+            .map_err(|refusal| vote::already_voted_text(persist, poll, user_id).unwrap_or(refusal))
+    })?;
+    let (poll_id, poll, change) = match updated {
+        store::Updated::Saved {
+            poll_id,
+            poll,
+            value,
+        } => (poll_id, poll, value),
+        store::Updated::Refused(refusal) => {
+            return eph_text(interaction, refusal, ctx.http()).await;
+        }
+    };
+
+    let first_vote = change.previous.is_empty();
+    match (first_vote, change.picks.is_empty()) {
+        (true, false) => counters::vote_cast(persist, poll.guild_id),
+        (false, true) => counters::vote_undone(persist, poll.guild_id),
+        _ => {}
+    }
+    let labels = poll.labels_of(&change.picks);
+    let text = match first_vote && !change.picks.is_empty() {
+        true => {
+            vote::custom_text(persist, &poll, VoteMessage::Voted, &labels).unwrap_or(change.text)
+        }
+        false => change.text,
+    } + &receipt::note(change.receipt.as_deref());
+
+    //Receipt codes are only shown in the confirmation, so anonymous polls ask why with a button
+    let changed = !change.picks.is_empty() && change.picks != change.previous;
+    if poll.ask_reasons && changed && change.receipt.is_none() {
+        ask_reason(interaction, text, ctx.http(), poll_id).await?;
+    } else {
+        confirm_vote(
+            interaction,
+            text,
+            ctx.http(),
+            persist,
+            &poll,
+            poll_id,
+            first_vote,
+        )
+        .await?;
+    }
+    refresh::live_tally(&ctx.http, persist, poll_id, &poll).await?;
+    milestone::announce(&ctx.http, poll_id, &poll, &change.milestones).await;
+    Ok(())
+}
+
+///Takes back a vote from an `undo_{poll}_{voted at}` button while its grace window is open
+async fn undo_vote(
+    ctx: &serenity::Context,
+    interaction: &MessageComponentInteraction,
+    persist: &PersistInstance,
+    args: &str,
+) -> Result<(), Error> {
+    let Some((poll_id, voted_at)) = args.split_once('_') else {
+        return Ok(());
+    };
+    let (Ok(poll_id), Ok(voted_at)) = (poll_id.parse(), voted_at.parse::<i64>()) else {
+        return Ok(());
+    };
+    let user_id = interaction.user.id.0;
+    let updated = store::update(persist, poll_id, |poll| {
+        let window = poll
+            .guild_id
+            .map_or(0, |g| GuildConfig::load(persist, g).undo_window());
+        if Timestamp::now().unix_timestamp() - voted_at > i64::from(window) {
+            return Err("The time to undo your vote has passed".to_string());
+        }
+        if poll.closed || poll.locked {
+            return Err("This poll no longer accepts votes".to_string());
+        }
+        if !poll.has_voted(user_id) {
+            return Err("You haven't voted on this poll".to_string());
+        }
+        let previous = poll.vote_of(user_id);
+        poll.remove_vote(user_id);
+        Ok(previous)
+    })?;
+
+    //The button is removed either way, it can only be used once
+    let text = match &updated {
+        store::Updated::Saved { .. } => "Your vote was undone!",
+        store::Updated::Refused(refusal) => refusal.as_str(),
+    };
+    interaction
+        .create_interaction_response(ctx.http(), |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.content(text).components(|c| c))
+        })
+        .await?;
+    let store::Updated::Saved {
+        poll_id,
+        poll,
+        value: previous,
+    } = updated
+    else {
+        return Ok(());
+    };
+    counters::vote_undone(persist, poll.guild_id);
+
+    if poll.live_tally() {
+        refresh::live_tally(&ctx.http, persist, poll_id, &poll).await?;
+    }
+    if let Err(e) = vote::sync_roles(&ctx.http, &poll, user_id, previous, None).await {
+        tracing::warn!("Failed to update poll roles for {user_id}: {e}");
+    }
+    Ok(())
+}
+
+///The merged tally in the user's results format, broken down per guild for federated polls
+pub fn results_text(
+    ctx: &serenity::Context,
+    persist: &PersistInstance,
+    poll: &Poll,
+    user_id: u64,
+) -> String {
+    let format = prefs::results_format(persist, user_id, poll.guild_id);
+    let mut text = match format {
+        ResultsFormat::Compact => poll.tally_text(),
+        ResultsFormat::PlainText => poll.plain_tally_text(),
+    };
+    //The per guild breakdown only counts yes and no votes
+    if !poll.federated || !poll.options().is_empty() {
+        return text;
+    }
+
+    for (guild_id, (yes, no)) in poll.guild_breakdown() {
+        let name = GuildId(guild_id)
+            .name(ctx)
+            .unwrap_or_else(|| "Unknown server".to_string());
+        text.push_str(&match format {
+            ResultsFormat::Compact => format!("\n{name} - Yes: {yes} No: {no}"),
+            ResultsFormat::PlainText => format!("\n{name}: {yes} yes, {no} no"),
+        });
+    }
+    text
+}
+
+///Cleans up after a poll whose message was deleted by hand, or forgets a deleted copy of one
+async fn message_deleted(
+    ctx: &serenity::Context,
+    persist: &PersistInstance,
+    message_id: u64,
+) -> Result<(), Error> {
+    let Ok((poll_id, poll)) = store::load_poll(persist, message_id) else {
+        return Ok(());
+    };
+
+    //The poll lives on in its other messages
+    if poll_id != message_id {
+        let update = |poll: &mut Poll| {
+            poll.copies.retain(|c| c.message_id != message_id);
+            Ok(())
+        };
+        let (_, poll, _) = store::update(persist, poll_id, update)?.saved()?;
+        store::delete_copy(persist, message_id)?;
+        if !poll.original_deleted || !poll.copies.is_empty() {
+            return Ok(());
+        }
+        return remove_poll(ctx, persist, poll_id, &poll).await;
+    }
+
+    //Deleting the original doesn't take the votes cast on its copies with it
+    if !poll.copies.is_empty() {
+        let update = |poll: &mut Poll| {
+            poll.original_deleted = true;
+            Ok(())
+        };
+        let (_, poll, _) = store::update(persist, message_id, update)?.saved()?;
+        poll.update_messages(&ctx.http, message_id).await?;
+        if let Some(guild_id) = poll.guild_id {
+            let action = format!(
+                "the message of poll \"{}\" ({message_id}) was deleted, its copies remain",
+                poll.title
+            );
+            audit::record(&ctx.http, persist, guild_id, audit::SYSTEM, action).await?;
+        }
+        return Ok(());
+    }
+    remove_poll(ctx, persist, message_id, &poll).await
+}
+
+///Removes a poll none of whose messages are left
+async fn remove_poll(
+    ctx: &serenity::Context,
+    persist: &PersistInstance,
+    poll_id: u64,
+    poll: &Poll,
+) -> Result<(), Error> {
+    scheduler::cancel_poll(persist, poll_id)?;
+    store::delete_poll(persist, poll_id, poll)?;
+    if let Some(guild_id) = poll.guild_id {
+        let action = format!(
+            "the message of poll \"{}\" ({poll_id}) was deleted, so the poll was removed",
+            poll.title
+        );
+        audit::record(&ctx.http, persist, guild_id, audit::SYSTEM, action).await?;
+    }
+    Ok(())
+}
+
+pub async fn event_handler(
+    ctx: &serenity::Context,
+    event: &Event<'_>,
+    fw_ctx: FrameworkContext<'_, Data, Error>,
+) -> Result<(), Error> {
+    match event {
+        Event::MessageDelete {
+            deleted_message_id, ..
+        } => {
+            return message_deleted(ctx, &fw_ctx.user_data.persist, deleted_message_id.0).await;
+        }
+        Event::MessageDeleteBulk {
+            multiple_deleted_messages_ids,
+            ..
+        } => {
+            for message_id in multiple_deleted_messages_ids {
+                message_deleted(ctx, &fw_ctx.user_data.persist, message_id.0).await?;
+            }
+            return Ok(());
+        }
+        Event::ReactionAdd { add_reaction } => {
+            return reaction::added(ctx, &fw_ctx.user_data.persist, add_reaction).await;
+        }
+        Event::ReactionRemove { removed_reaction } => {
+            return reaction::removed(ctx, &fw_ctx.user_data.persist, removed_reaction).await;
+        }
+        _ => {}
+    }
+
+    if let Event::InteractionCreate {
+        interaction: Interaction::ModalSubmit(submission),
+    } = event
+    {
+        //Other modals, like the one of /poll edit, are handled by their commands
+        if let Some(poll_id) = submission.data.custom_id.strip_prefix("reason_") {
+            return save_reason(ctx, submission, &fw_ctx.user_data.persist, poll_id).await;
+        }
+        return Ok(());
+    }
+
+    if let Event::InteractionCreate { interaction } = event {
+        if interaction.kind() != InteractionType::MessageComponent {
+            return Ok(());
+        }
+
+        let component_interaction = interaction.as_message_component().unwrap();
+        let component_data = component_interaction.clone().data;
+
+        let persist = &fw_ctx.user_data.persist;
+        //Votes wait out maintenance, buttons of command replies are left to their collectors
+        let custom_id = component_data.custom_id.as_str();
+        let voting = ["poll_", "undo_", "why_"]
+            .iter()
+            .any(|prefix| custom_id.starts_with(prefix));
+        let owner = fw_ctx
+            .options
+            .owners
+            .contains(&component_interaction.user.id);
+        if voting && !owner && runtime::current().maintenance {
+            let text = "The bot is down for maintenance, try again later";
+            return eph_text(component_interaction, text, ctx.http()).await;
+        }
+        if let Some(args) = component_data.custom_id.strip_prefix("voters_") {
+            return voters::flip(component_interaction, ctx.http(), persist, args).await;
+        }
+        if let Some(poll_id) = component_data.custom_id.strip_prefix("drill_") {
+            return voters::drill(component_interaction, ctx.http(), persist, poll_id).await;
+        }
+        if let Some(args) = component_data.custom_id.strip_prefix("undo_") {
+            return undo_vote(ctx, component_interaction, persist, args).await;
+        }
+        if let Some(poll_id) = component_data.custom_id.strip_prefix("why_") {
+            let Ok(poll_id) = poll_id.parse() else {
+                return Ok(());
+            };
+            let text = "Your reason".to_string();
+            return ask_reason(component_interaction, text, ctx.http(), poll_id).await;
+        }
+        //Other buttons, like poll previews, are handled by collectors in their commands
+        if !component_data.custom_id.starts_with("poll_") {
+            return Ok(());
+        }
+
+        let message_id = component_interaction.message.id.0;
+        let Ok((poll_id, mut poll)) = store::load_poll(persist, message_id) else {
+            return eph_text(
+                component_interaction,
+                "This poll doesn't exist!",
+                ctx.http(),
+            )
+            .await;
+        };
+
+        //Polls from before channels were recorded learn theirs from the first click on them
+        if poll.channel_id.is_none() && poll_id == message_id {
+            let channel_id = component_interaction.channel_id.0;
+            let guild_id = component_interaction.guild_id.map(|g| g.0);
+            let update = |poll: &mut Poll| {
+                poll.channel_id.get_or_insert(channel_id);
+                poll.guild_id = poll.guild_id.or(guild_id);
+                Ok(())
+            };
+            poll = store::update(persist, poll_id, update)?.saved()?.1;
+        }
+
+        if poll.closed && component_data.custom_id != "poll_view" {
+            return eph_text(component_interaction, "This poll has ended!", ctx.http()).await;
+        }
+        if poll.locked && component_data.custom_id != "poll_view" {
+            return eph_text(component_interaction, "This poll is locked!", ctx.http()).await;
+        }
+
+        let roles: Vec<u64> = component_interaction
+            .member
+            .as_ref()
+            .map(|m| m.roles.iter().map(|r| r.0).collect())
+            .unwrap_or_default();
+        let user_id = component_interaction.user.id.0;
+        let observer = component_interaction.guild_id.map_or(false, |g| {
+            let config = GuildConfig::load(persist, g.0);
+            roles.iter().any(|r| config.observer_roles.contains(r))
+        });
+
+        if component_data.custom_id == "poll_view" {
+            if !observer && !poll.can_view_results(user_id, &roles) {
+                let text = "The results of this poll are restricted until it closes";
+                return eph_text(component_interaction, text, ctx.http()).await;
+            }
+        } else if observer {
+            let text = "Observers can view the results of polls here, but can't vote on them";
+            return eph_text(component_interaction, text, ctx.http()).await;
+        }
+
+        if component_data.custom_id == "poll_pick" {
+            let picks = component_data
+                .values
+                .iter()
+                .filter_map(|v| v.parse().ok())
+                .collect();
+            let pick = OptionPick::Many(picks);
+            return option_vote(ctx, component_interaction, persist, pick).await;
+        }
+        if let Some(index) = component_data.custom_id.strip_prefix("poll_option_") {
+            let Ok(index) = index.parse() else {
+                return Ok(());
+            };
+            let pick = OptionPick::One(index);
+            return option_vote(ctx, component_interaction, persist, pick).await;
+        }
+
+        match component_data.custom_id.as_str() {
+            "poll_view" if !poll.options().is_empty() => {
+                let results = results_text(ctx, persist, &poll, user_id);
+                return voters::show_options(
+                    component_interaction,
+                    ctx.http(),
+                    poll_id,
+                    &poll,
+                    results,
+                )
+                .await;
+            }
+            "poll_view" if poll.visibility == Visibility::Public => {
+                let results = results_text(ctx, persist, &poll, user_id);
+                return voters::show(component_interaction, ctx.http(), poll_id, &poll, results)
+                    .await;
+            }
+            "poll_view" => {
+                return eph_text(
+                    component_interaction,
+                    results_text(ctx, persist, &poll, user_id),
+                    ctx.http(),
+                )
+                .await;
+            }
+            _ => {}
+        }
+
+        let click = vote::Click {
+            message_id: component_interaction.message.id.0,
+            custom_id: &component_data.custom_id,
+            user_id,
+            guild_id: component_interaction.guild_id.map(|g| g.0),
+        };
+        let (poll_id, poll, change) = match vote::click(persist, &click)? {
+            vote::Cast::Voted {
+                poll_id,
+                poll,
+                change,
+            } => (poll_id, poll, change),
+            vote::Cast::Refused(refusal) => {
+                //Closed and locked polls were refused above, so a voter refused here already voted
+                let text = vote::already_voted_text(persist, &poll, user_id).unwrap_or(refusal);
+                return eph_text(component_interaction, text, ctx.http()).await;
+            }
+        };
+
+        let text = match (change.previous, change.new) {
+            (None, Some(new)) => {
+                vote::custom_text(persist, &poll, VoteMessage::Voted, new.as_str())
+            }
+            _ => None,
+        }
+        .unwrap_or_else(|| change.text.clone());
+        if poll.ask_reasons && change.new.is_some() && change.receipt.is_none() {
+            ask_reason(component_interaction, text, ctx.http(), poll_id).await?;
+        } else {
+            confirm_vote(
+                component_interaction,
+                text + &receipt::note(change.receipt.as_deref()),
+                ctx.http(),
+                persist,
+                &poll,
+                poll_id,
+                change.previous.is_none(),
+            )
+            .await?;
+        }
+        let channel_id = component_interaction.channel_id;
+        vote::after(
+            &ctx.http, persist, poll_id, &poll, user_id, &change, channel_id,
+        )
+        .await?;
+    }
+    Ok(())
+}
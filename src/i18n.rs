@@ -0,0 +1,62 @@
+//(Discord locale, English text, translation) of every translated button text
+const TRANSLATIONS: &[(&str, &str, &str)] = &[
+    ("de", "Yes!", "Ja!"),
+    ("de", "No!", "Nein!"),
+    ("de", "View Results", "Ergebnisse anzeigen"),
+    ("de", "Sign", "Unterschreiben"),
+    ("de", "Going", "Dabei"),
+    ("de", "Maybe", "Vielleicht"),
+    ("de", "Can't", "Kann nicht"),
+    ("de", "Pick up to", "Wähle bis zu"),
+    ("fr", "Yes!", "Oui !"),
+    ("fr", "No!", "Non !"),
+    ("fr", "View Results", "Voir les résultats"),
+    ("fr", "Sign", "Signer"),
+    ("fr", "Going", "Présent"),
+    ("fr", "Maybe", "Peut-être"),
+    ("fr", "Can't", "Absent"),
+    ("fr", "Pick up to", "Choisissez jusqu'à"),
+    ("es-ES", "Yes!", "¡Sí!"),
+    ("es-ES", "No!", "¡No!"),
+    ("es-ES", "View Results", "Ver resultados"),
+    ("es-ES", "Sign", "Firmar"),
+    ("es-ES", "Going", "Voy"),
+    ("es-ES", "Maybe", "Quizás"),
+    ("es-ES", "Can't", "No puedo"),
+    ("es-ES", "Pick up to", "Elige hasta"),
+    ("pt-BR", "Yes!", "Sim!"),
+    ("pt-BR", "No!", "Não!"),
+    ("pt-BR", "View Results", "Ver resultados"),
+    ("pt-BR", "Sign", "Assinar"),
+    ("pt-BR", "Going", "Vou"),
+    ("pt-BR", "Maybe", "Talvez"),
+    ("pt-BR", "Can't", "Não posso"),
+    ("pt-BR", "Pick up to", "Escolha até"),
+    ("it", "Yes!", "Sì!"),
+    ("it", "No!", "No!"),
+    ("it", "View Results", "Vedi risultati"),
+    ("it", "Sign", "Firma"),
+    ("it", "Going", "Ci sono"),
+    ("it", "Maybe", "Forse"),
+    ("it", "Can't", "Non posso"),
+    ("it", "Pick up to", "Scegli fino a"),
+    ("nl", "Yes!", "Ja!"),
+    ("nl", "No!", "Nee!"),
+    ("nl", "View Results", "Resultaten bekijken"),
+    ("nl", "Sign", "Ondertekenen"),
+    ("nl", "Going", "Ik kom"),
+    ("nl", "Maybe", "Misschien"),
+    ("nl", "Can't", "Kan niet"),
+    ("nl", "Pick up to", "Kies maximaal"),
+];
+
+///A button text in a Discord locale, or as it is if it has no translation there
+pub fn tr<'a>(locale: Option<&str>, text: &'a str) -> &'a str {
+    let Some(locale) = locale else {
+        return text;
+    };
+    TRANSLATIONS
+        .iter()
+        .find(|(l, english, _)| *l == locale && *english == text)
+        .map_or(text, |(_, _, translated)| translated)
+}
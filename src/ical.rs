@@ -0,0 +1,85 @@
+use std::borrow::Cow;
+
+use poise::serenity_prelude::{AttachmentType, Timestamp};
+
+use crate::poll::Poll;
+use crate::tally;
+
+///The start and end of the time slot that won a closed scheduling poll, as unix timestamps
+pub fn winning_slot(poll: &Poll) -> Option<(i64, i64)> {
+    if !poll.closed || poll.slots.is_empty() {
+        return None;
+    }
+    let counts: Vec<usize> = poll.options().iter().map(|o| o.votes.len()).collect();
+    //Ties and polls nobody voted on have no single time to add
+    let [winner] = tally::leaders(&counts)[..] else {
+        return None;
+    };
+    let start = *poll.slots.get(winner)?;
+    Some((start, start + i64::from(poll.slot_minutes) * 60))
+}
+
+///A unix timestamp in iCalendar's UTC form, e.g. 20240131T180000Z
+fn ics_time(unix: i64) -> String {
+    Timestamp::from_unix_timestamp(unix)
+        .unwrap_or_else(|_| Timestamp::now())
+        .to_string()
+        .replace(['-', ':'], "")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+///A calendar file with a single event for the slot
+pub fn ics(poll: &Poll, poll_id: u64, start: i64, end: i64) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//PoisePollBot//Scheduling poll//EN".to_string(),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{poll_id}@poisepollbot"),
+        format!("DTSTAMP:{}", ics_time(Timestamp::now().unix_timestamp())),
+        format!("DTSTART:{}", ics_time(start)),
+        format!("DTEND:{}", ics_time(end)),
+        format!("SUMMARY:{}", escape(&poll.title)),
+        format!("DESCRIPTION:{}", escape(&poll.description)),
+    ];
+    if let Some(link) = poll.link(poll_id) {
+        lines.push(format!("URL:{link}"));
+    }
+    lines.push("END:VEVENT".to_string());
+    lines.push("END:VCALENDAR".to_string());
+    //The format requires CRLF line endings
+    lines.join("\r\n") + "\r\n"
+}
+
+///The calendar file of a closed scheduling poll's winning slot, to attach to its close announcement
+pub fn attachment(poll: &Poll, poll_id: u64) -> Option<AttachmentType<'static>> {
+    let (start, end) = winning_slot(poll)?;
+    Some(AttachmentType::Bytes {
+        data: Cow::Owned(ics(poll, poll_id, start, end).into_bytes()),
+        filename: "event.ics".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_are_in_utc_form() {
+        assert_eq!(ics_time(1_706_724_000), "20240131T180000Z");
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(
+            escape("Games; snacks, drinks\nBring a friend"),
+            "Games\\; snacks\\, drinks\\nBring a friend"
+        );
+    }
+}
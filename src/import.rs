@@ -0,0 +1,157 @@
+use poise::serenity_prelude::Timestamp;
+use serde::Deserialize;
+
+use crate::commands::{eph_reply, from_guild, publish_poll};
+use crate::config::can_create_polls;
+use crate::poll::{Poll, PollKind, PollOption, MAX_OPTIONS};
+use crate::{validate, Context, Error};
+
+const STRAWPOLL_API: &str = "https://api.strawpoll.com/v3/polls";
+//Button labels are capped at 80 characters
+const LABEL_LIMIT: usize = 80;
+
+#[derive(Deserialize)]
+struct StrawPoll {
+    title: String,
+    #[serde(default)]
+    poll_options: Vec<StrawPollOption>,
+    #[serde(default)]
+    poll_config: StrawPollConfig,
+}
+
+#[derive(Deserialize)]
+struct StrawPollOption {
+    //Only text options have a value, meeting polls use dates instead
+    #[serde(default)]
+    value: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct StrawPollConfig {
+    #[serde(default)]
+    is_multiple_choice: bool,
+    #[serde(default)]
+    multiple_choice_max: Option<u32>,
+    //Unix timestamp
+    #[serde(default)]
+    deadline_at: Option<i64>,
+}
+
+///The ID in a StrawPoll link, like https://strawpoll.com/polls/NPgxkzPqrn2
+fn strawpoll_id(url: &str) -> Option<&str> {
+    let url = url.trim().trim_end_matches('/');
+    let path = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let path = path.strip_prefix("www.").unwrap_or(path);
+    let id = path.strip_prefix("strawpoll.com/")?;
+    //Older links leave out the polls/ part
+    let id = id.strip_prefix("polls/").unwrap_or(id);
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())).then_some(id)
+}
+
+async fn fetch_strawpoll(id: &str, api_key: Option<&str>) -> Result<StrawPoll, Error> {
+    let mut request = reqwest::Client::new().get(format!("{STRAWPOLL_API}/{id}"));
+    if let Some(key) = api_key {
+        request = request.header("X-API-KEY", key);
+    }
+    let body = request.send().await?.error_for_status()?.bytes().await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+///Turns a fetched StrawPoll into options, or explains why it can't be imported
+fn strawpoll_options(strawpoll: &StrawPoll) -> Result<Vec<PollOption>, String> {
+    let options = strawpoll
+        .poll_options
+        .iter()
+        .map(|o| {
+            let label = o
+                .value
+                .as_deref()
+                .ok_or("Only text options can be imported")?;
+            //Labels too long for a button are shortened rather than refusing the whole poll
+            let label: String = label.trim().chars().take(LABEL_LIMIT).collect();
+            Ok(PollOption {
+                label: validate::text("option", &label, LABEL_LIMIT)?,
+                capacity: None,
+                votes: Vec::new(),
+                reason: None,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    if options.len() < 2 || options.len() > MAX_OPTIONS {
+        return Err(format!(
+            "That poll has {} options, but polls here need between 2 and {MAX_OPTIONS}",
+            options.len()
+        ));
+    }
+    Ok(options)
+}
+
+//Recreates a StrawPoll as a poll here, votes already cast there aren't carried over
+#[poise::command(slash_command, check = "can_create_polls")]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "Link to the StrawPoll"] url: String,
+    #[description = "Close the poll after this many hours, instead of at the StrawPoll's deadline"]
+    #[min = 1]
+    duration_hours: Option<u32>,
+    #[description = "Let members change or retract their vote"] allow_vote_changes: Option<bool>,
+) -> Result<(), Error> {
+    let Some(id) = strawpoll_id(&url) else {
+        return eph_reply(ctx, "That isn't a StrawPoll link").await;
+    };
+    ctx.defer().await?;
+
+    let strawpoll = match fetch_strawpoll(id, ctx.data().strawpoll_key.as_deref()).await {
+        Ok(strawpoll) => strawpoll,
+        Err(e) => {
+            tracing::warn!("Failed to fetch StrawPoll {id}: {e}");
+            return eph_reply(ctx, "Couldn't fetch that StrawPoll, is it public?").await;
+        }
+    };
+    let options = match strawpoll_options(&strawpoll) {
+        Ok(options) => options,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    let title = match validate::text("title", &strawpoll.title, validate::TITLE_LIMIT) {
+        Ok(title) => title,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+
+    let config = &strawpoll.poll_config;
+    let max_choices = match config.is_multiple_choice {
+        true => config
+            .multiple_choice_max
+            .filter(|max| *max > 0)
+            .unwrap_or(options.len() as u32),
+        false => 1,
+    };
+    let now = Timestamp::now().unix_timestamp();
+    let deadline = match duration_hours {
+        Some(hours) => Some(now + i64::from(hours) * 3600),
+        None => config.deadline_at.filter(|d| *d > now),
+    };
+
+    //Options replace yes and no, so there are no reasons to vote either way
+    let mut poll = Poll::new(
+        title,
+        format!("Imported from {}", url.trim()),
+        String::new(),
+        String::new(),
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        ctx.channel_id().0,
+    );
+    from_guild(ctx, &mut poll);
+    poll.kind = PollKind::Options {
+        options,
+        max_choices,
+        rsvp: false,
+    };
+    poll.allow_changes = allow_vote_changes.unwrap_or(false);
+    poll.deadline = deadline;
+
+    publish_poll(ctx, poll, false, false).await
+}
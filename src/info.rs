@@ -0,0 +1,150 @@
+use poise::serenity_prelude::{Permissions, ShardId};
+
+use crate::{counters, store, Context, Error};
+
+//Shows every command, or the details of one
+#[poise::command(slash_command)]
+pub async fn help(
+    ctx: Context<'_>,
+    #[description = "Command to show the details of"]
+    #[autocomplete = "poise::builtins::autocomplete_command"]
+    command: Option<String>,
+) -> Result<(), Error> {
+    poise::builtins::help(
+        ctx,
+        command.as_deref(),
+        poise::builtins::HelpConfiguration {
+            extra_text_at_bottom: "Use /help <command> for the details of a command",
+            ephemeral: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+///Formats a duration in seconds like 3d 4h 5m
+fn format_uptime(seconds: u64) -> String {
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+    match (days, hours) {
+        (0, 0) => format!("{minutes}m"),
+        (0, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h {minutes}m"),
+    }
+}
+
+//Shows the bot's version, uptime and where to get it
+#[poise::command(slash_command)]
+pub async fn about(ctx: Context<'_>) -> Result<(), Error> {
+    let bot_id = ctx.serenity_context().cache.current_user_id();
+    //Everything the bot needs to post polls, hand out vote roles and create events
+    let permissions = Permissions::SEND_MESSAGES
+        | Permissions::EMBED_LINKS
+        | Permissions::MANAGE_ROLES
+        | Permissions::MANAGE_EVENTS
+        | Permissions::MANAGE_MESSAGES;
+    let invite = format!(
+        "https://discord.com/api/oauth2/authorize?client_id={bot_id}&permissions={}&scope=bot%20applications.commands",
+        permissions.bits()
+    );
+    let uptime = format_uptime(ctx.data().started.elapsed().as_secs());
+
+    ctx.send(|r| {
+        r.ephemeral(true).embed(|e| {
+            e.title("PoisePollBot")
+                .description("Polls, petitions, suggestions and signups for your server")
+                .field("Version", env!("CARGO_PKG_VERSION"), true)
+                .field("Uptime", uptime, true)
+                .field("Invite", format!("[Add the bot]({invite})"), true);
+            if let Some(support) = &ctx.data().support_server {
+                e.field("Support", support, true);
+            }
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+///Memory the bot is using in MiB, read from /proc so only known on Linux
+fn memory_usage() -> Option<f64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kib: f64 = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kib / 1024.0)
+}
+
+//Shows how much the bot is used and how it is running
+#[poise::command(slash_command)]
+pub async fn botstats(ctx: Context<'_>) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    //The first run ever counts every stored poll, which can take a moment
+    ctx.defer().await?;
+
+    let totals = counters::global(&persist)?;
+    let active = counters::open_polls(&persist)?;
+
+    let serenity_ctx = ctx.serenity_context();
+    let latency = {
+        let shard_manager = ctx.framework().shard_manager();
+        let manager = shard_manager.lock().await;
+        let runners = manager.runners.lock().await;
+        runners
+            .get(&ShardId(serenity_ctx.shard_id))
+            .and_then(|runner| runner.latency)
+    };
+    //Shards only know their latency after their first heartbeat
+    let latency = latency.map_or("Not measured yet".to_string(), |l| {
+        format!("{}ms", l.as_millis())
+    });
+    let memory = memory_usage().map_or("Unknown".to_string(), |m| format!("{m:.1} MiB"));
+    let uptime = format_uptime(ctx.data().started.elapsed().as_secs());
+
+    ctx.send(|r| {
+        r.embed(|e| {
+            e.title("Bot stats")
+                .field("Servers", serenity_ctx.cache.guild_count(), true)
+                .field("Active polls", active, true)
+                .field("Polls created", totals.polls, true)
+                .field("Votes cast", totals.votes, true)
+                .field("Uptime", uptime, true)
+                .field("Shard latency", latency, true)
+                .field("Memory", memory, true)
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+//Shows how many polls this server has run and how many votes they got
+#[poise::command(slash_command, guild_only)]
+pub async fn pollstats(ctx: Context<'_>) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(());
+    };
+    ctx.defer().await?;
+
+    let totals = counters::guild(&persist, guild_id.0)?;
+    let open = store::guild_polls(&persist, guild_id.0)
+        .iter()
+        .filter(|p| !p.closed)
+        .count();
+
+    ctx.send(|r| {
+        r.embed(|e| {
+            e.title("Poll stats")
+                .field("Open polls", open, true)
+                .field("Polls created", totals.polls, true)
+                .field("Votes cast", totals.votes, true)
+        })
+    })
+    .await?;
+    Ok(())
+}
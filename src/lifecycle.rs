@@ -0,0 +1,380 @@
+use std::borrow::Cow;
+
+use poise::serenity_prelude::{
+    AttachmentType, ChannelId, GuildId, Http, Message, ScheduledEventType, Timestamp, UserId,
+};
+use shuttle_persist::PersistInstance;
+
+use crate::config::GuildConfig;
+use crate::poll::{
+    tie_break_pick, Poll, PollEvent, PollMessage, TieBreak, TieResolution, Visibility,
+};
+use crate::scheduler::{self, Job};
+use crate::Error;
+use crate::{counters, forum, native, reaction, receipt, sheets, store, threads};
+
+///Starts tracking a poll that was just posted on a message, posting its live results message
+///and opening its option threads if asked for and scheduling its deadline, reminders and warning
+pub async fn start_poll(
+    http: &Http,
+    persist: &PersistInstance,
+    message: &Message,
+    mut poll: Poll,
+    results_message: bool,
+    option_threads: bool,
+) -> Result<(), Error> {
+    if results_message {
+        let results = message
+            .channel_id
+            .send_message(http, |m| m.embed(|e| poll.results_embed(e)))
+            .await?;
+        //Pinning needs Manage Messages, the results are kept updated either way
+        if let Err(e) = results.pin(http).await {
+            tracing::warn!(
+                "Failed to pin the results message of poll {}: {e}",
+                message.id
+            );
+        }
+        poll.results_message = Some(PollMessage {
+            channel_id: results.channel_id.0,
+            message_id: results.id.0,
+        });
+    }
+    if let Some(seed) = poll.tie_seed.take() {
+        store::save_tie_seed(persist, message.id.0, seed)?;
+    }
+    store::create_poll(persist, message.id.0, &poll)?;
+    if poll.reactions {
+        if let Err(e) = reaction::add(http, message).await {
+            tracing::warn!("Failed to add voting reactions to poll {}: {e}", message.id);
+        }
+    }
+    if option_threads {
+        //The poll works without its threads, so missing thread permissions only get a warning
+        if let Err(e) = threads::open(http, persist, message.id.0, &mut poll).await {
+            tracing::warn!(
+                "Failed to open the option threads of poll {}: {e}",
+                message.id
+            );
+        }
+    }
+    if let Some(deadline) = poll.deadline {
+        scheduler::schedule(
+            persist,
+            deadline,
+            Job::ClosePoll {
+                message_id: message.id.0,
+            },
+        )?;
+    }
+    let now = Timestamp::now().unix_timestamp();
+    scheduler::schedule_reminder(persist, message.id.0, &poll, now)?;
+    scheduler::schedule_warning(persist, message.id.0, &poll, now)?;
+    Ok(())
+}
+
+///Draws the secret seed of a poll settled by a random pick, before it is posted so its embed
+///shows the seed's hash. `start_poll` stores the seed apart from the poll
+pub fn draw_tie_seed(poll: &mut Poll) {
+    if poll.tie_break != TieBreak::Random || poll.tie_seed.is_some() {
+        return;
+    }
+    let seed = rand::random();
+    poll.tie_seed = Some(seed);
+    poll.tie_commitment = Some(receipt::seed_commitment(seed));
+}
+
+///Closes a poll on every message showing it and schedules its event if it passed.
+///`poll` is updated to the closed poll. Returns a one line summary of the outcome
+pub async fn close_poll(
+    http: &Http,
+    persist: &PersistInstance,
+    message_id: u64,
+    poll: &mut Poll,
+) -> Result<String, Error> {
+    //Saved as closed first, so no votes come in while Discord is waited on below and a failure
+    //there can't leave the poll open
+    let update = |poll: &mut Poll| {
+        let was_open = !poll.closed;
+        poll.closed = true;
+        Ok(was_open)
+    };
+    let (_, closed, was_open) = store::update(persist, message_id, update)?.saved()?;
+    *poll = closed;
+    if was_open {
+        counters::poll_ended(persist);
+    }
+    //Ending early leaves the deadline's job and reminders behind otherwise
+    scheduler::cancel_poll(persist, message_id)?;
+    //Discord only lets the bot end polls it posted itself, adopted ones run until they expire
+    if poll.native && !poll.adopted {
+        if let Err(e) = native::expire(http, poll, message_id).await {
+            tracing::warn!("Failed to end native poll {message_id}: {e}");
+        }
+    }
+    //The last synced votes are announced if Discord can't be reached
+    if poll.synced() {
+        if let Err(e) = native::sync(http, persist, message_id, poll).await {
+            tracing::warn!("Failed to copy the votes of poll {message_id}: {e}");
+        }
+    }
+    if poll.tied() {
+        let resolution = break_tie(http, persist, message_id, poll).await;
+        let update = |poll: &mut Poll| {
+            poll.tie_resolution = resolution;
+            Ok(())
+        };
+        *poll = store::update(persist, message_id, update)?.saved()?.1;
+    }
+    //A deleted poll message shouldn't keep the result from being announced
+    if let Err(e) = poll.update_messages(http, message_id).await {
+        tracing::warn!("Failed to show poll {message_id} as closed: {e}");
+    }
+    threads::archive(http, poll, true).await;
+    if !poll.receipts.is_empty() {
+        if let Err(e) = receipt::publish(http, poll).await {
+            tracing::warn!("Failed to publish the receipts of poll {message_id}: {e}");
+        }
+    }
+
+    let outcome = if !poll.options().is_empty() {
+        "ended"
+    } else if poll.passed() {
+        "passed"
+    } else {
+        "was rejected"
+    };
+    let mut summary = format!("\"{}\" {} ({})", poll.title, outcome, poll.tally_text());
+    summary.push_str(&schedule_event(http, poll).await);
+
+    //Closed DMs are common and nothing to act on
+    if let Err(e) = dm_summary(http, message_id, poll).await {
+        tracing::info!("Couldn't DM the summary of poll {message_id} to its creator: {e}");
+    }
+    let config = poll.guild_id.map(|g| GuildConfig::load(persist, g));
+    if let Some(spreadsheet) = config.as_ref().and_then(|c| c.spreadsheet.clone()) {
+        let link = poll.link(message_id);
+        if let Err(e) = sheets::append_result(&spreadsheet, poll, link).await {
+            tracing::warn!("Failed to add poll {message_id} to its guild's spreadsheet: {e}");
+        }
+    }
+    if let Some(forum) = config.and_then(|c| c.archive_forum) {
+        if let Err(e) = forum::archive(http, forum, message_id, poll).await {
+            tracing::warn!("Failed to post poll {message_id} to its guild's archive forum: {e}");
+        }
+    }
+    Ok(summary)
+}
+
+///DMs the creator how their poll ended, with every vote attached as CSV if they asked for it
+async fn dm_summary(http: &Http, message_id: u64, poll: &Poll) -> Result<(), Error> {
+    //Polls from before creators were recorded have nobody to tell
+    if poll.creator_id == 0 {
+        return Ok(());
+    }
+
+    let voters = poll.voter_count();
+    let members = match poll.guild_id {
+        Some(guild_id) => http
+            .get_guild_with_counts(guild_id)
+            .await
+            .ok()
+            .and_then(|g| g.approximate_member_count),
+        None => None,
+    };
+    let turnout = match members {
+        Some(members) if members > 0 => format!(
+            "{voters} voters, {}% of the server's {members} members",
+            voters as u64 * 100 / members
+        ),
+        _ => format!("{voters} voters"),
+    };
+
+    let mut text = format!(
+        "Your poll \"{}\" has closed\nResult: {}\nVotes: {}\nTurnout: {turnout}",
+        poll.title,
+        poll.outcome(),
+        poll.tally_text()
+    );
+    if let Some(reasons) = poll.reasons_summary() {
+        text.push_str("\nWhy people voted:\n");
+        text.push_str(&reasons);
+    }
+    if let Some(link) = poll.link(message_id) {
+        text.push('\n');
+        text.push_str(&link);
+    }
+
+    let dm = UserId(poll.creator_id).create_dm_channel(http).await?;
+    dm.send_message(http, |m| {
+        m.content(text);
+        if poll.summary_csv {
+            m.add_file(AttachmentType::Bytes {
+                data: Cow::Owned(votes_csv(poll).into_bytes()),
+                filename: "votes.csv".to_string(),
+            });
+        }
+        m
+    })
+    .await?;
+    Ok(())
+}
+
+///Every vote of a poll as CSV, only the counts for anonymous polls
+fn votes_csv(poll: &Poll) -> String {
+    if poll.visibility == Visibility::Anonymous {
+        let mut csv = "choice,votes\n".to_string();
+        for (choice, votes) in poll.vote_groups() {
+            csv.push_str(&format!(
+                "\"{}\",{}\n",
+                choice.replace('"', "\"\""),
+                votes.len()
+            ));
+        }
+        return csv;
+    }
+
+    let quote = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+    let mut csv = "choice,user_id,reason\n".to_string();
+    for (choice, votes) in poll.vote_groups() {
+        for vote in votes {
+            let reason = poll.vote_reasons.get(&vote.0).map_or("", String::as_str);
+            csv.push_str(&format!("{},{},{}\n", quote(choice), vote.0, quote(reason)));
+        }
+    }
+    csv
+}
+
+///Lets a closed poll be voted on again, closing it at the new deadline if one is given
+pub async fn reopen_poll(
+    http: &Http,
+    persist: &PersistInstance,
+    message_id: u64,
+    poll: &mut Poll,
+    deadline: Option<i64>,
+) -> Result<(), Error> {
+    let update = |poll: &mut Poll| {
+        let was_closed = poll.closed;
+        poll.closed = false;
+        //The tie is settled again once the poll closes for good
+        poll.tie_resolution = None;
+        poll.deadline = deadline;
+        Ok(was_closed)
+    };
+    let (_, reopened, was_closed) = store::update(persist, message_id, update)?.saved()?;
+    *poll = reopened;
+    if was_closed {
+        counters::poll_opened(persist);
+    }
+    if let Some(deadline) = deadline {
+        scheduler::schedule(persist, deadline, Job::ClosePoll { message_id })?;
+    }
+    let now = Timestamp::now().unix_timestamp();
+    scheduler::schedule_reminder(persist, message_id, poll, now)?;
+    scheduler::schedule_warning(persist, message_id, poll, now)?;
+    poll.update_messages(http, message_id).await?;
+    threads::archive(http, poll, false).await;
+    Ok(())
+}
+
+///Settles a tied poll according to the tie-break its creator chose
+async fn break_tie(
+    http: &Http,
+    persist: &PersistInstance,
+    message_id: u64,
+    poll: &Poll,
+) -> Option<TieResolution> {
+    match poll.tie_break {
+        TieBreak::Declare => None,
+        TieBreak::Random => {
+            //Polls posted before seeds were drawn fall back to their message ID
+            let seed = store::tie_seed(persist, message_id).unwrap_or(message_id);
+            Some(TieResolution::Random {
+                seed,
+                winner: tie_break_pick(seed),
+            })
+        }
+        TieBreak::Creator => Some(TieResolution::Creator { winner: None }),
+        //The poll is already closed, so a runoff that can't be posted leaves the tie declared
+        //instead of stopping the close halfway
+        TieBreak::Runoff => match start_runoff(http, persist, poll).await {
+            Ok(message_id) => Some(TieResolution::Runoff { message_id }),
+            Err(e) => {
+                tracing::warn!("Failed to start the runoff of poll {message_id}: {e}");
+                None
+            }
+        },
+    }
+}
+
+///Posts a 24 hour runoff of a tied poll in the same channel, returning its message ID
+async fn start_runoff(http: &Http, persist: &PersistInstance, poll: &Poll) -> Result<u64, Error> {
+    let channel_id = poll.channel_id.ok_or("The poll's channel is unknown")?;
+
+    let mut runoff = Poll::new(
+        format!("Runoff: {}", poll.title),
+        poll.description.clone(),
+        poll.reason_to_vote_yes.clone(),
+        poll.reason_to_vote_no.clone(),
+        poll.creator_id,
+        poll.guild_id,
+        channel_id,
+    );
+    runoff.locale = poll.locale.clone();
+    runoff.allow_changes = poll.allow_changes;
+    runoff.event = poll.event.clone();
+    let deadline = Timestamp::now().unix_timestamp() + 24 * 3600;
+    runoff.deadline = Some(deadline);
+    //A runoff that ties again is settled randomly instead of running forever
+    runoff.tie_break = TieBreak::Random;
+    draw_tie_seed(&mut runoff);
+
+    let message = ChannelId(channel_id)
+        .send_message(http, |m| {
+            m.embed(|e| runoff.embed(e))
+                .components(|c| c.set_action_rows(runoff.buttons()))
+        })
+        .await?;
+    if let Some(seed) = runoff.tie_seed {
+        store::save_tie_seed(persist, message.id.0, seed)?;
+    }
+    store::create_poll(persist, message.id.0, &runoff)?;
+    scheduler::schedule(
+        persist,
+        deadline,
+        Job::ClosePoll {
+            message_id: message.id.0,
+        },
+    )?;
+    Ok(message.id.0)
+}
+
+///Creates the scheduled event of a passed poll, returning a note on how it went for summaries
+pub async fn schedule_event(http: &Http, poll: &Poll) -> String {
+    let (true, Some(event)) = (poll.passed(), &poll.event) else {
+        return String::new();
+    };
+    match create_event(http, poll, event).await {
+        Ok(()) => ", its scheduled event was created".to_string(),
+        Err(e) => format!(", but its event could not be scheduled: {e}"),
+    }
+}
+
+///Creates the guild scheduled event attached to a passed poll
+async fn create_event(http: &Http, poll: &Poll, event: &PollEvent) -> Result<(), Error> {
+    let guild_id = poll.guild_id.ok_or("The poll isn't in a server")?;
+
+    //Scheduled event descriptions are capped at 1000 characters
+    let description: String = poll.description.chars().take(1000).collect();
+    GuildId(guild_id)
+        .create_scheduled_event(http, |e| {
+            e.name(&poll.title)
+                .description(description)
+                .kind(ScheduledEventType::External)
+                .location(&event.location)
+                .start_time(event.start)
+                .end_time(event.end)
+        })
+        .await?;
+    Ok(())
+}
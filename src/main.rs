@@ -1,126 +1,76 @@
+mod audit;
+mod automod;
+mod commands;
+mod config;
+mod context;
+mod counters;
+mod filter;
+mod forum;
+mod handler;
+mod i18n;
+mod ical;
+mod import;
+mod info;
+mod lifecycle;
+mod migrate;
+mod milestone;
+mod mydata;
+mod native;
+mod owner;
+mod petition;
+mod poll;
+mod potd;
+mod prefs;
+mod presence;
+mod queue;
+mod reaction;
+mod receipt;
+mod refresh;
+mod rsvp;
+mod runtime;
+mod scheduler;
+mod search;
+mod sheets;
+mod signup;
+mod store;
+mod suggest;
+mod tally;
+mod threads;
+mod timepoll;
+mod validate;
+mod vote;
+mod voters;
+
+use std::time::Instant;
+
 use anyhow::Context as _;
-use once_cell::sync::Lazy;
-use poise::serenity_prelude::{
-    ButtonStyle, CacheHttp, Color, CreateActionRow, Http, InteractionResponseType, InteractionType,
-    MessageComponentInteraction,
-};
-use poise::{serenity_prelude as serenity, BoxFuture, Event, FrameworkContext};
-use serde::{Deserialize, Serialize};
+use poise::serenity_prelude as serenity;
 use shuttle_persist::PersistInstance;
 use shuttle_poise::ShuttlePoise;
 use shuttle_secrets::SecretStore;
 
-//Static poll buttons as they are the same and do not need to be recreated every time
-static POLL_BUTTONS: Lazy<CreateActionRow> = Lazy::new(|| {
-    let mut row = CreateActionRow::default();
-
-    row.create_button(|b| {
-        b.custom_id("poll_yes")
-            .label("Yes!")
-            .style(ButtonStyle::Success)
-    })
-    .create_button(|b| {
-        b.custom_id("poll_no")
-            .label("No!")
-            .style(ButtonStyle::Danger)
-    })
-    .create_button(|b| {
-        b.custom_id("poll_view")
-            .label("View Results")
-            .style(ButtonStyle::Primary)
-    });
-
-    row
-});
-
 #[derive(Clone)]
-struct Data {
+pub struct Data {
     persist: PersistInstance,
+    started: Instant,
+    support_server: Option<String>,
+    dev_guild: Option<u64>,
+    strawpoll_key: Option<String>,
 } // User data, which is stored and accessible in all command invocations
 
-#[derive(Serialize, Deserialize, Clone)]
-struct Poll {
-    title: String,
-    description: String,
-    reason_to_vote_yes: String,
-    reason_to_vote_no: String,
-    yes_votes: Vec<PollVote>,
-    no_votes: Vec<PollVote>,
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-//u64 = UserId
-struct PollVote(u64);
-
-type Error = Box<dyn std::error::Error + Send + Sync>;
-type Context<'a> = poise::Context<'a, Data, Error>;
-
-//Creates a poll
-#[poise::command(slash_command)]
-async fn poll(
-    ctx: Context<'_>,
-    title: String,
-    description: String,
-    reason_to_vote_yes: String,
-    reason_to_vote_no: String,
-) -> Result<(), Error> {
-    let persist = ctx.data().clone().persist;
-
-    let reply = ctx
-        .send(|r| {
-            r.embed(|e| {
-                e.title(title.clone())
-                    .description(description.clone())
-                    .color(Color::from_rgb(0, 255, 0))
-                    .field("Yes", reason_to_vote_yes.clone(), true)
-                    .field("No", reason_to_vote_no.clone(), true)
-            })
-            .components(|c| c.add_action_row(POLL_BUTTONS.clone()))
-        })
-        .await?;
-
-    let message = reply.message().await?;
-    persist.save(
-        &message.id.to_string(),
-        Poll {
-            title,
-            description,
-            reason_to_vote_yes,
-            reason_to_vote_no,
-            yes_votes: Vec::new(),
-            no_votes: Vec::new(),
-        },
-    )?;
-    Ok(())
-}
-
-///Responds to a component interaction with ephemeral text
-async fn eph_text(
-    interaction: &MessageComponentInteraction,
-    text: impl Into<String>,
-    http: &Http,
-) -> Result<(), Error> {
-    interaction
-        .create_interaction_response(http, |r| {
-            r.kind(InteractionResponseType::ChannelMessageWithSource)
-                .interaction_response_data(|d| d.ephemeral(true).content(text.into()))
-        })
-        .await?;
-    Ok(())
-}
-
-///Check if a user has voted
-fn get_voted(
-    component_interaction: &MessageComponentInteraction,
-    yes_votes: &[PollVote],
-    no_votes: &[PollVote],
-) -> bool {
-    yes_votes
-        .iter()
-        .any(|v| component_interaction.user.id.0 == v.0)
-        || no_votes
-            .iter()
-            .any(|v| component_interaction.user.id.0 == v.0)
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Context<'a> = poise::Context<'a, Data, Error>;
+pub type ApplicationContext<'a> = poise::ApplicationContext<'a, Data, Error>;
+
+/// The prefix of text commands in the guild the message was sent in
+async fn guild_prefix(
+    ctx: poise::PartialContext<'_, Data, Error>,
+) -> Result<Option<String>, Error> {
+    let prefix = ctx
+        .guild_id
+        .and_then(|g| config::GuildConfig::load(&ctx.data.persist, g.0).prefix)
+        .unwrap_or_else(|| config::DEFAULT_PREFIX.to_string());
+    Ok(Some(prefix))
 }
 
 #[shuttle_runtime::main]
@@ -132,83 +82,107 @@ async fn poise(
     let discord_token = secret_store
         .get("DISCORD_TOKEN")
         .context("'DISCORD_TOKEN' was not found")?;
+    // An optional invite to the support server, shown by /about
+    let support_server = secret_store.get("SUPPORT_SERVER");
+    // An optional StrawPoll API key for /poll import, public polls can be fetched without one
+    let strawpoll_key = secret_store.get("STRAWPOLL_API_KEY");
+    // Set `GOOGLE_SERVICE_ACCOUNT` to a service account key file to let guilds log results to Google Sheets
+    if let Some(key_file) = secret_store.get("GOOGLE_SERVICE_ACCOUNT") {
+        sheets::init(&key_file).map_err(|e| {
+            anyhow::anyhow!("'GOOGLE_SERVICE_ACCOUNT' is not a valid key file: {e}")
+        })?;
+    }
+    // Set `DEV_GUILD_ID` to register commands instantly in that guild only while developing
+    let dev_guild = match secret_store.get("DEV_GUILD_ID") {
+        Some(id) => Some(
+            id.parse::<u64>()
+                .context("'DEV_GUILD_ID' is not a guild ID")?,
+        ),
+        None => None,
+    };
+    // Set `MIGRATE_ON_STARTUP = "true"` to move stored polls to the current schema before starting
+    let migrate_on_startup = secret_store
+        .get("MIGRATE_ON_STARTUP")
+        .map_or(false, |v| v == "true");
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![poll()],
-            event_handler: |ctx: &serenity::Context,
-                            event,
-                            fw_ctx: FrameworkContext<Data, Error>,
-                            _|
-             -> BoxFuture<'_, Result<(), Error>> {
-                Box::pin(async move {
-                    if let Event::InteractionCreate { interaction } = event {
-                        if interaction.kind() != InteractionType::MessageComponent {
-                            return Ok(());
-                        }
-
-                        let component_interaction = interaction.as_message_component().unwrap();
-                        let component_data = component_interaction.clone().data;
-
-                        let poll_id = &component_interaction.message.id.to_string();
-                        let mut poll: Poll = fw_ctx.user_data.persist.load(poll_id)?;
-
-                        if !component_data.custom_id.starts_with("poll_") {
-                            return eph_text(component_interaction, "Unknown id", ctx.http()).await;
-                        }
-
-                        if get_voted(component_interaction, &poll.yes_votes, &poll.no_votes) {
-                            return eph_text(
-                                component_interaction,
-                                "You already voted!",
-                                ctx.http(),
-                            )
-                            .await;
-                        }
-
-                        match component_data.custom_id.as_str() {
-                            "poll_yes" => {
-                                eph_text(component_interaction, "You voted yes!", ctx.http())
-                                    .await?;
-
-                                poll.yes_votes
-                                    .append(&mut vec![PollVote(component_interaction.user.id.0)])
-                            }
-                            "poll_no" => {
-                                eph_text(component_interaction, "You voted no!", ctx.http())
-                                    .await?;
-
-                                poll.no_votes
-                                    .append(&mut vec![PollVote(component_interaction.user.id.0)])
-                            }
-                            "poll_view" => {
-                                return eph_text(
-                                    component_interaction,
-                                    format!(
-                                        "Yes: {} No: {}",
-                                        poll.yes_votes.len(),
-                                        poll.no_votes.len()
-                                    ),
-                                    ctx.http(),
-                                )
-                                .await;
-                            }
-                            _ => {}
-                        }
-
-                        fw_ctx.user_data.clone().persist.save(poll_id, poll)?;
-                    }
-                    Ok(())
-                })
+            commands: vec![
+                commands::poll(),
+                commands::my_vote(),
+                commands::vote(),
+                commands::results(),
+                config::pollconfig(),
+                info::about(),
+                info::botstats(),
+                info::pollstats(),
+                info::help(),
+                migrate::migrate_polls(),
+                mydata::mydata(),
+                owner::resync(),
+                owner::loadtest(),
+                owner::broadcast(),
+                runtime::runtime(),
+                petition::petition(),
+                potd::potd(),
+                prefs::preferences(),
+                prefs::privacy(),
+                queue::pollqueue(),
+                rsvp::rsvp(),
+                signup::signup(),
+                suggest::suggest(),
+                suggest::suggestion(),
+                timepoll::timepoll(),
+                native::nativepoll(),
+                native::track(),
+            ],
+            prefix_options: poise::PrefixFrameworkOptions {
+                dynamic_prefix: Some(|ctx| Box::pin(guild_prefix(ctx))),
+                ..Default::default()
+            },
+            command_check: Some(|ctx| Box::pin(runtime::command_check(ctx))),
+            event_handler: |ctx, event, fw_ctx, _| {
+                Box::pin(handler::event_handler(ctx, event, fw_ctx))
             },
             ..Default::default()
         })
         .token(discord_token)
-        .intents(serenity::GatewayIntents::non_privileged())
+        // Message content is privileged and has to be enabled for the bot to read text commands,
+        // server members too for the warning DMs to list who hasn't voted
+        .intents(
+            serenity::GatewayIntents::non_privileged()
+                | serenity::GatewayIntents::MESSAGE_CONTENT
+                | serenity::GatewayIntents::GUILD_MEMBERS,
+        )
         .setup(|ctx, _ready, framework| {
             Box::pin(async move {
-                poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data { persist })
+                let commands = &framework.options().commands;
+                match dev_guild {
+                    Some(guild_id) => {
+                        poise::builtins::register_in_guild(
+                            ctx,
+                            commands,
+                            serenity::GuildId(guild_id),
+                        )
+                        .await?
+                    }
+                    None => poise::builtins::register_globally(ctx, commands).await?,
+                }
+                if migrate_on_startup {
+                    tracing::info!("{}", migrate::migrate(&persist)?);
+                }
+                runtime::reload(&persist);
+                prefs::load_hidden(&persist);
+                counters::spawn_backfill(persist.clone());
+                scheduler::spawn(ctx.http.clone(), persist.clone());
+                presence::spawn(ctx.clone(), persist.clone());
+                Ok(Data {
+                    persist,
+                    started: Instant::now(),
+                    support_server,
+                    dev_guild,
+                    strawpoll_key,
+                })
             })
         })
         .build()
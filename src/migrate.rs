@@ -0,0 +1,67 @@
+use shuttle_persist::PersistInstance;
+
+use crate::commands::eph_reply;
+use crate::store;
+use crate::{Context, Error};
+
+///Message IDs of every poll stored, including closed polls only stored under the archive key
+pub fn stored_polls(persist: &PersistInstance) -> Result<Vec<u64>, Error> {
+    let mut ids: Vec<u64> = persist
+        .list()?
+        .iter()
+        .filter_map(|k| k.strip_prefix("archive_").unwrap_or(k).parse().ok())
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    Ok(ids)
+}
+
+///Re-saves every stored poll in the current schema, which fills in fields added since it was
+///saved, moves closed polls back under their message ID with a summary in the archive and
+///rebuilds the guild indexes. Returns a report of what happened
+pub fn migrate(persist: &PersistInstance) -> Result<String, Error> {
+    let ids = stored_polls(persist)?;
+    let mut failed = Vec::new();
+    let mut migrated = 0;
+
+    for &id in &ids {
+        //Loading goes through serde's defaults, so old blobs come back with every new field
+        match store::update(persist, id, |_| Ok(()))? {
+            store::Updated::Saved { poll_id, poll, .. } => {
+                store::list_poll(persist, poll_id, &poll)?;
+                migrated += 1;
+            }
+            store::Updated::Refused(e) => failed.push(format!("{id}: {e}")),
+        }
+    }
+
+    //Every poll must still be there, exactly once, after being moved around
+    let after = stored_polls(persist)?;
+    let mut report = format!(
+        "Found {} polls, migrated {migrated}, {} stored afterwards",
+        ids.len(),
+        after.len()
+    );
+    if after != ids {
+        report.push_str("\nThe polls stored afterwards don't match the ones found, check the logs");
+        tracing::error!("Migration changed the stored polls from {ids:?} to {after:?}");
+    }
+    if !failed.is_empty() {
+        report.push_str(&format!(
+            "\nCould not read {} polls:\n{}",
+            failed.len(),
+            failed.join("\n")
+        ));
+    }
+    Ok(report)
+}
+
+//Moves every stored poll to the current storage schema, only for the bot's owners
+#[poise::command(slash_command, owners_only, hide_in_help)]
+pub async fn migrate_polls(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let report = migrate(&ctx.data().persist)?;
+    tracing::info!("{report}");
+    //Reports listing many unreadable polls are cut short to fit in a message
+    eph_reply(ctx, report.chars().take(2000).collect::<String>()).await
+}
@@ -0,0 +1,186 @@
+use poise::serenity_prelude::{ChannelId, Http, UserId};
+use serde::{Deserialize, Serialize};
+
+use crate::poll::Poll;
+use crate::tally;
+use crate::Error;
+
+///What to tell a poll's creator about while it runs, set with /poll notify
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Milestones {
+    //Voters the poll needs
+    #[serde(default)]
+    pub quorum: Option<u32>,
+    //Voter counts the creator wants to hear about
+    #[serde(default)]
+    pub counts: Vec<u32>,
+    #[serde(default)]
+    pub leader_changes: bool,
+    //Mentions the creator in the poll's channel instead of DMing them
+    #[serde(default)]
+    pub in_channel: bool,
+    //What was already announced, so votes that are retracted and cast again don't repeat it
+    #[serde(default)]
+    pub quorum_reached: bool,
+    #[serde(default)]
+    pub reached: Vec<u32>,
+    #[serde(default)]
+    pub leader: Option<String>,
+}
+
+impl Milestones {
+    ///Marks what the poll already reached as announced, for when the settings change mid-poll
+    pub fn catch_up(&mut self, poll: &Poll) {
+        let voters = poll.voter_count() as u32;
+        self.quorum_reached = self.quorum.map_or(false, |q| voters >= q);
+        self.reached = self
+            .counts
+            .iter()
+            .copied()
+            .filter(|c| voters >= *c)
+            .collect();
+        self.leader = leader(poll);
+    }
+}
+
+///The name of the only option with the most votes, if one leads alone
+fn leader(poll: &Poll) -> Option<String> {
+    let groups = poll.vote_groups();
+    let counts: Vec<usize> = groups.iter().map(|(_, votes)| votes.len()).collect();
+    match tally::leaders(&counts).as_slice() {
+        [leader] => Some(groups[*leader].0.to_string()),
+        _ => None,
+    }
+}
+
+///Everything the poll newly reached with the latest vote, recording it so it's only announced once
+pub fn check(poll: &mut Poll) -> Vec<String> {
+    let voters = poll.voter_count() as u32;
+    let current_leader = leader(poll);
+    let title = poll.title.clone();
+    let milestones = &mut poll.milestones;
+    let mut notes = Vec::new();
+
+    if let Some(quorum) = milestones.quorum {
+        if !milestones.quorum_reached && voters >= quorum {
+            milestones.quorum_reached = true;
+            notes.push(format!("\"{title}\" reached its quorum of {quorum} voters"));
+        }
+    }
+
+    let newly_reached: Vec<u32> = milestones
+        .counts
+        .iter()
+        .copied()
+        .filter(|c| voters >= *c && !milestones.reached.contains(c))
+        .collect();
+    //Only the highest is worth a message when one vote passes several at once
+    if let Some(highest) = newly_reached.iter().max() {
+        notes.push(format!("\"{title}\" reached {highest} voters"));
+    }
+    milestones.reached.extend(newly_reached);
+
+    //Ties keep the last leader, so a tie that goes back the same way isn't a flip
+    if let Some(current) = current_leader {
+        let flipped = milestones.leader.as_ref().map_or(false, |l| *l != current);
+        if flipped && milestones.leader_changes {
+            notes.push(format!("{current} is now leading \"{title}\""));
+        }
+        milestones.leader = Some(current);
+    }
+    notes
+}
+
+///Tells the creator what their poll reached, in a DM or its channel
+pub async fn announce(http: &Http, poll_id: u64, poll: &Poll, notes: &[String]) {
+    //Polls from before creators were recorded have nobody to tell
+    if notes.is_empty() || poll.creator_id == 0 {
+        return;
+    }
+    //Closed DMs are common and shouldn't fail the vote
+    if let Err(e) = send(http, poll_id, poll, notes).await {
+        tracing::info!("Couldn't tell the creator of poll {poll_id} about a milestone: {e}");
+    }
+}
+
+async fn send(http: &Http, poll_id: u64, poll: &Poll, notes: &[String]) -> Result<(), Error> {
+    let mut text = notes.join("\n");
+    if let Some(link) = poll.link(poll_id) {
+        text.push('\n');
+        text.push_str(&link);
+    }
+
+    match (poll.milestones.in_channel, poll.channel_id) {
+        (true, Some(channel_id)) => {
+            ChannelId(channel_id)
+                .send_message(http, |m| {
+                    m.content(format!("<@{}> {text}", poll.creator_id))
+                        .allowed_mentions(|a| a.empty_parse().users([poll.creator_id]))
+                })
+                .await?;
+        }
+        _ => {
+            let dm = UserId(poll.creator_id).create_dm_channel(http).await?;
+            dm.say(http, text).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poll::Choice;
+
+    fn poll(milestones: Milestones) -> Poll {
+        let mut poll = Poll::new(
+            "Title".to_string(),
+            "Description".to_string(),
+            "Yes".to_string(),
+            "No".to_string(),
+            1,
+            None,
+            1,
+        );
+        poll.milestones = milestones;
+        poll
+    }
+
+    #[test]
+    fn announces_each_milestone_once() {
+        let mut poll = poll(Milestones {
+            quorum: Some(2),
+            counts: vec![1, 3],
+            ..Milestones::default()
+        });
+        poll.add_vote(1, Choice::Yes);
+        assert_eq!(check(&mut poll), vec!["\"Title\" reached 1 voters"]);
+
+        poll.add_vote(2, Choice::Yes);
+        assert_eq!(
+            check(&mut poll),
+            vec!["\"Title\" reached its quorum of 2 voters"]
+        );
+
+        poll.remove_vote(2);
+        check(&mut poll);
+        poll.add_vote(2, Choice::Yes);
+        assert!(check(&mut poll).is_empty());
+    }
+
+    #[test]
+    fn announces_leader_changes_but_not_ties() {
+        let mut poll = poll(Milestones {
+            leader_changes: true,
+            ..Milestones::default()
+        });
+        poll.add_vote(1, Choice::Yes);
+        assert!(check(&mut poll).is_empty());
+
+        poll.add_vote(2, Choice::No);
+        assert!(check(&mut poll).is_empty());
+
+        poll.add_vote(3, Choice::No);
+        assert_eq!(check(&mut poll), vec!["No is now leading \"Title\""]);
+    }
+}
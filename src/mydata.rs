@@ -0,0 +1,102 @@
+use std::borrow::Cow;
+
+use poise::serenity_prelude::{self as serenity, AttachmentType, GuildId, Timestamp};
+use shuttle_persist::PersistInstance;
+
+use crate::poll::Choice;
+use crate::{migrate, store, Context, Error};
+
+///Quotes a CSV field, doubling any quotes inside it
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+///Every poll a user voted in as CSV with their choice on each, and how many there were
+fn votes_csv(
+    ctx: &serenity::Context,
+    persist: &PersistInstance,
+    user_id: u64,
+) -> Result<(String, usize), Error> {
+    let mut csv = "guild,title,choice,voted_at,poll_id\n".to_string();
+    let mut count = 0;
+    for id in migrate::stored_polls(persist)? {
+        let Ok((_, poll)) = store::load_poll(persist, id) else {
+            continue;
+        };
+        //Members can always see their own vote, even on anonymous polls
+        let choices: Vec<&str> = poll
+            .options_of(user_id)
+            .into_iter()
+            .map(|i| poll.options()[i].label.as_str())
+            .chain(poll.vote_of(user_id).map(Choice::as_str))
+            .collect();
+        if choices.is_empty() {
+            continue;
+        }
+
+        let guild = match poll.guild_id {
+            Some(guild_id) => GuildId(guild_id)
+                .name(ctx)
+                .unwrap_or_else(|| guild_id.to_string()),
+            None => "Direct message".to_string(),
+        };
+        //Votes from before vote times were recorded have none
+        let voted_at = poll
+            .voted_at
+            .get(&user_id)
+            .and_then(|t| Timestamp::from_unix_timestamp(*t).ok())
+            .map(|t| t.to_string())
+            .unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{voted_at},{id}\n",
+            quote(&guild),
+            quote(&poll.title),
+            quote(&choices.join("; "))
+        ));
+        count += 1;
+    }
+    Ok((csv, count))
+}
+
+//Parent command, only its subcommands can be invoked
+#[poise::command(slash_command, subcommands("export"))]
+pub async fn mydata(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+//DMs you a file of every poll you voted in, with what you voted
+#[poise::command(slash_command)]
+async fn export(ctx: Context<'_>) -> Result<(), Error> {
+    //Every stored poll is read, which can take longer than Discord waits for a reply
+    ctx.defer_ephemeral().await?;
+    let persist = ctx.data().clone().persist;
+    let (csv, count) = votes_csv(ctx.serenity_context(), &persist, ctx.author().id.0)?;
+    if count == 0 {
+        ctx.say("You haven't voted in any polls").await?;
+        return Ok(());
+    }
+
+    let http = &ctx.serenity_context().http;
+    let sent = match ctx.author().create_dm_channel(http).await {
+        Ok(dm) => dm
+            .send_message(http, |m| {
+                m.content(format!("Your votes in {count} polls"))
+                    .add_file(AttachmentType::Bytes {
+                        data: Cow::Owned(csv.into_bytes()),
+                        filename: "my_votes.csv".to_string(),
+                    })
+            })
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    let text = match sent {
+        Ok(()) => "Sent you your votes by DM",
+        Err(e) => {
+            tracing::info!("Couldn't DM {} their votes: {e}", ctx.author().id);
+            "I couldn't DM you, do you allow DMs from this server's members?"
+        }
+    };
+    ctx.say(text).await?;
+    Ok(())
+}
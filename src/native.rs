@@ -0,0 +1,524 @@
+use std::collections::HashSet;
+
+use poise::serenity_prelude::{self as serenity, ChannelId, Http, ReactionType, Timestamp};
+use serde::Deserialize;
+use shuttle_persist::PersistInstance;
+
+use crate::automod::{bot_token, DISCORD_API};
+use crate::commands::{eph_reply, from_guild};
+use crate::config::can_create_polls;
+use crate::poll::{Poll, PollKind, PollOption, PollVote, Visibility, MAX_OPTIONS};
+use crate::scheduler::{self, Job};
+use crate::{counters, filter, queue, store, validate, ApplicationContext, Context, Error};
+
+//Limits of Discord's polls, which are stricter than the bot's own
+const QUESTION_LIMIT: usize = 300;
+const ANSWER_LIMIT: usize = 55;
+const MAX_ANSWERS: usize = 10;
+const MAX_HOURS: u32 = 768;
+//Content of the poll's message
+const CONTENT_LIMIT: usize = 2000;
+//Voters listed per page of an answer
+const VOTERS_PAGE: usize = 100;
+
+#[derive(Deserialize)]
+struct CreatedMessage {
+    //Snowflakes are sent as strings
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct FetchedMessage {
+    #[serde(default)]
+    poll: Option<NativePoll>,
+}
+
+#[derive(Deserialize)]
+struct NativePoll {
+    question: PollMedia,
+    answers: Vec<NativeAnswer>,
+    //ISO 8601, unset for polls that don't expire
+    #[serde(default)]
+    expiry: Option<String>,
+    #[serde(default)]
+    allow_multiselect: bool,
+}
+
+#[derive(Deserialize)]
+struct NativeAnswer {
+    poll_media: PollMedia,
+}
+
+#[derive(Deserialize)]
+struct PollMedia {
+    //Answers can be only an emoji
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnswerVoters {
+    users: Vec<Voter>,
+}
+
+#[derive(Deserialize)]
+struct Voter {
+    id: String,
+}
+
+///Posts a poll as one of Discord's own polls in its channel, open until its deadline, returning
+///its message's ID
+pub async fn post(http: &Http, poll: &Poll) -> Result<u64, Error> {
+    let channel_id = poll.channel_id.ok_or("The poll's channel is unknown")?;
+    //Discord polls last whole hours, a deadline between two is rounded up
+    let left = poll.deadline.unwrap_or_default() - Timestamp::now().unix_timestamp();
+    let hours = ((left + 3599) / 3600).clamp(1, i64::from(MAX_HOURS));
+    let multiselect = matches!(poll.kind, PollKind::Options { max_choices, .. } if max_choices > 1);
+    let answers: Vec<serde_json::Value> = poll
+        .options()
+        .iter()
+        .map(|o| serde_json::json!({ "poll_media": { "text": o.label } }))
+        .collect();
+    let message: CreatedMessage = reqwest::Client::new()
+        .post(format!("{DISCORD_API}/channels/{channel_id}/messages"))
+        .header("Authorization", bot_token(http))
+        .json(&serde_json::json!({
+            "content": poll.description,
+            "allowed_mentions": { "parse": [] },
+            "poll": {
+                "question": { "text": poll.title },
+                "answers": answers,
+                "duration": hours,
+                "allow_multiselect": multiselect,
+            },
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(message.id.parse()?)
+}
+
+///Starts tracking a Discord poll the bot just posted
+pub fn start(persist: &PersistInstance, message_id: u64, poll: &Poll) -> Result<(), Error> {
+    store::create_poll(persist, message_id, poll)?;
+    //Discord closes the poll itself, the job announces the result and archives it
+    scheduler::schedule(
+        persist,
+        poll.deadline.unwrap_or_default(),
+        Job::ClosePoll { message_id },
+    )
+}
+
+///The Discord poll on a message, serenity doesn't know about polls yet
+async fn fetch_native(
+    http: &Http,
+    channel_id: u64,
+    message_id: u64,
+) -> Result<Option<NativePoll>, Error> {
+    let message: FetchedMessage = reqwest::Client::new()
+        .get(format!(
+            "{DISCORD_API}/channels/{channel_id}/messages/{message_id}"
+        ))
+        .header("Authorization", bot_token(http))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(message.poll)
+}
+
+///Everyone who voted for an answer. Discord numbers answers from 1 in the order they were sent
+async fn answer_voters(
+    http: &Http,
+    channel_id: u64,
+    message_id: u64,
+    answer_id: usize,
+) -> Result<Vec<u64>, Error> {
+    let client = reqwest::Client::new();
+    let mut voters = Vec::new();
+    loop {
+        let mut request = client
+            .get(format!(
+                "{DISCORD_API}/channels/{channel_id}/polls/{message_id}/answers/{answer_id}"
+            ))
+            .header("Authorization", bot_token(http))
+            .query(&[("limit", VOTERS_PAGE)]);
+        if let Some(last) = voters.last() {
+            request = request.query(&[("after", last)]);
+        }
+        let page: AnswerVoters = request.send().await?.error_for_status()?.json().await?;
+        let full = page.users.len() == VOTERS_PAGE;
+        voters.extend(page.users.iter().filter_map(|u| u.id.parse::<u64>().ok()));
+        if !full {
+            return Ok(voters);
+        }
+    }
+}
+
+///Every member who reacted with an emoji, leaving out bots
+async fn reaction_voters(
+    http: &Http,
+    channel_id: u64,
+    message_id: u64,
+    emoji: &str,
+) -> Result<Vec<u64>, Error> {
+    let emoji = ReactionType::try_from(emoji)?;
+    let mut voters = Vec::new();
+    let mut after = None;
+    loop {
+        let page = ChannelId(channel_id)
+            .reaction_users(
+                http,
+                message_id,
+                emoji.clone(),
+                Some(VOTERS_PAGE as u8),
+                after,
+            )
+            .await?;
+        after = page.last().map(|u| u.id);
+        let full = page.len() == VOTERS_PAGE;
+        voters.extend(page.iter().filter(|u| !u.bot).map(|u| u.id.0));
+        if !full {
+            return Ok(voters);
+        }
+    }
+}
+
+///Copies the votes of a Discord poll or reaction vote into the bot's copy of it, so the tally,
+///exports and announcements work like for the bot's own polls. `poll` is updated to the saved copy
+pub async fn sync(
+    http: &Http,
+    persist: &PersistInstance,
+    message_id: u64,
+    poll: &mut Poll,
+) -> Result<(), Error> {
+    let channel_id = poll.channel_id.ok_or("The poll's channel is unknown")?;
+    let mut answers = Vec::new();
+    for (i, option) in poll.options().iter().enumerate() {
+        answers.push(match poll.native {
+            true => answer_voters(http, channel_id, message_id, i + 1).await?,
+            false => reaction_voters(http, channel_id, message_id, &option.label).await?,
+        });
+    }
+
+    let (_, synced, (new_voters, gone_voters)) =
+        store::update(persist, message_id, |poll| Ok(replace_votes(poll, answers)))?.saved()?;
+    for _ in 0..new_voters {
+        counters::vote_cast(persist, synced.guild_id);
+    }
+    for _ in 0..gone_voters {
+        counters::vote_undone(persist, synced.guild_id);
+    }
+    *poll = synced;
+    Ok(())
+}
+
+///Replaces each option's votes with the voters Discord lists for it, returning how many of them
+///hadn't voted before and how many voters took their votes back
+fn replace_votes(poll: &mut Poll, answers: Vec<Vec<u64>>) -> (usize, usize) {
+    let before: HashSet<u64> = poll
+        .vote_groups()
+        .iter()
+        .flat_map(|(_, votes)| votes.iter().map(|v| v.0))
+        .collect();
+    let after: HashSet<u64> = answers.iter().flatten().copied().collect();
+    let now = Timestamp::now().unix_timestamp();
+    let gone: Vec<u64> = before.difference(&after).copied().collect();
+    for voter in &gone {
+        poll.remove_vote(*voter);
+    }
+    if let PollKind::Options { options, .. } = &mut poll.kind {
+        for (option, voters) in options.iter_mut().zip(answers) {
+            option.votes = voters.into_iter().map(PollVote).collect();
+        }
+    }
+    let new: Vec<u64> = after.difference(&before).copied().collect();
+    for voter in &new {
+        poll.voted_at.insert(*voter, now);
+    }
+    (new.len(), gone.len())
+}
+
+///Ends a Discord poll early, polls that already ended are left as they are
+pub async fn expire(http: &Http, poll: &Poll, message_id: u64) -> Result<(), Error> {
+    let channel_id = poll.channel_id.ok_or("The poll's channel is unknown")?;
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{DISCORD_API}/channels/{channel_id}/polls/{message_id}/expire"
+        ))
+        .header("Authorization", bot_token(http))
+        .send()
+        .await?;
+    //Expiring a poll that has already ended is refused as a bad request
+    if response.status() != reqwest::StatusCode::BAD_REQUEST {
+        response.error_for_status()?;
+    }
+    Ok(())
+}
+
+//Creates a poll with Discord's own poll interface, tracked and announced by the bot like any other
+#[poise::command(slash_command, check = "can_create_polls")]
+pub async fn nativepoll(
+    ctx: Context<'_>,
+    #[description = "The question"] question: String,
+    #[description = "Comma separated answers, at most 10"] answers: String,
+    #[description = "Hours until the poll closes, at most 768"]
+    #[min = 1]
+    #[max = 768]
+    duration_hours: u32,
+    #[description = "Text posted above the poll"] description: Option<String>,
+    #[description = "Let members pick more than one answer"] allow_multiple: Option<bool>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+
+    let question = match validate::text("question", &question, QUESTION_LIMIT) {
+        Ok(question) => question,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    let description = description.unwrap_or_default();
+    let description = match validate::optional_text("description", &description, CONTENT_LIMIT) {
+        Ok(description) => description,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    let options = answers
+        .split(',')
+        .map(|answer| {
+            Ok(PollOption {
+                label: validate::text("answer", answer, ANSWER_LIMIT)?,
+                capacity: None,
+                votes: Vec::new(),
+                reason: None,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>();
+    let options = match options {
+        Ok(options) if (2..=MAX_ANSWERS).contains(&options.len()) => options,
+        Ok(_) => {
+            let text = format!("A poll needs between 2 and {MAX_ANSWERS} answers");
+            return eph_reply(ctx, text).await;
+        }
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    let hours = duration_hours.min(MAX_HOURS);
+
+    let mut poll = Poll::new(
+        question,
+        description,
+        String::new(),
+        String::new(),
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        ctx.channel_id().0,
+    );
+    from_guild(ctx, &mut poll);
+    poll.kind = PollKind::Options {
+        max_choices: match allow_multiple.unwrap_or(false) {
+            true => options.len() as u32,
+            false => 1,
+        },
+        options,
+        rsvp: false,
+    };
+    poll.native = true;
+    //Discord shows everyone who voted on its polls
+    poll.visibility = Visibility::Public;
+    poll.deadline = Some(Timestamp::now().unix_timestamp() + i64::from(hours) * 3600);
+
+    if !filter::screen(ctx, &poll).await? {
+        return Ok(());
+    }
+    if queue::needs_approval(ctx).await {
+        return queue::submit(ctx, poll, false, false).await;
+    }
+
+    let http = &ctx.serenity_context().http;
+    let message_id = match post(http, &poll).await {
+        Ok(message_id) => message_id,
+        Err(e) => {
+            tracing::warn!("Failed to post a native poll in {}: {e}", ctx.channel_id());
+            return eph_reply(ctx, "The poll couldn't be posted in this channel").await;
+        }
+    };
+    start(&persist, message_id, &poll)?;
+    eph_reply(ctx, "Your poll was posted").await
+}
+
+#[derive(poise::Modal)]
+#[name = "Track this poll"]
+struct TrackModal {
+    #[name = "Title"]
+    #[max_length = 256]
+    title: String,
+    #[name = "Hours until the poll closes, blank for none"]
+    hours: Option<String>,
+}
+
+///An option per answer of a Discord poll, with when it expires
+fn native_options(native: &NativePoll) -> Result<(Vec<PollOption>, Option<i64>), String> {
+    let options = native
+        .answers
+        .iter()
+        .enumerate()
+        .map(|(i, answer)| PollOption {
+            label: answer
+                .poll_media
+                .text
+                .clone()
+                .unwrap_or_else(|| format!("Answer {}", i + 1)),
+            capacity: None,
+            votes: Vec::new(),
+            reason: None,
+        })
+        .collect();
+    let expiry = native
+        .expiry
+        .as_deref()
+        .and_then(|e| Timestamp::parse(e).ok())
+        .map(|e| e.unix_timestamp());
+    match expiry {
+        Some(expiry) if expiry <= Timestamp::now().unix_timestamp() => {
+            Err("That poll has already ended".to_string())
+        }
+        _ => Ok((options, expiry)),
+    }
+}
+
+///An option per emoji members reacted with, the emoji being its label
+fn reaction_options(message: &serenity::Message) -> Result<Vec<PollOption>, String> {
+    let options: Vec<PollOption> = message
+        .reactions
+        .iter()
+        .map(|r| PollOption {
+            label: r.reaction_type.to_string(),
+            capacity: None,
+            votes: Vec::new(),
+            reason: None,
+        })
+        .collect();
+    if options.len() < 2 || options.len() > MAX_OPTIONS {
+        return Err(format!(
+            "That message needs between 2 and {MAX_OPTIONS} different reactions to be tracked"
+        ));
+    }
+    Ok(options)
+}
+
+//Message context menu that starts tracking a Discord poll or a vote held with reactions,
+//giving it a deadline, exports and a result announcement
+#[poise::command(context_menu_command = "Track this poll", check = "can_create_polls")]
+pub async fn track(
+    app_ctx: ApplicationContext<'_>,
+    message: serenity::Message,
+) -> Result<(), Error> {
+    let ctx = Context::Application(app_ctx);
+    let persist = ctx.data().clone().persist;
+    if store::load_poll(&persist, message.id.0).is_ok() {
+        return eph_reply(ctx, "That poll is already tracked").await;
+    }
+
+    let http = &ctx.serenity_context().http;
+    let native = match fetch_native(http, message.channel_id.0, message.id.0).await {
+        Ok(native) => native,
+        Err(e) => {
+            tracing::warn!("Failed to fetch message {} to track it: {e}", message.id);
+            return eph_reply(ctx, "Couldn't read that message").await;
+        }
+    };
+
+    let now = Timestamp::now().unix_timestamp();
+    let is_native = native.is_some();
+    let (title, (options, max_choices), deadline) = match native {
+        Some(native) => {
+            //Copying in every answer's voters can take longer than Discord waits for a reply
+            ctx.defer_ephemeral().await?;
+            let (options, expiry) = match native_options(&native) {
+                Ok(found) => found,
+                Err(problem) => return eph_reply(ctx, problem).await,
+            };
+            let title = native.question.text.clone().unwrap_or_default();
+            let max_choices = match native.allow_multiselect {
+                true => options.len(),
+                false => 1,
+            };
+            (title, (options, max_choices), expiry)
+        }
+        None => {
+            let options = match reaction_options(&message) {
+                Ok(options) => options,
+                Err(problem) => return eph_reply(ctx, problem).await,
+            };
+            //Reaction votes have no question or deadline of their own, so the member gives them
+            let defaults = TrackModal {
+                title: message
+                    .content
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .chars()
+                    .take(validate::TITLE_LIMIT)
+                    .collect(),
+                hours: None,
+            };
+            let Some(form) = TrackModal::execute_with_defaults(app_ctx, defaults).await? else {
+                return Ok(());
+            };
+            let deadline = match form
+                .hours
+                .as_deref()
+                .map(str::trim)
+                .filter(|h| !h.is_empty())
+            {
+                None => None,
+                Some(hours) => match hours.parse::<u32>() {
+                    Ok(hours) if hours > 0 => Some(now + i64::from(hours) * 3600),
+                    _ => return eph_reply(ctx, "The hours must be a whole number above 0").await,
+                },
+            };
+            let max_choices = options.len();
+            (form.title, (options, max_choices), deadline)
+        }
+    };
+    let title = match validate::text("title", &title, validate::TITLE_LIMIT) {
+        Ok(title) => title,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+
+    let mut poll = Poll::new(
+        title,
+        format!("Tracked from {}", message.link()),
+        String::new(),
+        String::new(),
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        message.channel_id.0,
+    );
+    from_guild(ctx, &mut poll);
+    poll.kind = PollKind::Options {
+        options,
+        max_choices: max_choices as u32,
+        rsvp: false,
+    };
+    poll.native = is_native;
+    poll.reaction_vote = !is_native;
+    poll.adopted = true;
+    //Discord already shows who voted on its polls and who reacted, the bot can't hide it
+    poll.visibility = Visibility::Public;
+    poll.deadline = deadline;
+
+    //The votes so far count too, later ones are copied in when the results are looked at
+    store::create_poll(&persist, message.id.0, &poll)?;
+    sync(http, &persist, message.id.0, &mut poll).await?;
+    if let Some(deadline) = poll.deadline {
+        scheduler::schedule(
+            &persist,
+            deadline,
+            Job::ClosePoll {
+                message_id: message.id.0,
+            },
+        )?;
+    }
+    eph_reply(ctx, format!("Now tracking \"{}\"", poll.title)).await
+}
@@ -0,0 +1,268 @@
+use std::time::{Duration, Instant};
+
+use poise::serenity_prelude::{ChannelId, GuildId, Timestamp};
+
+use crate::commands::eph_reply;
+use crate::config::GuildConfig;
+use crate::poll::{Choice, Poll, PollMessage};
+use crate::scheduler::{self, Job};
+use crate::store::{self, MemoryStore, PollStore};
+use crate::{validate, vote, Context, Error};
+
+//Saved to once per run of /loadtest and removed right after
+const LOAD_TEST_KEY: &str = "loadtest_poll";
+
+//Registers every command again, in the development guild if one is set
+#[poise::command(slash_command, prefix_command, owners_only, hide_in_help)]
+pub async fn resync(ctx: Context<'_>) -> Result<(), Error> {
+    let commands = &ctx.framework().options().commands;
+    match ctx.data().dev_guild {
+        Some(guild_id) => {
+            poise::builtins::register_in_guild(ctx, commands, GuildId(guild_id)).await?;
+            eph_reply(
+                ctx,
+                format!(
+                    "Registered {} commands in the development guild",
+                    commands.len()
+                ),
+            )
+            .await
+        }
+        None => {
+            poise::builtins::register_globally(ctx, commands).await?;
+            eph_reply(
+                ctx,
+                format!(
+                    "Registered {} commands globally, they can take an hour to show up",
+                    commands.len()
+                ),
+            )
+            .await
+        }
+    }
+}
+
+///The value below which a share of the sorted samples fall, like the 99th percentile at 0.99
+fn percentile(sorted: &[Duration], share: f64) -> Duration {
+    let index = ((sorted.len() - 1) as f64 * share).round() as usize;
+    sorted[index]
+}
+
+fn describe(name: &str, mut samples: Vec<Duration>) -> String {
+    samples.sort_unstable();
+    format!(
+        "{name}: p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+        percentile(&samples, 0.5),
+        percentile(&samples, 0.9),
+        percentile(&samples, 0.99),
+        samples[samples.len() - 1]
+    )
+}
+
+///Votes from many threads at once against a poll kept in memory, timing every click.
+///Returns the click timings and how long all of them took
+fn simulate_votes(votes: u32, workers: u32) -> Result<(Vec<Duration>, Duration), Error> {
+    let store = MemoryStore::default();
+    let mut poll = Poll::new(
+        "Load test".to_string(),
+        String::new(),
+        String::new(),
+        String::new(),
+        0,
+        None,
+        0,
+    );
+    poll.allow_changes = true;
+    store.save(0, &poll)?;
+
+    let started = Instant::now();
+    let timings = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..workers)
+            .map(|worker| {
+                let store = &store;
+                scope.spawn(move || {
+                    (0..votes)
+                        .filter(|vote| vote % workers == worker)
+                        .map(|vote| {
+                            let click = vote::Click {
+                                message_id: 0,
+                                custom_id: if vote % 2 == 0 { "poll_yes" } else { "poll_no" },
+                                //Some voters vote again, exercising changes as well as new votes
+                                user_id: u64::from(vote % (votes / 2 + 1)),
+                                guild_id: None,
+                            };
+                            let clicked = Instant::now();
+                            let _ = vote::click(store, &click);
+                            clicked.elapsed()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap_or_default())
+            .collect::<Vec<_>>()
+    });
+    Ok((timings, started.elapsed()))
+}
+
+//Simulates votes in memory and times them along with saves to the real store
+#[poise::command(slash_command, owners_only, hide_in_help)]
+pub async fn loadtest(
+    ctx: Context<'_>,
+    #[description = "Votes to simulate"]
+    #[min = 10]
+    #[max = 100000]
+    votes: Option<u32>,
+    #[description = "Threads voting at once"]
+    #[min = 1]
+    #[max = 64]
+    workers: Option<u32>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let (votes, workers) = (votes.unwrap_or(5000), workers.unwrap_or(8));
+
+    let (timings, total) =
+        tokio::task::spawn_blocking(move || simulate_votes(votes, workers)).await??;
+    let throughput = f64::from(votes) / total.as_secs_f64();
+
+    //Saves a poll with every simulated vote, as big as a real poll with that many voters
+    let persist = ctx.data().clone().persist;
+    let (poll, saves) = tokio::task::spawn_blocking(move || {
+        let mut poll = Poll::new(
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            0,
+            None,
+            0,
+        );
+        for user_id in 0..u64::from(votes) {
+            poll.add_vote(user_id, Choice::Yes);
+        }
+        let saves: Vec<Duration> = (0..20)
+            .map(|_| {
+                let saved = Instant::now();
+                let _ = persist.save(LOAD_TEST_KEY, &poll);
+                saved.elapsed()
+            })
+            .collect();
+        let _ = persist.remove(LOAD_TEST_KEY);
+        (poll, saves)
+    })
+    .await?;
+
+    let report = format!(
+        "{votes} votes from {workers} threads in {total:?}, {throughput:.0} votes/s\n{}\n{}\nThe saved poll held {} votes",
+        describe("Vote handling, including waiting for the vote lock", timings),
+        describe("Saving to the store", saves),
+        poll.yes_votes.len()
+    );
+    tracing::info!("{report}");
+    eph_reply(ctx, report).await
+}
+
+//Asks every server the same question in its announcement channel, with one combined tally
+#[poise::command(slash_command, owners_only, hide_in_help)]
+pub async fn broadcast(
+    ctx: Context<'_>,
+    #[description = "The question, e.g. Should the bot add feature X?"] title: String,
+    #[description = "Details of the question"] description: String,
+    #[description = "Reason to vote yes"] reason_to_vote_yes: Option<String>,
+    #[description = "Reason to vote no"] reason_to_vote_no: Option<String>,
+    #[description = "Close the poll automatically after this many hours"]
+    #[min = 1]
+    duration_hours: Option<u32>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let texts = validate::poll_text(
+        &title,
+        &description,
+        reason_to_vote_yes.as_deref().unwrap_or_default(),
+        reason_to_vote_no.as_deref().unwrap_or_default(),
+    );
+    let [title, description, reason_to_vote_yes, reason_to_vote_no] = match texts {
+        Ok(texts) => texts,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    ctx.defer_ephemeral().await?;
+
+    let mut poll = Poll::new(
+        title,
+        description,
+        reason_to_vote_yes,
+        reason_to_vote_no,
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        ctx.channel_id().0,
+    );
+    //Votes remember their guild, which gives the per server breakdown
+    poll.federated = true;
+    poll.deadline =
+        duration_hours.map(|hours| Timestamp::now().unix_timestamp() + i64::from(hours) * 3600);
+
+    //The original is posted where the command was used, every server gets a copy
+    let http = &ctx.serenity_context().http;
+    let message = ctx
+        .channel_id()
+        .send_message(http, |m| {
+            m.embed(|e| poll.embed(e))
+                .components(|c| c.set_action_rows(poll.buttons()))
+        })
+        .await?;
+    let message_id = message.id.0;
+    store::create_poll(&persist, message_id, &poll)?;
+
+    let (mut unconfigured, mut failed) = (0, 0);
+    for guild_id in ctx.serenity_context().cache.guilds() {
+        let Some(channel_id) = GuildConfig::load(&persist, guild_id.0).announcement_channel else {
+            unconfigured += 1;
+            continue;
+        };
+        if channel_id == message.channel_id.0 {
+            continue;
+        }
+        let copy = ChannelId(channel_id)
+            .send_message(http, |m| {
+                m.embed(|e| poll.embed(e))
+                    .components(|c| c.set_action_rows(poll.buttons()))
+            })
+            .await;
+        match copy {
+            //Each copy is recorded as it is posted, votes come in on the original meanwhile
+            Ok(copy) => {
+                store::save_copy(&persist, copy.id.0, message_id)?;
+                let copy = PollMessage {
+                    channel_id,
+                    message_id: copy.id.0,
+                };
+                let update = |poll: &mut Poll| {
+                    poll.copies.push(copy);
+                    Ok(())
+                };
+                poll = store::update(&persist, message_id, update)?.saved()?.1;
+            }
+            //Channels can be deleted or lose the bot's permissions after being configured
+            Err(e) => {
+                tracing::warn!("Failed to broadcast poll {message_id} to {channel_id}: {e}");
+                failed += 1;
+            }
+        }
+    }
+    //Shows the merged tally on every copy
+    poll.update_messages(http, message_id).await?;
+    if let Some(deadline) = poll.deadline {
+        scheduler::schedule(&persist, deadline, Job::ClosePoll { message_id })?;
+    }
+
+    eph_reply(
+        ctx,
+        format!(
+            "Posted the poll in {} servers, {failed} failed and {unconfigured} have no announcement channel",
+            poll.copies.len()
+        ),
+    )
+    .await
+}
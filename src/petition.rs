@@ -0,0 +1,72 @@
+use poise::serenity_prelude as serenity;
+
+use crate::commands::{eph_reply, from_guild, publish_poll};
+use crate::config::{can_create_polls, is_moderator};
+use crate::poll::{Poll, PollKind};
+use crate::{validate, Context, Error};
+
+//Starts a petition that becomes actionable once enough members sign it
+#[poise::command(slash_command, guild_only, check = "can_create_polls")]
+pub async fn petition(
+    ctx: Context<'_>,
+    #[description = "What the petition is for"] title: String,
+    #[description = "Details of the petition"] description: String,
+    #[description = "Signatures needed before moderators are notified"]
+    #[min = 1]
+    threshold: u32,
+    #[description = "Role to ping once it is reached"] notify_role: Option<serenity::Role>,
+) -> Result<(), Error> {
+    let texts = validate::text("title", &title, validate::TITLE_LIMIT).and_then(|title| {
+        validate::text("description", &description, validate::DESCRIPTION_LIMIT)
+            .map(|description| (title, description))
+    });
+    let (title, description) = match texts {
+        Ok(texts) => texts,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    if let Some(role) = &notify_role {
+        if !can_ping(ctx, role).await {
+            return eph_reply(
+                ctx,
+                "You can only have the petition ping a role you could mention",
+            )
+            .await;
+        }
+    }
+
+    //Petitions only have signatures, so there are no reasons to vote either way
+    let mut poll = Poll::new(
+        title,
+        description,
+        String::new(),
+        String::new(),
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        ctx.channel_id().0,
+    );
+    from_guild(ctx, &mut poll);
+    poll.kind = PollKind::Petition {
+        threshold,
+        notify_role: notify_role.map(|r| r.id.0),
+        actionable: false,
+    };
+
+    publish_poll(ctx, poll, false, false).await
+}
+
+///Whether the author may have the bot ping a role for them, moderators can pick any role
+async fn can_ping(ctx: Context<'_>, role: &serenity::Role) -> bool {
+    if is_moderator(ctx).await {
+        return true;
+    }
+    let mention_everyone = match ctx.author_member().await {
+        Some(member) => member
+            .permissions
+            .or_else(|| member.permissions(ctx.serenity_context()).ok())
+            .map_or(false, |p| p.mention_everyone()),
+        None => false,
+    };
+    //@everyone shares the guild's ID and always needs Mention Everyone
+    let everyone = role.id.0 == role.guild_id.0;
+    mention_everyone || (role.mentionable && !everyone)
+}
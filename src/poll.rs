@@ -0,0 +1,1221 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use poise::serenity_prelude::{
+    ButtonStyle, ChannelId, Color, CreateActionRow, CreateEmbed, Http, Timestamp,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::tr;
+use crate::milestone::Milestones;
+use crate::tally::{self, YesNo};
+use crate::Error;
+use crate::{prefs, receipt};
+
+///Yes, no and results buttons in the order and colours of the poll's layout, labelled in its locale
+fn poll_buttons(locale: Option<&str>, layout: &ButtonLayout) -> CreateActionRow {
+    let mut row = CreateActionRow::default();
+
+    for button in &layout.order {
+        let (custom_id, label, color) = match button {
+            PollButton::Yes => ("poll_yes", "Yes!", layout.yes),
+            PollButton::No => ("poll_no", "No!", layout.no),
+            PollButton::Results if !layout.show_results => continue,
+            PollButton::Results => (
+                "poll_view",
+                "View Results",
+                layout.results.unwrap_or(ButtonColor::Blurple),
+            ),
+        };
+        row.create_button(|b| {
+            b.custom_id(custom_id)
+                .label(tr(locale, label))
+                .style(color.style())
+        });
+    }
+
+    row
+}
+
+fn petition_buttons(locale: Option<&str>) -> CreateActionRow {
+    let mut row = CreateActionRow::default();
+
+    row.create_button(|b| {
+        b.custom_id("poll_sign")
+            .label(tr(locale, "Sign"))
+            .style(ButtonStyle::Success)
+    });
+
+    row
+}
+
+///Most options a poll can have, Discord allows 5 rows of 5 buttons and
+///the last rows are kept for the results and preview buttons
+pub const MAX_OPTIONS: usize = 15;
+
+///The options of an RSVP, in the order their buttons are coloured
+pub const RSVP_OPTIONS: [&str; 3] = ["Going", "Maybe", "Can't"];
+
+///A button per option, 5 to a row, followed by the results button.
+///Only the built in RSVP options are translated, other labels are the creator's own words
+fn option_buttons(
+    options: &[PollOption],
+    rsvp: bool,
+    locale: Option<&str>,
+    layout: &ButtonLayout,
+) -> Vec<CreateActionRow> {
+    let mut rows: Vec<CreateActionRow> = options
+        .chunks(5)
+        .enumerate()
+        .map(|(row_index, chunk)| {
+            let mut row = CreateActionRow::default();
+            for (i, option) in chunk.iter().enumerate() {
+                let style = match (rsvp, i) {
+                    (true, 0) => ButtonStyle::Success,
+                    (true, 2) => ButtonStyle::Danger,
+                    _ => ButtonStyle::Primary,
+                };
+                let label = match rsvp {
+                    true => tr(locale, &option.label),
+                    false => &option.label,
+                };
+                row.create_button(|b| {
+                    b.custom_id(format!("poll_option_{}", row_index * 5 + i))
+                        .label(label)
+                        .style(style)
+                });
+            }
+            row
+        })
+        .collect();
+
+    rows.extend(view_button(locale, layout));
+    rows
+}
+
+///A menu to pick up to `max_choices` options, followed by the results button
+fn option_menu(
+    options: &[PollOption],
+    max_choices: u32,
+    locale: Option<&str>,
+    layout: &ButtonLayout,
+) -> Vec<CreateActionRow> {
+    let mut menu = CreateActionRow::default();
+    menu.create_select_menu(|m| {
+        //Picking nothing takes back every pick on polls that allow changes
+        m.custom_id("poll_pick")
+            .placeholder(format!("{} {max_choices}", tr(locale, "Pick up to")))
+            .min_values(0)
+            .max_values(u64::from(max_choices).min(options.len() as u64))
+            .options(|o| {
+                for (i, option) in options.iter().enumerate() {
+                    o.create_option(|o| o.label(&option.label).value(i));
+                }
+                o
+            })
+    });
+    let mut rows = vec![menu];
+    rows.extend(view_button(locale, layout));
+    rows
+}
+
+///The results button on its own row, unless the creator hid it
+fn view_button(locale: Option<&str>, layout: &ButtonLayout) -> Option<CreateActionRow> {
+    if !layout.show_results {
+        return None;
+    }
+    let color = layout.results.unwrap_or(ButtonColor::Grey);
+    let mut row = CreateActionRow::default();
+    row.create_button(|b| {
+        b.custom_id("poll_view")
+            .label(tr(locale, "View Results"))
+            .style(color.style())
+    });
+    Some(row)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Poll {
+    pub title: String,
+    pub description: String,
+    pub reason_to_vote_yes: String,
+    pub reason_to_vote_no: String,
+    pub yes_votes: Vec<PollVote>,
+    pub no_votes: Vec<PollVote>,
+    //Polls created before these fields existed have no creator or guild recorded
+    #[serde(default)]
+    pub creator_id: u64,
+    #[serde(default)]
+    pub guild_id: Option<u64>,
+    #[serde(default)]
+    pub channel_id: Option<u64>,
+    #[serde(default)]
+    pub closed: bool,
+    #[serde(default)]
+    pub event: Option<PollEvent>,
+    #[serde(default)]
+    pub allow_changes: bool,
+    //Roles granted to voters of each choice, u64 = RoleId
+    #[serde(default)]
+    pub yes_role: Option<u64>,
+    #[serde(default)]
+    pub no_role: Option<u64>,
+    //Copies of the poll posted in other channels, sharing this poll's votes
+    #[serde(default)]
+    pub copies: Vec<PollMessage>,
+    //The original message was deleted while copies were left, the poll carries on in those
+    #[serde(default)]
+    pub original_deleted: bool,
+    //Federated polls have copies in other guilds and track which guild each vote came from
+    #[serde(default)]
+    pub federated: bool,
+    //u64 = UserId, u64 = GuildId
+    #[serde(default)]
+    pub voter_guilds: HashMap<u64, u64>,
+    #[serde(default)]
+    pub kind: PollKind,
+    //Unix timestamp the scheduler closes the poll at
+    #[serde(default)]
+    pub deadline: Option<i64>,
+    #[serde(default)]
+    pub tie_break: TieBreak,
+    #[serde(default)]
+    pub tie_resolution: Option<TieResolution>,
+    //Hash of the secret seed a random tie-break uses, shown while the poll is open so the seed
+    //revealed when it closes can be checked against it
+    #[serde(default)]
+    pub tie_commitment: Option<String>,
+    //The seed itself is only kept here until the poll is posted, then stored apart from it
+    #[serde(skip)]
+    pub tie_seed: Option<u64>,
+    //Locked polls stay open but don't accept votes
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub edits: Vec<PollEdit>,
+    #[serde(default)]
+    pub visibility: Visibility,
+    //u64 = RoleId, when not empty only these roles and the creator can view results before the poll closes
+    #[serde(default)]
+    pub results_roles: Vec<u64>,
+    //Also counts ✅ and ❌ reactions as votes, for members on clients that mishandle buttons
+    #[serde(default)]
+    pub reactions: bool,
+    //Anonymous polls keep a hash of each voter's receipt code and choice, published when they close
+    #[serde(default)]
+    pub receipt_salt: String,
+    //u64 = UserId
+    #[serde(default)]
+    pub receipts: HashMap<u64, String>,
+    //Attaches every vote as CSV to the summary DMed to the creator when the poll closes
+    #[serde(default)]
+    pub summary_csv: bool,
+    #[serde(default)]
+    pub milestones: Milestones,
+    //Hours between bumps of the poll in its channel
+    #[serde(default)]
+    pub remind_hours: Option<u32>,
+    //Minutes before the deadline to warn that the poll is closing
+    #[serde(default)]
+    pub warn_minutes: Option<u32>,
+    //Also DMs the warning to members who haven't voted
+    #[serde(default)]
+    pub warn_dm: bool,
+    #[serde(default)]
+    pub warned: bool,
+    //Pinned message the bot keeps showing the tally in, so the poll's own message stays clean
+    #[serde(default)]
+    pub results_message: Option<PollMessage>,
+    //u64 = UserId, unix timestamp of each voter's latest vote, for showing how votes came in
+    #[serde(default)]
+    pub voted_at: HashMap<u64, i64>,
+    //Unix timestamp each option of a scheduling poll starts at, in the same order as the options
+    #[serde(default)]
+    pub slots: Vec<i64>,
+    #[serde(default)]
+    pub slot_minutes: u32,
+    //Discord locale the buttons are labelled in, like de or pt-BR, unset is English
+    #[serde(default)]
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub layout: ButtonLayout,
+    //Members of the guild when the poll was created, for {eligible_count} in the description
+    #[serde(default)]
+    pub eligible_count: Option<u64>,
+    #[serde(default)]
+    pub context: Option<PollContext>,
+    //u64 = ChannelId, a discussion thread per option in the same order as the options
+    #[serde(default)]
+    pub option_threads: Vec<u64>,
+    //Asks voters why in a modal after they vote, answering is optional
+    #[serde(default)]
+    pub ask_reasons: bool,
+    //u64 = UserId, why each voter voted the way they did, if they said
+    #[serde(default)]
+    pub vote_reasons: HashMap<u64, String>,
+    //Posted as one of Discord's own polls, whose votes are copied in instead of cast on buttons
+    #[serde(default)]
+    pub native: bool,
+    //Adopted from a message voted on with reactions, one option per emoji
+    #[serde(default)]
+    pub reaction_vote: bool,
+    //Tracked from a message someone else posted, which the bot can't end or edit
+    #[serde(default)]
+    pub adopted: bool,
+}
+
+//Fewest reasons a choice of an anonymous poll needs before they are shown
+pub const ANONYMOUS_REASON_MINIMUM: usize = 3;
+
+///Who can see which members voted for what
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum Visibility {
+    #[default]
+    #[name = "Only vote counts"]
+    Counts,
+    #[name = "Everyone sees who voted"]
+    Public,
+    #[name = "Nobody sees who voted, not even the creator"]
+    Anonymous,
+}
+
+///A message a poll is about, quoted in its embed with a link back to it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PollContext {
+    pub link: String,
+    //u64 = UserId
+    pub author_id: u64,
+    //The start of the message as it was when the poll was created, already formatted as a quote
+    pub quote: String,
+}
+
+///A button of a yes/no poll
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PollButton {
+    Yes,
+    No,
+    Results,
+}
+
+impl PollButton {
+    ///Parses an order like "no, yes, results", which has to name every button once
+    pub fn parse_order(text: &str) -> Result<Vec<PollButton>, String> {
+        let order = text
+            .split(',')
+            .map(|b| match b.trim().to_lowercase().as_str() {
+                "yes" => Ok(PollButton::Yes),
+                "no" => Ok(PollButton::No),
+                "results" => Ok(PollButton::Results),
+                other => Err(format!(
+                    "\"{other}\" isn't a button, use yes, no and results"
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let every_button = [PollButton::Yes, PollButton::No, PollButton::Results]
+            .iter()
+            .all(|b| order.iter().filter(|o| *o == b).count() == 1);
+        match every_button && order.len() == 3 {
+            true => Ok(order),
+            false => Err("The order has to name yes, no and results once each".to_string()),
+        }
+    }
+}
+
+///Colour of a button, named the way Discord's own docs do
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum ButtonColor {
+    Blurple,
+    Grey,
+    Green,
+    Red,
+}
+
+impl ButtonColor {
+    fn style(self) -> ButtonStyle {
+        match self {
+            ButtonColor::Blurple => ButtonStyle::Primary,
+            ButtonColor::Grey => ButtonStyle::Secondary,
+            ButtonColor::Green => ButtonStyle::Success,
+            ButtonColor::Red => ButtonStyle::Danger,
+        }
+    }
+}
+
+///How a poll's buttons are arranged, changed with /poll buttons
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ButtonLayout {
+    //Only yes/no polls have their buttons reordered
+    pub order: Vec<PollButton>,
+    pub yes: ButtonColor,
+    pub no: ButtonColor,
+    //Unset is blurple next to yes and no, grey under options
+    pub results: Option<ButtonColor>,
+    //Hidden results can still be seen with /results
+    pub show_results: bool,
+}
+
+impl Default for ButtonLayout {
+    fn default() -> Self {
+        Self {
+            order: vec![PollButton::Yes, PollButton::No, PollButton::Results],
+            yes: ButtonColor::Green,
+            no: ButtonColor::Red,
+            results: None,
+            show_results: true,
+        }
+    }
+}
+
+///A change made with /poll edit, keeping what the poll said before it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PollEdit {
+    //Unix timestamp
+    pub at: i64,
+    //u64 = UserId
+    pub editor_id: u64,
+    pub old_title: String,
+    pub old_description: String,
+    pub old_deadline: Option<i64>,
+}
+
+///What the poll is being used for, each kind reuses the same yes/no voting
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub enum PollKind {
+    #[default]
+    Standard,
+    Suggestion {
+        status: SuggestionStatus,
+    },
+    //Signatures are stored as yes votes
+    Petition {
+        threshold: u32,
+        //u64 = RoleId, pinged once the threshold is reached
+        notify_role: Option<u64>,
+        actionable: bool,
+    },
+    //Named options instead of yes and no, members pick one of them
+    Options {
+        options: Vec<PollOption>,
+        //More than 1 shows a menu to pick up to this many options instead of buttons
+        #[serde(default)]
+        max_choices: u32,
+        //RSVPs are Going, Maybe and Can't, with the names of who picked each shown in the embed
+        #[serde(default)]
+        rsvp: bool,
+    },
+}
+
+///An option of a poll with named options, with an optional number of slots like a signup sheet
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PollOption {
+    pub label: String,
+    pub capacity: Option<u32>,
+    pub votes: Vec<PollVote>,
+    //Why to pick the option, shown above its count
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl PollOption {
+    pub fn is_full(&self) -> bool {
+        self.capacity
+            .map_or(false, |capacity| self.votes.len() >= capacity as usize)
+    }
+
+    ///Mentions of everyone who picked the option, cut short to fit in an embed field
+    pub fn names_text(&self) -> String {
+        let (hidden, shown): (Vec<&PollVote>, Vec<&PollVote>) =
+            self.votes.iter().partition(|v| prefs::is_hidden(v.0));
+        let mut text = String::new();
+        for (i, vote) in shown.iter().enumerate() {
+            let mention = format!("<@{}>\n", vote.0);
+            //Leaves room for the count line above and the notes below
+            if text.len() + mention.len() > 900 {
+                text.push_str(&format!("and {} more\n", shown.len() - i));
+                break;
+            }
+            text.push_str(&mention);
+        }
+        if let Some(note) = prefs::hidden_note(hidden.len()) {
+            text.push_str(&note);
+        }
+        text
+    }
+
+    ///Vote count, out of the capacity if there is one
+    pub fn count_text(&self) -> String {
+        match self.capacity {
+            Some(capacity) if self.is_full() => format!("{}/{capacity} FULL", self.votes.len()),
+            Some(capacity) => format!("{}/{capacity}", self.votes.len()),
+            None => self.votes.len().to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum SuggestionStatus {
+    Pending,
+    Approved,
+    Denied,
+    Implemented,
+}
+
+impl SuggestionStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SuggestionStatus::Pending => "Pending",
+            SuggestionStatus::Approved => "Approved",
+            SuggestionStatus::Denied => "Denied",
+            SuggestionStatus::Implemented => "Implemented",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, poise::ChoiceParameter)]
+pub enum Choice {
+    Yes,
+    No,
+}
+
+impl Choice {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Choice::Yes => "Yes",
+            Choice::No => "No",
+        }
+    }
+}
+
+///How a poll that closes with equal yes and no votes is settled
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum TieBreak {
+    #[default]
+    #[name = "Declare a tie"]
+    Declare,
+    #[name = "Random pick"]
+    Random,
+    #[name = "Creator decides"]
+    Creator,
+    #[name = "24 hour runoff poll"]
+    Runoff,
+}
+
+///How a tie was settled when the poll closed
+#[derive(Serialize, Deserialize, Clone)]
+pub enum TieResolution {
+    //The seed is published so anyone can recompute the pick with `tie_break_pick` and check
+    //it against the hash shown before the poll closed
+    Random { seed: u64, winner: Choice },
+    Creator { winner: Option<Choice> },
+    Runoff { message_id: u64 },
+}
+
+///Link that jumps to a message, direct messages use @me in place of a guild
+pub fn message_link(guild_id: Option<u64>, channel_id: u64, message_id: u64) -> String {
+    let guild = guild_id.map_or_else(|| "@me".to_string(), |g| g.to_string());
+    format!("https://discord.com/channels/{guild}/{channel_id}/{message_id}")
+}
+
+///Picks a tie winner from a seed using SplitMix64, simple enough to recompute by hand
+pub fn tie_break_pick(seed: u64) -> Choice {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+
+    if z % 2 == 0 {
+        Choice::Yes
+    } else {
+        Choice::No
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+//u64 = UserId
+pub struct PollVote(pub u64);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PollMessage {
+    pub channel_id: u64,
+    pub message_id: u64,
+}
+
+///Scheduled event to create in the guild once the poll passes
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PollEvent {
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub location: String,
+}
+
+impl Poll {
+    ///A fresh open poll with no votes and every optional feature turned off
+    pub fn new(
+        title: String,
+        description: String,
+        reason_to_vote_yes: String,
+        reason_to_vote_no: String,
+        creator_id: u64,
+        guild_id: Option<u64>,
+        channel_id: u64,
+    ) -> Self {
+        Self {
+            title,
+            description,
+            reason_to_vote_yes,
+            reason_to_vote_no,
+            yes_votes: Vec::new(),
+            no_votes: Vec::new(),
+            creator_id,
+            guild_id,
+            channel_id: Some(channel_id),
+            closed: false,
+            event: None,
+            allow_changes: false,
+            yes_role: None,
+            no_role: None,
+            copies: Vec::new(),
+            original_deleted: false,
+            federated: false,
+            voter_guilds: HashMap::new(),
+            kind: PollKind::Standard,
+            deadline: None,
+            tie_break: TieBreak::Declare,
+            tie_resolution: None,
+            tie_commitment: None,
+            tie_seed: None,
+            locked: false,
+            edits: Vec::new(),
+            visibility: Visibility::Counts,
+            results_roles: Vec::new(),
+            reactions: false,
+            receipt_salt: String::new(),
+            receipts: HashMap::new(),
+            summary_csv: false,
+            milestones: Milestones::default(),
+            remind_hours: None,
+            warn_minutes: None,
+            warn_dm: false,
+            warned: false,
+            results_message: None,
+            voted_at: HashMap::new(),
+            slots: Vec::new(),
+            slot_minutes: 0,
+            locale: None,
+            layout: ButtonLayout::default(),
+            eligible_count: None,
+            context: None,
+            option_threads: Vec::new(),
+            ask_reasons: false,
+            vote_reasons: HashMap::new(),
+            native: false,
+            reaction_vote: false,
+            adopted: false,
+        }
+    }
+
+    ///Link that jumps to the poll's message, if its channel was recorded
+    pub fn link(&self, message_id: u64) -> Option<String> {
+        self.messages(message_id)
+            .first()
+            .map(|m| message_link(self.guild_id, m.channel_id, m.message_id))
+    }
+
+    ///Whether a member with these roles may view the results yet
+    pub fn can_view_results(&self, user_id: u64, roles: &[u64]) -> bool {
+        self.closed
+            || self.results_roles.is_empty()
+            || user_id == self.creator_id
+            || roles.iter().any(|r| self.results_roles.contains(r))
+    }
+
+    ///Whether a user may list who voted for what, creators can unless the poll is anonymous
+    pub fn can_see_voters(&self, user_id: u64) -> bool {
+        match self.visibility {
+            Visibility::Public => true,
+            Visibility::Counts => user_id == self.creator_id,
+            Visibility::Anonymous => false,
+        }
+    }
+
+    ///Whether members vote outside the bot's buttons, so the votes have to be copied in
+    pub fn synced(&self) -> bool {
+        self.native || self.reaction_vote
+    }
+
+    ///The named options of the poll, empty unless it is an options poll
+    pub fn options(&self) -> &[PollOption] {
+        match &self.kind {
+            PollKind::Options { options, .. } => options,
+            _ => &[],
+        }
+    }
+
+    ///The index of the option a user picked, if any
+    pub fn option_of(&self, user_id: u64) -> Option<usize> {
+        self.options()
+            .iter()
+            .position(|o| o.votes.iter().any(|v| v.0 == user_id))
+    }
+
+    ///The indexes of every option a user picked
+    pub fn options_of(&self, user_id: u64) -> Vec<usize> {
+        self.options()
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.votes.iter().any(|v| v.0 == user_id))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    ///The labels of some options, comma separated
+    pub fn labels_of(&self, indexes: &[usize]) -> String {
+        indexes
+            .iter()
+            .map(|i| self.options()[*i].label.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn add_option_vote(&mut self, user_id: u64, index: usize) {
+        if let PollKind::Options { options, .. } = &mut self.kind {
+            options[index].votes.push(PollVote(user_id));
+            self.voted_at
+                .insert(user_id, Timestamp::now().unix_timestamp());
+        }
+    }
+
+    ///How many members voted, counting members who picked several options once
+    pub fn voter_count(&self) -> usize {
+        let voters: HashSet<u64> = self
+            .vote_groups()
+            .iter()
+            .flat_map(|(_, votes)| votes.iter().map(|v| v.0))
+            .collect();
+        voters.len()
+    }
+
+    pub fn has_voted(&self, user_id: u64) -> bool {
+        self.vote_of(user_id).is_some() || self.option_of(user_id).is_some()
+    }
+
+    ///Each option's name with its voters, yes and no for yes/no polls
+    pub fn vote_groups(&self) -> Vec<(&str, &[PollVote])> {
+        match &self.kind {
+            PollKind::Options { options, .. } => options
+                .iter()
+                .map(|o| (o.label.as_str(), o.votes.as_slice()))
+                .collect(),
+            _ => vec![
+                ("Yes", self.yes_votes.as_slice()),
+                ("No", self.no_votes.as_slice()),
+            ],
+        }
+    }
+
+    ///The reasons given for each choice without who gave them. Anonymous polls list them in
+    ///alphabetical order instead of the order votes came in, and leave out choices with too few
+    ///reasons to hide whose they are
+    pub fn reason_digest(&self) -> Vec<(&str, Vec<&str>)> {
+        let anonymous = self.visibility == Visibility::Anonymous;
+        self.vote_groups()
+            .into_iter()
+            .filter_map(|(name, votes)| {
+                let mut reasons: Vec<&str> = votes
+                    .iter()
+                    .filter_map(|v| self.vote_reasons.get(&v.0))
+                    .map(String::as_str)
+                    .collect();
+                if anonymous {
+                    reasons.sort_unstable();
+                }
+                match reasons.len() {
+                    0 => None,
+                    count if anonymous && count < ANONYMOUS_REASON_MINIMUM => None,
+                    _ => Some((name, reasons)),
+                }
+            })
+            .collect()
+    }
+
+    ///A few of the reasons given for each choice, for the results and the creator's summary
+    pub fn reasons_summary(&self) -> Option<String> {
+        //Keeps the summary within an embed field
+        const SHOWN: usize = 3;
+        const REASON_LENGTH: usize = 80;
+        const FIELD_LENGTH: usize = 1024;
+        let mut lines = Vec::new();
+        for (name, reasons) in self.reason_digest() {
+            lines.push(match reasons.len() {
+                1 => format!("**{name}** (1 reason)"),
+                count => format!("**{name}** ({count} reasons)"),
+            });
+            for reason in reasons.iter().take(SHOWN) {
+                let mut short: String = reason.chars().take(REASON_LENGTH).collect();
+                if short.len() < reason.len() {
+                    short.push('…');
+                }
+                lines.push(format!("> {}", short.replace('\n', " ")));
+            }
+        }
+        if lines.is_empty() {
+            return None;
+        }
+        //Polls with many options could still overflow the field
+        let text = lines.join("\n");
+        match text.chars().count() > FIELD_LENGTH {
+            true => Some(text.chars().take(FIELD_LENGTH - 1).chain(['…']).collect()),
+            false => Some(text),
+        }
+    }
+
+    ///The vote counts on one line, e.g. "Yes: 3 No: 1"
+    pub fn tally_text(&self) -> String {
+        match &self.kind {
+            PollKind::Options { options, .. } => options
+                .iter()
+                .map(|o| format!("{}: {}", o.label, o.count_text()))
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => format!("Yes: {} No: {}", self.yes_votes.len(), self.no_votes.len()),
+        }
+    }
+
+    ///The tally with one line per choice and its share spelled out, for screen readers
+    pub fn plain_tally_text(&self) -> String {
+        let groups = self.vote_groups();
+        let total: usize = groups.iter().map(|(_, votes)| votes.len()).sum();
+        let votes = |count: usize| match count {
+            1 => "1 vote".to_string(),
+            count => format!("{count} votes"),
+        };
+
+        let mut lines: Vec<String> = groups
+            .iter()
+            .map(|(name, voters)| {
+                let percent = tally::percent(voters.len(), total);
+                format!("{name}: {}, {percent} percent", votes(voters.len()))
+            })
+            .collect();
+        lines.push(format!("Total: {}", votes(total)));
+        lines.push(match self.closed {
+            true => format!("Status: closed, {}", self.outcome()),
+            false => "Status: open".to_string(),
+        });
+        lines.join("\n")
+    }
+
+    ///The choice a user voted for, if any
+    pub fn vote_of(&self, user_id: u64) -> Option<Choice> {
+        if self.yes_votes.iter().any(|v| v.0 == user_id) {
+            Some(Choice::Yes)
+        } else if self.no_votes.iter().any(|v| v.0 == user_id) {
+            Some(Choice::No)
+        } else {
+            None
+        }
+    }
+
+    pub fn add_vote(&mut self, user_id: u64, choice: Choice) {
+        match choice {
+            Choice::Yes => self.yes_votes.push(PollVote(user_id)),
+            Choice::No => self.no_votes.push(PollVote(user_id)),
+        }
+        self.voted_at
+            .insert(user_id, Timestamp::now().unix_timestamp());
+    }
+
+    pub fn remove_vote(&mut self, user_id: u64) {
+        self.voter_guilds.remove(&user_id);
+        self.receipts.remove(&user_id);
+        self.voted_at.remove(&user_id);
+        self.vote_reasons.remove(&user_id);
+        self.yes_votes.retain(|v| v.0 != user_id);
+        self.no_votes.retain(|v| v.0 != user_id);
+        if let PollKind::Options { options, .. } = &mut self.kind {
+            for option in options {
+                option.votes.retain(|v| v.0 != user_id);
+            }
+        }
+    }
+
+    ///Gives a voter on an anonymous poll a receipt code for their choice, replacing any earlier one
+    pub fn issue_receipt(&mut self, user_id: u64, choice: &str) -> Option<String> {
+        if self.visibility != Visibility::Anonymous {
+            return None;
+        }
+        if self.receipt_salt.is_empty() {
+            self.receipt_salt = receipt::random_code();
+        }
+        let code = receipt::random_code();
+        self.receipts.insert(
+            user_id,
+            receipt::commitment(&self.receipt_salt, &code, choice),
+        );
+        Some(code)
+    }
+
+    ///Yes and no counts per guild, votes without a recorded guild count towards the poll's own guild
+    pub fn guild_breakdown(&self) -> BTreeMap<u64, (usize, usize)> {
+        let mut breakdown = BTreeMap::new();
+        let home = self.guild_id.unwrap_or_default();
+
+        for (votes, is_yes) in [(&self.yes_votes, true), (&self.no_votes, false)] {
+            for vote in votes {
+                let guild = self.voter_guilds.get(&vote.0).copied().unwrap_or(home);
+                let (yes, no) = breakdown.entry(guild).or_insert((0, 0));
+                if is_yes {
+                    *yes += 1;
+                } else {
+                    *no += 1;
+                }
+            }
+        }
+        breakdown
+    }
+
+    ///The role granted for voting a choice
+    pub fn role_for(&self, choice: Choice) -> Option<u64> {
+        match choice {
+            Choice::Yes => self.yes_role,
+            Choice::No => self.no_role,
+        }
+    }
+
+    ///Whether the poll has equal yes and no votes, petitions and options polls can't tie
+    pub fn tied(&self) -> bool {
+        matches!(self.kind, PollKind::Standard | PollKind::Suggestion { .. })
+            && tally::yes_no(self.yes_votes.len(), self.no_votes.len()) == YesNo::Tied
+    }
+
+    ///The winning choice, taking any tie-break into account
+    pub fn winner(&self) -> Option<Choice> {
+        if !self.tied() {
+            return match tally::yes_no(self.yes_votes.len(), self.no_votes.len()) {
+                YesNo::Passed => Some(Choice::Yes),
+                _ => Some(Choice::No),
+            };
+        }
+        match self.tie_resolution {
+            Some(TieResolution::Random { winner, .. }) => Some(winner),
+            Some(TieResolution::Creator { winner }) => winner,
+            _ => None,
+        }
+    }
+
+    ///Whether more people voted yes than no, or yes won the tie-break
+    pub fn passed(&self) -> bool {
+        match self.kind {
+            PollKind::Petition { threshold, .. } => self.yes_votes.len() >= threshold as usize,
+            //Options polls have no yes to pass with
+            PollKind::Options { .. } => false,
+            _ => self.winner() == Some(Choice::Yes),
+        }
+    }
+
+    ///The result of a closed poll in words
+    pub fn outcome(&self) -> String {
+        if let PollKind::Options { options, .. } = &self.kind {
+            let counts: Vec<usize> = options.iter().map(|o| o.votes.len()).collect();
+            let leaders: Vec<&str> = tally::leaders(&counts)
+                .into_iter()
+                .map(|i| options[i].label.as_str())
+                .collect();
+            return match leaders.as_slice() {
+                [] => "Nobody voted".to_string(),
+                [leader] => format!("{leader} won"),
+                leaders => format!("Tied between {}", leaders.join(", ")),
+            };
+        }
+        if !self.tied() {
+            return if self.passed() { "Passed" } else { "Rejected" }.to_string();
+        }
+        match self.tie_resolution {
+            Some(TieResolution::Random { seed, winner }) => {
+                format!("Tied, {} won a random pick (seed {seed})", winner.as_str())
+            }
+            Some(TieResolution::Creator {
+                winner: Some(winner),
+            }) => format!("Tied, the creator picked {}", winner.as_str()),
+            Some(TieResolution::Creator { winner: None }) => {
+                "Tied, waiting for the creator to decide".to_string()
+            }
+            Some(TieResolution::Runoff { .. }) => "Tied, went to a runoff poll".to_string(),
+            None => "Tied".to_string(),
+        }
+    }
+
+    ///Whether the embed shows counts that need re-rendering after every vote
+    pub fn live_tally(&self) -> bool {
+        !self.copies.is_empty()
+            || self.results_message.is_some()
+            || matches!(
+                self.kind,
+                PollKind::Petition { .. } | PollKind::Options { .. }
+            )
+    }
+
+    ///The rows of buttons shown under the poll
+    pub fn buttons(&self) -> Vec<CreateActionRow> {
+        let locale = self.locale.as_deref();
+        match &self.kind {
+            PollKind::Petition { .. } => vec![petition_buttons(locale)],
+            PollKind::Options {
+                options,
+                max_choices,
+                ..
+            } if *max_choices > 1 => option_menu(options, *max_choices, locale, &self.layout),
+            PollKind::Options { options, rsvp, .. } => {
+                option_buttons(options, *rsvp, locale, &self.layout)
+            }
+            _ => vec![poll_buttons(locale, &self.layout)],
+        }
+    }
+
+    ///Fills in the poll embed, including the final result once the poll is closed
+    pub fn embed<'a>(&self, e: &'a mut CreateEmbed) -> &'a mut CreateEmbed {
+        e.title(&self.title)
+            .description(self.rendered_description());
+
+        if let Some(context) = &self.context {
+            e.field(
+                "Context",
+                format!(
+                    "{}\n<@{}> [Jump to the message]({})",
+                    context.quote, context.author_id, context.link
+                ),
+                false,
+            );
+        }
+
+        //Lets voters know the question may have changed since they voted
+        if let Some(edit) = self.edits.last() {
+            e.footer(|f| f.text("Edited"));
+            if let Ok(at) = Timestamp::from_unix_timestamp(edit.at) {
+                e.timestamp(at);
+            }
+        }
+
+        match self.kind {
+            PollKind::Petition {
+                threshold,
+                actionable,
+                ..
+            } => {
+                e.field(
+                    "Signatures",
+                    format!("{}/{threshold}", self.yes_votes.len()),
+                    true,
+                );
+                if actionable {
+                    e.field("Status", "Threshold reached, ready for action", true);
+                }
+            }
+            PollKind::Suggestion { status } => {
+                self.reason_fields(e);
+                e.field("Status", status.as_str(), false);
+            }
+            PollKind::Standard => {
+                self.reason_fields(e);
+            }
+            PollKind::Options {
+                ref options, rsvp, ..
+            } => {
+                for (i, option) in options.iter().enumerate() {
+                    let mut value = match rsvp {
+                        true => format!("{}\n{}", option.count_text(), option.names_text()),
+                        false => option.count_text(),
+                    };
+                    if let Some(reason) = &option.reason {
+                        value = format!("{reason}\n{value}");
+                    }
+                    if let Some(thread) = self.option_threads.get(i) {
+                        value.push_str(&format!("\nDiscuss in <#{thread}>"));
+                    }
+                    e.field(&option.label, value, true);
+                }
+            }
+        }
+
+        match self.visibility {
+            Visibility::Public => {
+                e.field("Public votes", "Everyone can see who voted for what", false);
+            }
+            Visibility::Anonymous => {
+                e.field(
+                    "Anonymous votes",
+                    "Nobody can see who voted for what",
+                    false,
+                );
+            }
+            Visibility::Counts => {}
+        }
+
+        if !self.closed {
+            if self.locked {
+                e.field("Locked", "Voting is paused by a moderator", false);
+            }
+            if self.reactions {
+                e.field(
+                    "Voting",
+                    "React with ✅ or ❌ if the buttons don't work for you",
+                    false,
+                );
+            }
+            if let Some(deadline) = self.deadline {
+                e.field("Closes", format!("<t:{deadline}:R>"), false);
+            }
+            if let Some(commitment) = &self.tie_commitment {
+                e.field(
+                    "Tie-break",
+                    format!(
+                        "A tie is settled by a random pick, the hash of its seed is `{commitment}`"
+                    ),
+                    false,
+                );
+            }
+            //Crossposted copies show the merged tally so every channel sees the same counts,
+            //unless the results are restricted to some roles
+            if !self.copies.is_empty() && self.results_roles.is_empty() {
+                e.field("Votes", self.tally_text(), false);
+            }
+            return e.color(Color::from_rgb(0, 255, 0));
+        }
+
+        e.color(Color::from_rgb(255, 0, 0)).field(
+            "Result",
+            format!("{} ({})", self.outcome(), self.tally_text()),
+            false,
+        )
+    }
+
+    ///The tally with a bar per choice, for the live results message
+    pub fn results_embed<'a>(&self, e: &'a mut CreateEmbed) -> &'a mut CreateEmbed {
+        const BAR_WIDTH: usize = 20;
+        let groups = self.vote_groups();
+        let total: usize = groups.iter().map(|(_, votes)| votes.len()).sum();
+
+        let lines: Vec<String> = groups
+            .iter()
+            .map(|(name, votes)| {
+                let filled = tally::filled(votes.len(), total, BAR_WIDTH);
+                let percent = tally::percent(votes.len(), total);
+                format!(
+                    "**{name}**\n`{}{}` {} ({percent}%)",
+                    "█".repeat(filled),
+                    "░".repeat(BAR_WIDTH - filled),
+                    votes.len()
+                )
+            })
+            .collect();
+
+        e.title(format!("Live results: {}", self.title))
+            .description(lines.join("\n"))
+            .footer(|f| f.text(format!("{} voters, last updated", self.voter_count())))
+            .timestamp(Timestamp::now());
+        if self.closed {
+            e.field("Final result", self.outcome(), false);
+        }
+        if let Some(reasons) = self.reasons_summary() {
+            e.field("Why people voted", reasons, false);
+        }
+        e
+    }
+
+    ///Every message showing this poll, the original first unless it was deleted
+    pub fn messages(&self, message_id: u64) -> Vec<PollMessage> {
+        let mut messages: Vec<PollMessage> = self
+            .channel_id
+            .filter(|_| !self.original_deleted)
+            .map(|channel_id| PollMessage {
+                channel_id,
+                message_id,
+            })
+            .into_iter()
+            .collect();
+        messages.extend(self.copies.iter().cloned());
+        messages
+    }
+
+    ///Re-renders the poll on every message showing it, removing the buttons once closed
+    pub async fn update_messages(&self, http: &Http, message_id: u64) -> Result<(), Error> {
+        //Discord shows the tally of its own polls and of reactions, and adopted messages
+        //aren't the bot's to edit
+        if self.synced() {
+            return Ok(());
+        }
+        for message in self.messages(message_id) {
+            ChannelId(message.channel_id)
+                .edit_message(http, message.message_id, |m| {
+                    m.embed(|e| self.embed(e));
+                    //Reopened polls get their buttons back
+                    if self.closed {
+                        m.components(|c| c);
+                    } else {
+                        m.components(|c| c.set_action_rows(self.buttons()));
+                    }
+                    m
+                })
+                .await?;
+        }
+
+        //A missing results message shouldn't stop the poll itself from updating
+        if let Some(results) = &self.results_message {
+            let edited = ChannelId(results.channel_id)
+                .edit_message(http, results.message_id, |m| {
+                    m.embed(|e| self.results_embed(e))
+                })
+                .await;
+            if let Err(e) = edited {
+                tracing::warn!("Failed to update the results message of poll {message_id}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    ///The description with its {deadline}, {quorum}, {creator} and {eligible_count} filled in.
+    ///Rendered whenever the embed is, so edits to the poll show up in it
+    pub fn rendered_description(&self) -> String {
+        let deadline = self
+            .deadline
+            .map_or("no deadline".to_string(), |d| format!("<t:{d}:f>"));
+        let quorum = self
+            .milestones
+            .quorum
+            .map_or("no quorum".to_string(), |q| q.to_string());
+        let eligible = self
+            .eligible_count
+            .map_or("everyone".to_string(), |c| c.to_string());
+        self.description
+            .replace("{deadline}", &deadline)
+            .replace("{quorum}", &quorum)
+            .replace("{creator}", &format!("<@{}>", self.creator_id))
+            .replace("{eligible_count}", &eligible)
+    }
+
+    ///The reason to vote a choice with the role voting it grants, if there is either
+    fn reason_with_role(&self, choice: Choice) -> Option<String> {
+        let reason = match choice {
+            Choice::Yes => &self.reason_to_vote_yes,
+            Choice::No => &self.reason_to_vote_no,
+        };
+        let grant = self
+            .role_for(choice)
+            .map(|role| format!("Voting grants <@&{role}>"));
+        match (reason.is_empty(), grant) {
+            (true, grant) => grant,
+            (false, Some(grant)) => Some(format!("{reason}\n{grant}")),
+            (false, None) => Some(reason.clone()),
+        }
+    }
+
+    ///Yes and no fields with their reasons, leaving out choices without one.
+    ///Embed fields can't be empty, and quick polls don't need them
+    fn reason_fields(&self, e: &mut CreateEmbed) {
+        for choice in [Choice::Yes, Choice::No] {
+            if let Some(reason) = self.reason_with_role(choice) {
+                e.field(choice.as_str(), reason, true);
+            }
+        }
+    }
+}
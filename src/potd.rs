@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+
+use poise::serenity_prelude::{self as serenity, ChannelId, Http};
+use serde::{Deserialize, Serialize};
+use shuttle_persist::PersistInstance;
+
+use crate::commands::eph_reply;
+use crate::poll::Poll;
+use crate::{lifecycle, store, validate, Context, Error};
+
+///A guild's poll of the day settings and its queue of upcoming questions
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PollOfTheDay {
+    pub guild_id: u64,
+    pub channel_id: u64,
+    //Hour of the day to post at, in UTC
+    pub hour: u8,
+    pub queue: VecDeque<QueuedQuestion>,
+    //Day since the unix epoch the last poll was posted on
+    pub last_posted: i64,
+    //Message ID of the poll currently running
+    pub current_poll: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QueuedQuestion {
+    pub question: String,
+    //u64 = UserId, becomes the creator of the posted poll
+    pub added_by: u64,
+}
+
+fn key(guild_id: u64) -> String {
+    format!("potd_{guild_id}")
+}
+
+//Parent command, only its subcommands can be invoked
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("setup", "add", "queue", "stop")
+)]
+pub async fn potd(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+//Sets where and when the poll of the day is posted
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn setup(
+    ctx: Context<'_>,
+    #[description = "Channel to post the poll of the day in"] channel: serenity::GuildChannel,
+    #[description = "Hour of the day to post at, in UTC"]
+    #[min = 0]
+    #[max = 23]
+    hour: u8,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+
+    //Reconfiguring keeps the queue and the running poll
+    let potd = match persist.load::<PollOfTheDay>(&key(guild_id)) {
+        Ok(existing) => PollOfTheDay {
+            channel_id: channel.id.0,
+            hour,
+            ..existing
+        },
+        Err(_) => PollOfTheDay {
+            guild_id,
+            channel_id: channel.id.0,
+            hour,
+            queue: VecDeque::new(),
+            last_posted: 0,
+            current_poll: None,
+        },
+    };
+    persist.save(&key(guild_id), potd)?;
+
+    eph_reply(
+        ctx,
+        format!(
+            "The poll of the day will be posted in <#{}> at {hour:02}:00 UTC",
+            channel.id
+        ),
+    )
+    .await
+}
+
+//Queues a yes/no question for an upcoming poll of the day
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Yes/no question to ask"] question: String,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let key = key(ctx.guild_id().unwrap().0);
+
+    let Ok(mut potd) = persist.load::<PollOfTheDay>(&key) else {
+        return eph_reply(ctx, "Set up the poll of the day with /potd setup first").await;
+    };
+    let question = match validate::text("question", &question, validate::TITLE_LIMIT) {
+        Ok(question) => question,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    potd.queue.push_back(QueuedQuestion {
+        question,
+        added_by: ctx.author().id.0,
+    });
+    let position = potd.queue.len();
+    persist.save(&key, potd)?;
+
+    eph_reply(ctx, format!("Queued as question #{position}")).await
+}
+
+//Lists the queued poll of the day questions
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn queue(ctx: Context<'_>) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+
+    let Ok(potd) = persist.load::<PollOfTheDay>(&key(ctx.guild_id().unwrap().0)) else {
+        return eph_reply(ctx, "The poll of the day is not set up").await;
+    };
+    if potd.queue.is_empty() {
+        return eph_reply(ctx, "No questions are queued").await;
+    }
+
+    let list = potd
+        .queue
+        .iter()
+        .enumerate()
+        .map(|(i, q)| format!("{}. {}", i + 1, q.question))
+        .collect::<Vec<_>>()
+        .join("\n");
+    eph_reply(ctx, list).await
+}
+
+//Turns off the poll of the day and discards its queue
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+async fn stop(ctx: Context<'_>) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+
+    if persist.remove(&key(ctx.guild_id().unwrap().0)).is_err() {
+        return eph_reply(ctx, "The poll of the day is not set up").await;
+    }
+    eph_reply(ctx, "The poll of the day has been turned off").await
+}
+
+///Posts the poll of the day in every guild that is due, closing the previous day's poll first
+pub async fn tick(http: &Http, persist: &PersistInstance, now: i64) -> Result<(), Error> {
+    let day = now / 86400;
+    let hour = (now % 86400) / 3600;
+
+    for key in persist.list()?.iter().filter(|k| k.starts_with("potd_")) {
+        let mut potd: PollOfTheDay = persist.load(key)?;
+        if potd.last_posted >= day || hour < i64::from(potd.hour) {
+            continue;
+        }
+
+        if let Err(e) = post_next(http, persist, &mut potd, day).await {
+            tracing::warn!(
+                "Failed to post the poll of the day for {}: {e}",
+                potd.guild_id
+            );
+        }
+        persist.save(key, potd)?;
+    }
+    Ok(())
+}
+
+async fn post_next(
+    http: &Http,
+    persist: &PersistInstance,
+    potd: &mut PollOfTheDay,
+    day: i64,
+) -> Result<(), Error> {
+    let channel = ChannelId(potd.channel_id);
+
+    if let Some(previous) = potd.current_poll.take() {
+        //The previous poll may have been ended by hand or deleted
+        if let Ok((message_id, mut poll)) = store::load_poll(persist, previous) {
+            if !poll.closed {
+                let summary = lifecycle::close_poll(http, persist, message_id, &mut poll).await?;
+                channel
+                    .send_message(http, |m| {
+                        m.content(format!("Yesterday's poll {summary}"))
+                            .allowed_mentions(|a| a.empty_parse())
+                    })
+                    .await?;
+            }
+        }
+    }
+
+    //An empty queue is checked again on the next tick, so questions added later today still post
+    let Some(question) = potd.queue.pop_front() else {
+        return Ok(());
+    };
+    let poll = Poll::new(
+        question.question,
+        "Poll of the day".to_string(),
+        "Vote yes".to_string(),
+        "Vote no".to_string(),
+        question.added_by,
+        Some(potd.guild_id),
+        potd.channel_id,
+    );
+
+    let message = channel
+        .send_message(http, |m| {
+            m.embed(|e| poll.embed(e))
+                .components(|c| c.set_action_rows(poll.buttons()))
+        })
+        .await?;
+    store::create_poll(persist, message.id.0, &poll)?;
+
+    potd.current_poll = Some(message.id.0);
+    potd.last_posted = day;
+    Ok(())
+}
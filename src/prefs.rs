@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use shuttle_persist::PersistInstance;
+
+use crate::commands::eph_reply;
+use crate::config::GuildConfig;
+use crate::{Context, Error};
+
+///How poll results are written out
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum ResultsFormat {
+    #[default]
+    #[name = "Compact, every count on one line"]
+    Compact,
+    #[name = "Plain text, a line per choice with numbers spelled out"]
+    PlainText,
+}
+
+///Per user settings, changed with /preferences
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct UserPrefs {
+    //Unset uses the guild's format
+    #[serde(default)]
+    pub results_format: Option<ResultsFormat>,
+}
+
+fn key(user_id: u64) -> String {
+    format!("prefs_{user_id}")
+}
+
+//Members who hid their name from voter lists, kept in one list so showing a poll's voters
+//doesn't read every voter's preferences
+const HIDDEN_KEY: &str = "hidden_names";
+
+//Mirrors the stored list, so polls can be rendered without the store at hand
+static HIDDEN: Lazy<RwLock<HashSet<u64>>> = Lazy::new(Default::default);
+
+impl UserPrefs {
+    ///The user's settings, or the defaults if they never changed any
+    pub fn load(persist: &PersistInstance, user_id: u64) -> Self {
+        persist.load(&key(user_id)).unwrap_or_default()
+    }
+
+    pub fn save(&self, persist: &PersistInstance, user_id: u64) -> Result<(), Error> {
+        persist.save(&key(user_id), self)?;
+        Ok(())
+    }
+}
+
+///Reads who hid their name, once when the bot starts
+pub fn load_hidden(persist: &PersistInstance) {
+    let hidden = persist.load(HIDDEN_KEY).unwrap_or_default();
+    *HIDDEN.write().unwrap_or_else(|e| e.into_inner()) = hidden;
+}
+
+///Whether a member asked for their name to be left out of voter lists
+pub fn is_hidden(user_id: u64) -> bool {
+    HIDDEN
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains(&user_id)
+}
+
+///Shown in a voter list in place of the members who hid their name
+pub fn hidden_note(count: usize) -> Option<String> {
+    match count {
+        0 => None,
+        1 => Some("1 hidden voter".to_string()),
+        count => Some(format!("{count} hidden voters")),
+    }
+}
+
+fn set_hidden(persist: &PersistInstance, user_id: u64, hide: bool) -> Result<(), Error> {
+    let mut hidden = HIDDEN.write().unwrap_or_else(|e| e.into_inner());
+    match hide {
+        true => hidden.insert(user_id),
+        false => hidden.remove(&user_id),
+    };
+    persist.save(HIDDEN_KEY, &*hidden)?;
+    Ok(())
+}
+
+///The format results are shown to a user in, their own choice over their guild's
+pub fn results_format(
+    persist: &PersistInstance,
+    user_id: u64,
+    guild_id: Option<u64>,
+) -> ResultsFormat {
+    UserPrefs::load(persist, user_id)
+        .results_format
+        .or_else(|| guild_id.map(|g| GuildConfig::load(persist, g).results_format))
+        .unwrap_or_default()
+}
+
+//Parent command, only its subcommands can be invoked
+#[poise::command(slash_command, subcommands("results"))]
+pub async fn preferences(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+//Sets how poll results are written out for you, leave empty to use the server's format
+#[poise::command(slash_command)]
+async fn results(
+    ctx: Context<'_>,
+    #[description = "How to write out results"] format: Option<ResultsFormat>,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let user_id = ctx.author().id.0;
+
+    let mut prefs = UserPrefs::load(&persist, user_id);
+    prefs.results_format = format;
+    prefs.save(&persist, user_id)?;
+
+    match format {
+        Some(ResultsFormat::PlainText) => {
+            eph_reply(ctx, "Results are now written out as plain text for you").await
+        }
+        Some(ResultsFormat::Compact) => eph_reply(ctx, "Results are now compact for you").await,
+        None => eph_reply(ctx, "Results now use the server's format").await,
+    }
+}
+
+//Hides your name from the voter lists of every poll, showing you as a hidden voter instead
+#[poise::command(slash_command)]
+pub async fn privacy(
+    ctx: Context<'_>,
+    #[description = "Leave your name out of voter lists"] hide_my_name: bool,
+) -> Result<(), Error> {
+    set_hidden(&ctx.data().persist, ctx.author().id.0, hide_my_name)?;
+    match hide_my_name {
+        true => eph_reply(ctx, "Voter lists will count you as a hidden voter").await,
+        false => eph_reply(ctx, "Voter lists will show your name again").await,
+    }
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::{self as serenity, Activity};
+use shuttle_persist::PersistInstance;
+
+use crate::counters;
+
+//Discord rate limits presence updates, and the count doesn't need to be exact
+const REFRESH: Duration = Duration::from_secs(300);
+
+///Keeps the bot's status showing how many polls are open, like "Watching 42 active polls | /poll"
+pub fn spawn(ctx: serenity::Context, persist: PersistInstance) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH);
+        let mut shown = None;
+        loop {
+            interval.tick().await;
+            let open = match counters::open_polls(&persist) {
+                Ok(open) => open,
+                Err(e) => {
+                    tracing::warn!("Failed to count open polls for the bot's status: {e}");
+                    continue;
+                }
+            };
+            //Nothing is sent while the count stays the same
+            if shown == Some(open) {
+                continue;
+            }
+            let text = match open {
+                1 => "1 active poll | /poll".to_string(),
+                open => format!("{open} active polls | /poll"),
+            };
+            ctx.set_activity(Activity::watching(text)).await;
+            shown = Some(open);
+        }
+    });
+}
@@ -0,0 +1,324 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use poise::serenity_prelude::{
+    self as serenity, ButtonStyle, ChannelId, CreateComponents, Http, InteractionResponseType,
+    Timestamp, UserId,
+};
+use serde::{Deserialize, Serialize};
+use shuttle_persist::PersistInstance;
+
+use crate::commands::eph_reply;
+use crate::config::{is_moderator, GuildConfig};
+use crate::poll::Poll;
+use crate::{audit, lifecycle, native, Context, Error};
+
+//Held while a queue is read and written, so two reviews or submissions can't undo each other
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+fn key(guild_id: u64) -> String {
+    format!("queue_{guild_id}")
+}
+
+///A poll waiting for a moderator's approval before it is posted
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PendingPoll {
+    //ID of the command that submitted it, unique within the queue
+    pub id: u64,
+    //Unix timestamp, the poll's deadline counts from its approval instead
+    pub submitted_at: i64,
+    pub poll: Poll,
+    pub results_message: bool,
+    #[serde(default)]
+    pub option_threads: bool,
+}
+
+///Every poll waiting in a guild's queue, oldest first
+fn load(persist: &PersistInstance, guild_id: u64) -> Vec<PendingPoll> {
+    persist.load(&key(guild_id)).unwrap_or_default()
+}
+
+///Loads, changes and saves a guild's queue as one step
+fn update<T>(
+    persist: &PersistInstance,
+    guild_id: u64,
+    change: impl FnOnce(&mut Vec<PendingPoll>) -> T,
+) -> Result<T, Error> {
+    //A poisoned lock only means another update panicked, the saved queue is still fine
+    let _guard = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut queue = load(persist, guild_id);
+    let value = change(&mut queue);
+    persist.save(&key(guild_id), queue)?;
+    Ok(value)
+}
+
+///Removes a poll from the queue, `None` if another moderator already reviewed it
+fn take(persist: &PersistInstance, guild_id: u64, id: u64) -> Result<Option<PendingPoll>, Error> {
+    update(persist, guild_id, |queue| {
+        let position = queue.iter().position(|p| p.id == id)?;
+        Some(queue.remove(position))
+    })
+}
+
+///Puts a poll that couldn't be posted back in its place in the queue
+fn restore(persist: &PersistInstance, guild_id: u64, pending: PendingPoll) -> Result<(), Error> {
+    update(persist, guild_id, |queue| {
+        let position = queue.partition_point(|p| p.submitted_at <= pending.submitted_at);
+        queue.insert(position, pending);
+    })
+}
+
+///Whether the author's polls wait for a moderator's approval before they are posted
+pub async fn needs_approval(ctx: Context<'_>) -> bool {
+    match ctx.guild_id() {
+        Some(guild_id) => {
+            GuildConfig::load(&ctx.data().persist, guild_id.0).approval && !is_moderator(ctx).await
+        }
+        None => false,
+    }
+}
+
+///Puts a member's poll in their guild's queue instead of posting it
+pub async fn submit(
+    ctx: Context<'_>,
+    poll: Poll,
+    results_message: bool,
+    option_threads: bool,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+    let title = poll.title.clone();
+
+    let pending = PendingPoll {
+        id: ctx.id(),
+        submitted_at: Timestamp::now().unix_timestamp(),
+        poll,
+        results_message,
+        option_threads,
+    };
+    update(&persist, guild_id, |queue| queue.push(pending))?;
+
+    let action = format!("submitted poll \"{title}\" for approval, review it with /pollqueue");
+    audit::record(
+        &ctx.serenity_context().http,
+        &persist,
+        guild_id,
+        ctx.author().id.0,
+        action,
+    )
+    .await?;
+    eph_reply(
+        ctx,
+        "Your poll was sent to the moderators and will be posted once one of them approves it",
+    )
+    .await
+}
+
+///Lets the creator of a reviewed poll know what happened to it
+async fn tell_creator(http: &Http, poll: &Poll, text: String) {
+    let sent = match UserId(poll.creator_id).create_dm_channel(http).await {
+        Ok(dm) => dm.say(http, text).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    //Members can have DMs from servers turned off
+    if let Err(e) = sent {
+        tracing::warn!(
+            "Failed to tell {} about their reviewed poll: {e}",
+            poll.creator_id
+        );
+    }
+}
+
+///Posts an approved poll in the channel it was submitted in. A poll that couldn't be posted
+///goes back in the queue
+async fn approve(
+    http: &Http,
+    persist: &PersistInstance,
+    guild_id: u64,
+    moderator_id: u64,
+    pending: PendingPoll,
+) -> Result<String, Error> {
+    let mut poll = pending.poll.clone();
+    let waited = Timestamp::now().unix_timestamp() - pending.submitted_at;
+    poll.deadline = poll.deadline.map(|d| d + waited);
+    lifecycle::draw_tie_seed(&mut poll);
+
+    //Discord's own polls are posted through its poll interface instead of with buttons
+    let posted = match poll.native {
+        true => native::post(http, &poll).await.map(|id| (id, None)),
+        false => ChannelId(poll.channel_id.unwrap_or_default())
+            .send_message(http, |m| {
+                m.embed(|e| poll.embed(e))
+                    .components(|c| c.set_action_rows(poll.buttons()))
+            })
+            .await
+            .map(|message| (message.id.0, Some(message)))
+            .map_err(Error::from),
+    };
+    let (message_id, message) = match posted {
+        Ok(posted) => posted,
+        Err(e) => {
+            restore(persist, guild_id, pending)?;
+            return Err(e);
+        }
+    };
+    let link = poll.link(message_id).unwrap_or_default();
+    let title = poll.title.clone();
+    tell_creator(
+        http,
+        &poll,
+        format!("Your poll \"{title}\" was approved\n{link}"),
+    )
+    .await;
+    match message {
+        Some(message) => {
+            lifecycle::start_poll(
+                http,
+                persist,
+                &message,
+                poll,
+                pending.results_message,
+                pending.option_threads,
+            )
+            .await?
+        }
+        None => native::start(persist, message_id, &poll)?,
+    }
+
+    let action = format!("approved poll \"{title}\" ({message_id})");
+    audit::record(http, persist, guild_id, moderator_id, action).await?;
+    Ok(format!("Approved \"{title}\""))
+}
+
+async fn reject(
+    http: &Http,
+    persist: &PersistInstance,
+    guild_id: u64,
+    moderator_id: u64,
+    pending: PendingPoll,
+) -> Result<String, Error> {
+    let title = &pending.poll.title;
+    let text = format!("Your poll \"{title}\" was not approved by the moderators");
+    tell_creator(http, &pending.poll, text).await;
+
+    let action = format!("rejected poll \"{title}\"");
+    audit::record(http, persist, guild_id, moderator_id, action).await?;
+    Ok(format!("Rejected \"{title}\""))
+}
+
+///The oldest poll in the queue that wasn't skipped, with how many are waiting in total
+fn next(persist: &PersistInstance, guild_id: u64, skipped: &[u64]) -> Option<(PendingPoll, usize)> {
+    let queue = load(persist, guild_id);
+    let waiting = queue.len();
+    queue
+        .into_iter()
+        .find(|p| !skipped.contains(&p.id))
+        .map(|p| (p, waiting))
+}
+
+fn heading(pending: &PendingPoll, waiting: usize) -> String {
+    format!(
+        "Submitted by <@{}> in <#{}> <t:{}:R>, {waiting} waiting",
+        pending.poll.creator_id,
+        pending.poll.channel_id.unwrap_or_default(),
+        pending.submitted_at
+    )
+}
+
+fn review_buttons<'a>(c: &'a mut CreateComponents, prefix: &str) -> &'a mut CreateComponents {
+    c.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id(format!("{prefix}approve"))
+                .label("Approve")
+                .style(ButtonStyle::Success)
+        })
+        .create_button(|b| {
+            b.custom_id(format!("{prefix}reject"))
+                .label("Reject")
+                .style(ButtonStyle::Danger)
+        })
+        .create_button(|b| {
+            b.custom_id(format!("{prefix}skip"))
+                .label("Skip")
+                .style(ButtonStyle::Secondary)
+        })
+    })
+}
+
+//Reviews the polls waiting for approval one at a time, oldest first
+#[poise::command(slash_command, guild_only)]
+pub async fn pollqueue(ctx: Context<'_>) -> Result<(), Error> {
+    if !is_moderator(ctx).await {
+        return eph_reply(ctx, "Only moderators can review polls").await;
+    }
+    let persist = ctx.data().clone().persist;
+    let guild_id = ctx.guild_id().unwrap().0;
+    let http = &ctx.serenity_context().http;
+
+    let mut skipped = Vec::new();
+    let Some((mut pending, waiting)) = next(&persist, guild_id, &skipped) else {
+        return eph_reply(ctx, "No polls are waiting for approval").await;
+    };
+    let prefix = format!("queue_{}_", ctx.id());
+    ctx.send(|r| {
+        r.ephemeral(true)
+            .content(heading(&pending, waiting))
+            .embed(|e| pending.poll.embed(e))
+            .components(|c| review_buttons(c, &prefix))
+    })
+    .await?;
+
+    loop {
+        let filter_prefix = prefix.clone();
+        let Some(interaction) = serenity::CollectComponentInteraction::new(ctx.serenity_context())
+            .author_id(ctx.author().id)
+            .channel_id(ctx.channel_id())
+            .filter(move |i| i.data.custom_id.starts_with(&filter_prefix))
+            .timeout(Duration::from_secs(600))
+            .await
+        else {
+            return Ok(());
+        };
+
+        let moderator_id = ctx.author().id.0;
+        let status = match interaction.data.custom_id.strip_prefix(&prefix) {
+            Some("skip") => {
+                skipped.push(pending.id);
+                format!("Skipped \"{}\"", pending.poll.title)
+            }
+            Some(action @ ("approve" | "reject")) => match take(&persist, guild_id, pending.id)? {
+                Some(taken) if action == "approve" => {
+                    approve(http, &persist, guild_id, moderator_id, taken).await?
+                }
+                Some(taken) => reject(http, &persist, guild_id, moderator_id, taken).await?,
+                None => "Another moderator already reviewed that poll".to_string(),
+            },
+            _ => continue,
+        };
+
+        let Some((upcoming, waiting)) = next(&persist, guild_id, &skipped) else {
+            interaction
+                .create_interaction_response(http, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.content(format!("{status}\nNo more polls are waiting for review"))
+                                .set_embeds(Vec::new())
+                                .components(|c| c)
+                        })
+                })
+                .await?;
+            return Ok(());
+        };
+        pending = upcoming;
+        interaction
+            .create_interaction_response(http, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.content(format!("{status}\n{}", heading(&pending, waiting)))
+                            .embed(|e| pending.poll.embed(e))
+                            .components(|c| review_buttons(c, &prefix))
+                    })
+            })
+            .await?;
+    }
+}
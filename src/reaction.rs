@@ -0,0 +1,172 @@
+use poise::serenity_prelude::{self as serenity, Http, Message, Reaction, ReactionType};
+use shuttle_persist::PersistInstance;
+
+use crate::config::GuildConfig;
+use crate::poll::{Choice, Poll};
+use crate::{store, vote, Error};
+
+const YES: &str = "✅";
+const NO: &str = "❌";
+
+///Adds the reactions members can vote with under a poll's message
+pub async fn add(http: &Http, message: &Message) -> Result<(), Error> {
+    message
+        .react(http, ReactionType::Unicode(YES.to_string()))
+        .await?;
+    message
+        .react(http, ReactionType::Unicode(NO.to_string()))
+        .await?;
+    Ok(())
+}
+
+fn choice_of(emoji: &ReactionType) -> Option<Choice> {
+    match emoji {
+        ReactionType::Unicode(e) if e == YES => Some(Choice::Yes),
+        ReactionType::Unicode(e) if e == NO => Some(Choice::No),
+        _ => None,
+    }
+}
+
+fn emoji_of(choice: Choice) -> ReactionType {
+    let emoji = match choice {
+        Choice::Yes => YES,
+        Choice::No => NO,
+    };
+    ReactionType::Unicode(emoji.to_string())
+}
+
+///The poll, member and choice of a reaction, if it is a vote on a poll counting reactions
+fn reaction_vote(
+    ctx: &serenity::Context,
+    persist: &PersistInstance,
+    reaction: &Reaction,
+) -> Option<(Poll, u64, Choice)> {
+    let user_id = reaction.user_id?;
+    //The bot's own reactions are only there to click on
+    if user_id == ctx.cache.current_user_id() {
+        return None;
+    }
+    let choice = choice_of(&reaction.emoji)?;
+    let (_, poll) = store::load_poll(persist, reaction.message_id.0).ok()?;
+    if !poll.reactions || !poll.options().is_empty() {
+        return None;
+    }
+    Some((poll, user_id.0, choice))
+}
+
+fn click(reaction: &Reaction, user_id: u64, choice: Choice) -> vote::Click<'static> {
+    vote::Click {
+        message_id: reaction.message_id.0,
+        custom_id: match choice {
+            Choice::Yes => "poll_yes",
+            Choice::No => "poll_no",
+        },
+        user_id,
+        guild_id: reaction.guild_id.map(|g| g.0),
+    }
+}
+
+///Counts a reaction as a vote, taking it back if the vote is refused so it doesn't look counted
+pub async fn added(
+    ctx: &serenity::Context,
+    persist: &PersistInstance,
+    reaction: &Reaction,
+) -> Result<(), Error> {
+    let Some((poll, user_id, choice)) = reaction_vote(ctx, persist, reaction) else {
+        return Ok(());
+    };
+    //Already counted, e.g. from the button, and voting the same choice again would retract it
+    if poll.vote_of(user_id) == Some(choice) {
+        return Ok(());
+    }
+
+    let observer = match (reaction.guild_id, &reaction.member) {
+        (Some(guild_id), Some(member)) => {
+            let config = GuildConfig::load(persist, guild_id.0);
+            member
+                .roles
+                .iter()
+                .any(|r| config.observer_roles.contains(&r.0))
+        }
+        _ => false,
+    };
+    if observer {
+        reaction.delete(ctx).await?;
+        return Ok(());
+    }
+
+    match vote::click(persist, &click(reaction, user_id, choice))? {
+        vote::Cast::Voted {
+            poll_id,
+            poll,
+            change,
+        } => {
+            //A changed vote only keeps the reaction of the new choice
+            if let Some(previous) = change.previous {
+                let emoji = emoji_of(previous);
+                let removed = ctx
+                    .http
+                    .delete_reaction(
+                        reaction.channel_id.0,
+                        reaction.message_id.0,
+                        Some(user_id),
+                        &emoji,
+                    )
+                    .await;
+                if let Err(e) = removed {
+                    tracing::warn!("Failed to remove the old reaction of {user_id}: {e}");
+                }
+            }
+            //Reactions can't be replied to, so receipts of anonymous polls only reach button voters
+            vote::after(
+                &ctx.http,
+                persist,
+                poll_id,
+                &poll,
+                user_id,
+                &change,
+                reaction.channel_id,
+            )
+            .await
+        }
+        vote::Cast::Refused(_) => {
+            reaction.delete(ctx).await?;
+            Ok(())
+        }
+    }
+}
+
+///Retracts the vote of a removed reaction, on polls that let members retract votes
+pub async fn removed(
+    ctx: &serenity::Context,
+    persist: &PersistInstance,
+    reaction: &Reaction,
+) -> Result<(), Error> {
+    let Some((poll, user_id, choice)) = reaction_vote(ctx, persist, reaction) else {
+        return Ok(());
+    };
+    //Reactions the bot took back, like the old choice of a changed vote, aren't the member's vote
+    if !poll.allow_changes || poll.vote_of(user_id) != Some(choice) {
+        return Ok(());
+    }
+
+    //Voting the same choice again retracts it
+    if let vote::Cast::Voted {
+        poll_id,
+        poll,
+        change,
+    } = vote::click(persist, &click(reaction, user_id, choice))?
+    {
+        vote::after(
+            &ctx.http,
+            persist,
+            poll_id,
+            &poll,
+            user_id,
+            &change,
+            reaction.channel_id,
+        )
+        .await?;
+    }
+    Ok(())
+}
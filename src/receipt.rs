@@ -0,0 +1,91 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use poise::serenity_prelude::{AttachmentType, ChannelId, Http};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::poll::Poll;
+use crate::Error;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+///A random code to hand a voter or salt a poll's receipts with
+pub fn random_code() -> String {
+    let bytes: [u8; 16] = rand::thread_rng().gen();
+    hex(&bytes)
+}
+
+///The hash stored and published for a receipt, only the voter holding the code can recompute it
+pub fn commitment(salt: &str, code: &str, choice: &str) -> String {
+    hex(&Sha256::digest(format!("{salt}:{code}:{choice}")))
+}
+
+///The hash published for a random tie-break's seed before the seed itself is revealed
+pub fn seed_commitment(seed: u64) -> String {
+    hex(&Sha256::digest(seed.to_string()))
+}
+
+///Tells a voter their receipt code, if they got one
+pub fn note(code: Option<&str>) -> String {
+    match code {
+        Some(code) => format!(
+            "\nYour receipt is `{code}`, keep it to check your vote was counted with /poll receipt"
+        ),
+        None => String::new(),
+    }
+}
+
+///The choice a receipt code was counted for, if any vote was cast with it
+pub fn counted_choice(poll: &Poll, code: &str) -> Option<String> {
+    let code = code.trim().to_lowercase();
+    let receipts: HashSet<&String> = poll.receipts.values().collect();
+    let names: Vec<&str> = poll.vote_groups().into_iter().map(|(n, _)| n).collect();
+
+    //Multi-vote polls commit to every picked option together, so every combination is tried.
+    //Polls have at most 15 options, which keeps this to a few thousand hashes
+    (1..1u32 << names.len())
+        .map(|picked| {
+            names
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| picked & (1 << i) != 0)
+                .map(|(_, name)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .find(|choice| receipts.contains(&commitment(&poll.receipt_salt, &code, choice)))
+}
+
+///Posts every receipt hash of a closed anonymous poll, so voters can check theirs is among them
+pub async fn publish(http: &Http, poll: &Poll) -> Result<(), Error> {
+    let Some(channel_id) = poll.channel_id else {
+        return Ok(());
+    };
+    //Sorted so the list doesn't give away the order people voted in
+    let mut hashes: Vec<&String> = poll.receipts.values().collect();
+    hashes.sort();
+
+    let mut file = format!("salt {}\n", poll.receipt_salt);
+    for hash in hashes {
+        file.push_str(hash);
+        file.push('\n');
+    }
+
+    ChannelId(channel_id)
+        .send_message(http, |m| {
+            m.content(format!(
+                "Receipts of \"{}\". Check yours with /poll receipt, or by hashing salt:code:choice with SHA-256",
+                poll.title
+            ))
+            .allowed_mentions(|a| a.empty_parse())
+            .add_file(AttachmentType::Bytes {
+                data: Cow::Owned(file.into_bytes()),
+                filename: "receipts.txt".to_string(),
+            })
+        })
+        .await?;
+    Ok(())
+}
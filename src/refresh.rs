@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::Http;
+use shuttle_persist::PersistInstance;
+
+use crate::poll::Poll;
+use crate::{runtime, store, Error};
+
+struct Refresh {
+    last: Instant,
+    //An edit is already waiting to show every vote since the last one
+    scheduled: bool,
+}
+
+//u64 = the poll's message ID
+static REFRESHES: Lazy<Mutex<HashMap<u64, Refresh>>> = Lazy::new(Default::default);
+
+///Shows a vote on a poll's live tally, right away unless its messages were just edited,
+///in which case one edit is scheduled for every vote until then.
+///Closing a poll edits its messages directly, so the final result never waits
+pub async fn live_tally(
+    http: &Arc<Http>,
+    persist: &PersistInstance,
+    poll_id: u64,
+    poll: &Poll,
+) -> Result<(), Error> {
+    //Closing still edits the messages, so turned off the tally only shows once polls close
+    if !runtime::current().live_updates {
+        return Ok(());
+    }
+    //Discord rate limits message edits, so votes on busy polls are shown in batches at most
+    //this often
+    let min_interval = runtime::live_interval_duration();
+    let wait = {
+        let mut refreshes = REFRESHES.lock().unwrap_or_else(|e| e.into_inner());
+        //Polls that have been quiet for a while need no entry
+        refreshes.retain(|_, r| r.scheduled || r.last.elapsed() < min_interval);
+
+        match refreshes.get_mut(&poll_id) {
+            Some(refresh) if refresh.scheduled => return Ok(()),
+            Some(refresh) => {
+                refresh.scheduled = true;
+                min_interval.saturating_sub(refresh.last.elapsed())
+            }
+            None => {
+                let refresh = Refresh {
+                    last: Instant::now(),
+                    scheduled: false,
+                };
+                refreshes.insert(poll_id, refresh);
+                Duration::ZERO
+            }
+        }
+    };
+    if wait.is_zero() {
+        return poll.update_messages(http, poll_id).await;
+    }
+
+    let http = Arc::clone(http);
+    let persist = persist.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(wait).await;
+        if let Some(refresh) = REFRESHES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(&poll_id)
+        {
+            refresh.last = Instant::now();
+            refresh.scheduled = false;
+        }
+
+        //The poll is loaded again so the edit shows every vote cast while waiting
+        let updated = match store::load_poll(&persist, poll_id) {
+            Ok((_, poll)) => poll.update_messages(&http, poll_id).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = updated {
+            tracing::warn!("Failed to refresh the tally of poll {poll_id}: {e}");
+        }
+    });
+    Ok(())
+}
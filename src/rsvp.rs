@@ -0,0 +1,114 @@
+use std::borrow::Cow;
+
+use poise::serenity_prelude::{AttachmentType, Timestamp, UserId};
+
+use crate::commands::{autocomplete_poll, eph_reply, from_guild, load_own_poll, publish_poll};
+use crate::config::can_create_polls;
+use crate::poll::{Poll, PollKind, PollOption, Visibility, RSVP_OPTIONS};
+use crate::{validate, Context, Error};
+
+//Parent command, only its subcommands can be invoked
+#[poise::command(slash_command, subcommands("create", "export"))]
+pub async fn rsvp(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+//Asks members whether they are coming, listing everyone's answer
+#[poise::command(slash_command, check = "can_create_polls")]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "What members are answering for"] title: String,
+    #[description = "When and where it happens"] description: String,
+    #[description = "Most members that can be going"]
+    #[min = 1]
+    slots: Option<u32>,
+    #[description = "Stop taking answers after this many hours"]
+    #[min = 1]
+    duration_hours: Option<u32>,
+) -> Result<(), Error> {
+    let texts = validate::text("title", &title, validate::TITLE_LIMIT).and_then(|title| {
+        validate::text("description", &description, validate::DESCRIPTION_LIMIT)
+            .map(|description| (title, description))
+    });
+    let (title, description) = match texts {
+        Ok(texts) => texts,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+
+    let mut poll = Poll::new(
+        title,
+        description,
+        String::new(),
+        String::new(),
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        ctx.channel_id().0,
+    );
+    from_guild(ctx, &mut poll);
+    let options = RSVP_OPTIONS
+        .iter()
+        .enumerate()
+        .map(|(i, label)| PollOption {
+            label: label.to_string(),
+            capacity: if i == 0 { slots } else { None },
+            votes: Vec::new(),
+            reason: None,
+        })
+        .collect();
+    poll.kind = PollKind::Options {
+        options,
+        max_choices: 1,
+        rsvp: true,
+    };
+    //Answers are listed in the embed, so everyone can see them anyway
+    poll.visibility = Visibility::Public;
+    poll.allow_changes = true;
+    poll.deadline =
+        duration_hours.map(|hours| Timestamp::now().unix_timestamp() + i64::from(hours) * 3600);
+
+    publish_poll(ctx, poll, false, false).await
+}
+
+//Sends the organizer a spreadsheet of everyone's answers
+#[poise::command(slash_command)]
+async fn export(
+    ctx: Context<'_>,
+    #[description = "The RSVP"]
+    #[autocomplete = "autocomplete_poll"]
+    poll_id: String,
+) -> Result<(), Error> {
+    let Some((_, poll)) = load_own_poll(ctx, &poll_id, true).await? else {
+        return Ok(());
+    };
+    if !matches!(poll.kind, PollKind::Options { rsvp: true, .. }) {
+        return eph_reply(ctx, "That poll isn't an RSVP").await;
+    }
+    ctx.defer_ephemeral().await?;
+
+    let mut csv = "status,user_id,name\n".to_string();
+    for (status, votes) in poll.vote_groups() {
+        for vote in votes {
+            //Members that left or can't be fetched are still listed by ID
+            let name = match UserId(vote.0).to_user(ctx.serenity_context()).await {
+                Ok(user) => user.tag(),
+                Err(_) => String::new(),
+            };
+            csv.push_str(&format!(
+                "{status},{},\"{}\"\n",
+                vote.0,
+                name.replace('"', "\"\"")
+            ));
+        }
+    }
+
+    ctx.send(|r| {
+        r.ephemeral(true)
+            .content(format!("Answers to \"{}\"", poll.title))
+            .attachment(AttachmentType::Bytes {
+                data: Cow::Owned(csv.into_bytes()),
+                filename: "rsvp.csv".to_string(),
+            })
+    })
+    .await?;
+    Ok(())
+}
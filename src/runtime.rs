@@ -0,0 +1,171 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use shuttle_persist::PersistInstance;
+
+use crate::commands::eph_reply;
+use crate::{Context, Error};
+
+const KEY: &str = "runtime_config";
+
+///Settings the bot's owners can change while it runs, without a restart dropping the gateway
+///session. Stored, so they survive restarts too
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RuntimeConfig {
+    //Refuses commands and votes from everyone but the owners
+    #[serde(default)]
+    pub maintenance: bool,
+    //Edits poll messages as votes come in, otherwise only when polls close
+    #[serde(default = "enabled")]
+    pub live_updates: bool,
+    //Shortest time between edits of a poll's messages for new votes
+    #[serde(default = "live_interval")]
+    pub live_interval_secs: u64,
+    //How long a guild's AutoMod rules are mirrored before they are fetched again
+    #[serde(default = "automod_cache")]
+    pub automod_cache_secs: u64,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn live_interval() -> u64 {
+    5
+}
+
+fn automod_cache() -> u64 {
+    300
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            maintenance: false,
+            live_updates: enabled(),
+            live_interval_secs: live_interval(),
+            automod_cache_secs: automod_cache(),
+        }
+    }
+}
+
+//Read on every vote, so kept in memory rather than loaded from the store each time
+static CURRENT: Lazy<RwLock<RuntimeConfig>> = Lazy::new(Default::default);
+
+///The settings in effect
+pub fn current() -> RuntimeConfig {
+    CURRENT.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn live_interval_duration() -> Duration {
+    Duration::from_secs(current().live_interval_secs)
+}
+
+pub fn automod_cache_duration() -> Duration {
+    Duration::from_secs(current().automod_cache_secs)
+}
+
+///Puts the stored settings in effect, the defaults if none were ever changed
+pub fn reload(persist: &PersistInstance) -> RuntimeConfig {
+    let config: RuntimeConfig = persist.load(KEY).unwrap_or_default();
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = config.clone();
+    config
+}
+
+fn describe(config: &RuntimeConfig) -> String {
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    format!(
+        "Maintenance: {}\nLive updates: {}\nLive update interval: {}s\nAutoMod cache: {}s",
+        on_off(config.maintenance),
+        on_off(config.live_updates),
+        config.live_interval_secs,
+        config.automod_cache_secs
+    )
+}
+
+///Refuses commands from everyone but the owners during maintenance
+pub async fn command_check(ctx: Context<'_>) -> Result<bool, Error> {
+    if !current().maintenance || ctx.framework().options().owners.contains(&ctx.author().id) {
+        return Ok(true);
+    }
+    eph_reply(ctx, "The bot is down for maintenance, try again later").await?;
+    Ok(false)
+}
+
+#[derive(poise::ChoiceParameter)]
+pub enum Setting {
+    #[name = "Maintenance mode (on/off)"]
+    Maintenance,
+    #[name = "Live updates (on/off)"]
+    LiveUpdates,
+    #[name = "Live update interval (seconds)"]
+    LiveInterval,
+    #[name = "AutoMod cache (seconds)"]
+    AutomodCache,
+}
+
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value.trim().to_lowercase().as_str() {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => Err("That setting is on or off".to_string()),
+    }
+}
+
+fn parse_seconds(value: &str) -> Result<u64, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| "That setting is a number of seconds".to_string())
+}
+
+//Parent command, only its subcommands can be invoked
+#[poise::command(
+    slash_command,
+    owners_only,
+    hide_in_help,
+    subcommands("show", "set", "reload_stored")
+)]
+pub async fn runtime(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+//Shows the runtime settings in effect
+#[poise::command(slash_command, owners_only, hide_in_help)]
+async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    eph_reply(ctx, describe(&current())).await
+}
+
+//Changes a runtime setting, taking effect right away
+#[poise::command(slash_command, owners_only, hide_in_help)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "The setting"] setting: Setting,
+    #[description = "on or off, or a number of seconds"] value: String,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+
+    let mut config = reload(&persist);
+    let changed = match setting {
+        Setting::Maintenance => parse_switch(&value).map(|v| config.maintenance = v),
+        Setting::LiveUpdates => parse_switch(&value).map(|v| config.live_updates = v),
+        Setting::LiveInterval => parse_seconds(&value).map(|v| config.live_interval_secs = v),
+        Setting::AutomodCache => parse_seconds(&value).map(|v| config.automod_cache_secs = v),
+    };
+    if let Err(problem) = changed {
+        return eph_reply(ctx, problem).await;
+    }
+    persist.save(KEY, &config)?;
+    reload(&persist);
+    tracing::info!("{} changed the runtime settings", ctx.author().id);
+    eph_reply(ctx, describe(&config)).await
+}
+
+//Puts the stored runtime settings in effect again, for when they were changed outside the bot
+#[poise::command(slash_command, owners_only, hide_in_help, rename = "reload")]
+async fn reload_stored(ctx: Context<'_>) -> Result<(), Error> {
+    let config = reload(&ctx.data().persist);
+    eph_reply(ctx, describe(&config)).await
+}
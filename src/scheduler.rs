@@ -0,0 +1,346 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use poise::serenity_prelude::{ChannelId, GuildId, Http, Timestamp, UserId};
+use serde::{Deserialize, Serialize};
+use shuttle_persist::PersistInstance;
+
+use crate::config::GuildConfig;
+use crate::poll::{message_link, Poll};
+use crate::{ical, lifecycle, potd, store, Error};
+
+const JOBS_KEY: &str = "scheduled_jobs";
+
+//Keeps a warning from DMing a whole large server
+const MAX_WARNING_DMS: usize = 100;
+
+//A failing job is retried after 5, 10, 20 and 40 minutes before it is dropped
+const MAX_ATTEMPTS: u32 = 5;
+const RETRY_SECS: i64 = 300;
+
+//Held while the job list is read and written, so jobs scheduled at the same time aren't lost
+static JOBS_LOCK: Mutex<()> = Mutex::new(());
+
+///Something the scheduler does to a poll at a set time
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum Job {
+    ClosePoll { message_id: u64 },
+    //Bumps an open poll in its channel, scheduling the next bump each time
+    Remind { message_id: u64 },
+    //Warns that the poll is about to close, once per poll
+    Warn { message_id: u64 },
+}
+
+impl Job {
+    fn message_id(&self) -> u64 {
+        match *self {
+            Job::ClosePoll { message_id }
+            | Job::Remind { message_id }
+            | Job::Warn { message_id } => message_id,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct ScheduledJob {
+    //Unix timestamp the job runs at
+    at: i64,
+    job: Job,
+    //Times the job already failed
+    #[serde(default)]
+    attempts: u32,
+}
+
+fn load_jobs(persist: &PersistInstance) -> Vec<ScheduledJob> {
+    //No jobs have been saved yet on a fresh deployment
+    persist.load(JOBS_KEY).unwrap_or_default()
+}
+
+///Loads, changes and saves the job list as one step
+fn update_jobs(
+    persist: &PersistInstance,
+    change: impl FnOnce(&mut Vec<ScheduledJob>),
+) -> Result<(), Error> {
+    //A poisoned lock only means another update panicked, the saved jobs are still fine
+    let _guard = JOBS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut jobs = load_jobs(persist);
+    change(&mut jobs);
+    persist.save(JOBS_KEY, jobs)?;
+    Ok(())
+}
+
+///Runs a job at the given unix timestamp, surviving restarts
+pub fn schedule(persist: &PersistInstance, at: i64, job: Job) -> Result<(), Error> {
+    update_jobs(persist, |jobs| {
+        jobs.push(ScheduledJob {
+            at,
+            job,
+            attempts: 0,
+        })
+    })
+}
+
+///Drops every pending run of a job
+pub fn cancel(persist: &PersistInstance, job: &Job) -> Result<(), Error> {
+    update_jobs(persist, |jobs| jobs.retain(|j| &j.job != job))
+}
+
+///Drops every pending job of a poll, for when it closes or is deleted
+pub fn cancel_poll(persist: &PersistInstance, message_id: u64) -> Result<(), Error> {
+    update_jobs(persist, |jobs| {
+        jobs.retain(|j| j.job.message_id() != message_id)
+    })
+}
+
+///Schedules a poll's next reminder if it has an interval and the reminder comes before it closes
+pub fn schedule_reminder(
+    persist: &PersistInstance,
+    message_id: u64,
+    poll: &Poll,
+    now: i64,
+) -> Result<(), Error> {
+    let Some(hours) = poll.remind_hours else {
+        return Ok(());
+    };
+    let at = now + i64::from(hours) * 3600;
+    if poll.deadline.map_or(false, |deadline| at >= deadline) {
+        return Ok(());
+    }
+    schedule(persist, at, Job::Remind { message_id })
+}
+
+///Schedules the warning before a poll closes, unless it has no deadline or was already warned about
+pub fn schedule_warning(
+    persist: &PersistInstance,
+    message_id: u64,
+    poll: &Poll,
+    now: i64,
+) -> Result<(), Error> {
+    let (Some(minutes), Some(deadline), false) = (poll.warn_minutes, poll.deadline, poll.warned)
+    else {
+        return Ok(());
+    };
+    let at = deadline - i64::from(minutes) * 60;
+    if at <= now {
+        return Ok(());
+    }
+    schedule(persist, at, Job::Warn { message_id })
+}
+
+///Runs the bot's timed jobs once a minute for as long as the bot is up
+pub fn spawn(http: Arc<Http>, persist: PersistInstance) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let now = Timestamp::now().unix_timestamp();
+
+            if let Err(e) = run_due(&http, &persist, now).await {
+                tracing::error!("Scheduled jobs failed: {e}");
+            }
+            if let Err(e) = potd::tick(&http, &persist, now).await {
+                tracing::error!("Poll of the day job failed: {e}");
+            }
+        }
+    });
+}
+
+///Runs the jobs that are due, removing each once it ran and retrying failed ones later
+async fn run_due(http: &Arc<Http>, persist: &PersistInstance, now: i64) -> Result<(), Error> {
+    let due: Vec<ScheduledJob> = load_jobs(persist)
+        .into_iter()
+        .filter(|j| j.at <= now)
+        .collect();
+
+    for scheduled in due {
+        let result = run(http, persist, &scheduled.job, now).await;
+        update_jobs(persist, |jobs| {
+            //Closing a poll already cancelled its own jobs, so this may be gone
+            jobs.retain(|j| j != &scheduled);
+            let Err(e) = &result else {
+                return;
+            };
+            let attempts = scheduled.attempts + 1;
+            if attempts >= MAX_ATTEMPTS {
+                tracing::error!("Scheduled job failed {attempts} times, giving up: {e}");
+                return;
+            }
+            tracing::warn!("Scheduled job failed, retrying it later: {e}");
+            jobs.push(ScheduledJob {
+                at: now + RETRY_SECS * (1 << scheduled.attempts),
+                attempts,
+                ..scheduled
+            });
+        })?;
+    }
+    Ok(())
+}
+
+async fn run(
+    http: &Arc<Http>,
+    persist: &PersistInstance,
+    job: &Job,
+    now: i64,
+) -> Result<(), Error> {
+    match *job {
+        Job::ClosePoll { message_id } => {
+            //Polls deleted since have nothing left to close
+            let Ok((message_id, mut poll)) = store::load_poll(persist, message_id) else {
+                return Ok(());
+            };
+            let Some(channel_id) = poll.channel_id.filter(|_| !poll.closed) else {
+                return Ok(());
+            };
+
+            let summary = lifecycle::close_poll(http, persist, message_id, &mut poll).await?;
+            let link = message_link(poll.guild_id, channel_id, message_id);
+            ChannelId(channel_id)
+                .send_message(http, |m| {
+                    m.content(format!("Poll {summary}\n{link}"))
+                        .allowed_mentions(|a| a.empty_parse());
+                    //Scheduling polls come with their winning time for calendars
+                    if let Some(file) = ical::attachment(&poll, message_id) {
+                        m.add_file(file);
+                    }
+                    m
+                })
+                .await?;
+        }
+        Job::Remind { message_id } => {
+            let Ok((message_id, poll)) = store::load_poll(persist, message_id) else {
+                return Ok(());
+            };
+            let Some(channel_id) = poll.channel_id.filter(|_| !poll.closed) else {
+                return Ok(());
+            };
+            //Locked polls can't be voted on, so they skip bumps until they are unlocked
+            if !poll.locked {
+                let link = message_link(poll.guild_id, channel_id, message_id);
+                ChannelId(channel_id)
+                    .send_message(http, |m| {
+                        m.content(format!("{}\n{link}", reminder_text(&poll)))
+                            .allowed_mentions(|a| a.empty_parse())
+                    })
+                    .await?;
+            }
+            schedule_reminder(persist, message_id, &poll, now)?;
+        }
+        Job::Warn { message_id } => warn(http, persist, message_id).await?,
+    }
+    Ok(())
+}
+
+///e.g. Poll "Title" closes in 6 hours, 23 votes so far
+fn reminder_text(poll: &Poll) -> String {
+    let mut text = format!("Poll \"{}\"", poll.title);
+    match poll.deadline {
+        Some(deadline) => text.push_str(&format!(" closes <t:{deadline}:R>")),
+        None => text.push_str(" is still open"),
+    }
+    //Polls with restricted results don't show their counts to everyone
+    if poll.results_roles.is_empty() {
+        text.push_str(&match poll.voter_count() {
+            1 => ", 1 vote so far".to_string(),
+            count => format!(", {count} votes so far"),
+        });
+    }
+    text
+}
+
+///Warns that a poll is about to close in its channel, and by DM to members who haven't voted if asked
+async fn warn(http: &Arc<Http>, persist: &PersistInstance, message_id: u64) -> Result<(), Error> {
+    let updated = store::update(persist, message_id, |poll| {
+        //Polls deleted since fall through here too
+        if poll.closed || poll.warned {
+            return Err(String::new());
+        }
+        poll.warned = true;
+        Ok(())
+    })?;
+    let store::Updated::Saved {
+        poll_id: message_id,
+        poll,
+        ..
+    } = updated
+    else {
+        return Ok(());
+    };
+    let (Some(channel_id), Some(deadline)) = (poll.channel_id, poll.deadline) else {
+        return Ok(());
+    };
+
+    let link = message_link(poll.guild_id, channel_id, message_id);
+    let text = format!(
+        "Poll \"{}\" closes <t:{deadline}:R>, vote if you haven't yet\n{link}",
+        poll.title
+    );
+    ChannelId(channel_id)
+        .send_message(http, |m| {
+            m.content(&text).allowed_mentions(|a| a.empty_parse())
+        })
+        .await?;
+
+    //Going through every member takes a while, so it doesn't hold up the other due jobs
+    if let (true, Some(guild_id)) = (poll.warn_dm, poll.guild_id) {
+        let http = Arc::clone(http);
+        let persist = persist.clone();
+        tokio::spawn(async move {
+            let sent = dm_non_voters(&http, &persist, guild_id, channel_id, &poll, &text).await;
+            if let Err(e) = sent {
+                tracing::warn!("Failed to DM the non-voters of poll {message_id}: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+///DMs a warning to members of the poll's guild who can see its channel, haven't voted and may vote.
+///Listing members needs the Server Members intent enabled for the bot
+async fn dm_non_voters(
+    http: &Http,
+    persist: &PersistInstance,
+    guild_id: u64,
+    channel_id: u64,
+    poll: &Poll,
+    text: &str,
+) -> Result<(), Error> {
+    let config = GuildConfig::load(persist, guild_id);
+    let guild = GuildId(guild_id).to_partial_guild(http).await?;
+    let Some(channel) = ChannelId(channel_id).to_channel(http).await?.guild() else {
+        return Ok(());
+    };
+    let mut sent = 0;
+    let mut after = None;
+    loop {
+        let members = GuildId(guild_id).members(http, Some(1000), after).await?;
+        let Some(last) = members.last() else {
+            return Ok(());
+        };
+        after = Some(last.user.id);
+
+        for member in &members {
+            let observer = member
+                .roles
+                .iter()
+                .any(|r| config.observer_roles.contains(&r.0));
+            let can_view = guild
+                .user_permissions_in(&channel, member)
+                .map_or(false, |p| p.view_channel());
+            if member.user.bot || observer || !can_view || poll.has_voted(member.user.id.0) {
+                continue;
+            }
+            //Members with closed DMs are skipped
+            if let Ok(dm) = UserId(member.user.id.0).create_dm_channel(http).await {
+                if dm.say(http, text).await.is_ok() {
+                    sent += 1;
+                }
+            }
+            if sent >= MAX_WARNING_DMS {
+                return Ok(());
+            }
+        }
+        if members.len() < 1000 {
+            return Ok(());
+        }
+    }
+}
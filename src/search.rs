@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::{
+    self as serenity, ButtonStyle, CreateActionRow, InteractionResponseType, MessageId, Timestamp,
+};
+use shuttle_persist::PersistInstance;
+
+use crate::commands::eph_reply;
+use crate::poll::message_link;
+use crate::store::{self, IndexedPoll};
+use crate::{Context, Error};
+
+//Links are long, so pages are kept short to stay under the message length limit
+const PAGE_SIZE: usize = 8;
+
+#[derive(poise::ChoiceParameter, PartialEq, Eq, Clone, Copy)]
+pub enum PollStatus {
+    Open,
+    Ended,
+}
+
+///Parses a date like 2024-01-31, or a full timestamp, into a unix timestamp
+fn parse_date(date: &str) -> Option<i64> {
+    Timestamp::parse(date)
+        .or_else(|_| Timestamp::parse(&format!("{date}T00:00:00Z")))
+        .ok()
+        .map(|t| t.unix_timestamp())
+}
+
+///One line per poll, linking to its message when its channel is known. Ended polls show how
+///they came out from their archived summary
+fn page_text(
+    persist: &PersistInstance,
+    guild_id: u64,
+    polls: &[IndexedPoll],
+    page: usize,
+) -> String {
+    let pages = (polls.len() + PAGE_SIZE - 1) / PAGE_SIZE;
+    let lines: Vec<String> = polls
+        .iter()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .map(|p| {
+            let state = match p.closed {
+                true => match store::archived(persist, p.message_id) {
+                    Some(archived) => format!("ended, {}", archived.outcome),
+                    None => "ended".to_string(),
+                },
+                false => "open".to_string(),
+            };
+            let created = MessageId(p.message_id).created_at().unix_timestamp();
+            let title: String = p.title.chars().take(60).collect();
+            match p.channel_id {
+                Some(channel_id) => format!(
+                    "[{title}]({}) ({state}, <t:{created}:d>)",
+                    message_link(Some(guild_id), channel_id, p.message_id)
+                ),
+                None => format!("{title} ({state}, <t:{created}:d>, ID {})", p.message_id),
+            }
+        })
+        .collect();
+    format!(
+        "{} polls found\n{}\n\nPage {}/{pages}",
+        polls.len(),
+        lines.join("\n"),
+        page + 1
+    )
+}
+
+fn page_buttons(id: u64, page: usize, pages: usize) -> CreateActionRow {
+    let mut row = CreateActionRow::default();
+    row.create_button(|b| {
+        b.custom_id(format!("search_prev_{id}"))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0)
+    })
+    .create_button(|b| {
+        b.custom_id(format!("search_next_{id}"))
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= pages)
+    });
+    row
+}
+
+//Searches this server's polls, newest first
+#[poise::command(slash_command, guild_only)]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "Text in the title"] text: Option<String>,
+    #[description = "Who created the poll"] creator: Option<serenity::User>,
+    #[description = "Whether the poll is still open"] status: Option<PollStatus>,
+    #[description = "Created on or after this date (e.g. 2024-01-31)"] after: Option<String>,
+    #[description = "Created before this date (e.g. 2024-02-29)"] before: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().0;
+    let (after, before) = match (
+        after.map(|d| parse_date(&d)),
+        before.map(|d| parse_date(&d)),
+    ) {
+        (Some(None), _) | (_, Some(None)) => {
+            return eph_reply(ctx, "Dates must look like 2024-01-31").await;
+        }
+        (after, before) => (after.flatten(), before.flatten()),
+    };
+    let text = text.map(|t| t.to_lowercase());
+
+    let polls: Vec<IndexedPoll> = store::guild_polls(&ctx.data().persist, guild_id)
+        .into_iter()
+        .rev()
+        .filter(|p| {
+            let created = MessageId(p.message_id).created_at().unix_timestamp();
+            text.as_ref()
+                .map_or(true, |t| p.title.to_lowercase().contains(t))
+                && creator.as_ref().map_or(true, |c| c.id.0 == p.creator_id)
+                && status.map_or(true, |s| (s == PollStatus::Ended) == p.closed)
+                && after.map_or(true, |a| created >= a)
+                && before.map_or(true, |b| created < b)
+        })
+        .collect();
+    if polls.is_empty() {
+        return eph_reply(ctx, "No polls match that search").await;
+    }
+
+    let id = ctx.id();
+    let pages = (polls.len() + PAGE_SIZE - 1) / PAGE_SIZE;
+    let mut page = 0;
+    ctx.send(|r| {
+        r.ephemeral(true)
+            .content(page_text(&ctx.data().persist, guild_id, &polls, page))
+            .components(|c| c.add_action_row(page_buttons(id, page, pages)))
+    })
+    .await?;
+
+    //Pages can be flipped until nobody has for 10 minutes
+    loop {
+        let suffix = format!("_{id}");
+        let Some(interaction) = serenity::CollectComponentInteraction::new(ctx.serenity_context())
+            .author_id(ctx.author().id)
+            .channel_id(ctx.channel_id())
+            .filter(move |i| {
+                i.data.custom_id.starts_with("search_") && i.data.custom_id.ends_with(&suffix)
+            })
+            .timeout(Duration::from_secs(600))
+            .await
+        else {
+            return Ok(());
+        };
+
+        if interaction.data.custom_id.starts_with("search_next") {
+            page = (page + 1).min(pages - 1);
+        } else {
+            page = page.saturating_sub(1);
+        }
+
+        interaction
+            .create_interaction_response(ctx.serenity_context(), |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.content(page_text(&ctx.data().persist, guild_id, &polls, page))
+                            .components(|c| c.add_action_row(page_buttons(id, page, pages)))
+                    })
+            })
+            .await?;
+    }
+}
@@ -0,0 +1,139 @@
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use once_cell::sync::{Lazy, OnceCell};
+use poise::serenity_prelude::Timestamp;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::poll::Poll;
+use crate::Error;
+
+const SCOPE: &str = "https://www.googleapis.com/auth/spreadsheets";
+const SHEETS_API: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+
+///The fields of a Google service account key file the bot needs
+#[derive(Deserialize)]
+struct ServiceAccount {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+//Only set when the GOOGLE_SERVICE_ACCOUNT secret is
+static ACCOUNT: OnceCell<ServiceAccount> = OnceCell::new();
+//Access tokens last an hour, so one is reused until shortly before it expires
+static TOKEN: Lazy<Mutex<Option<(String, Instant)>>> = Lazy::new(Default::default);
+
+///Turns on the Sheets integration with the contents of a service account key file
+pub fn init(key_file: &str) -> Result<(), Error> {
+    let account: ServiceAccount = serde_json::from_str(key_file)?;
+    //Checked now so a broken key fails at startup rather than when the first poll closes
+    EncodingKey::from_rsa_pem(account.private_key.as_bytes())?;
+    let _ = ACCOUNT.set(account);
+    Ok(())
+}
+
+///The address spreadsheets have to be shared with, if the integration is on
+pub fn service_email() -> Option<&'static str> {
+    ACCOUNT.get().map(|a| a.client_email.as_str())
+}
+
+///The ID in a spreadsheet link like https://docs.google.com/spreadsheets/d/{id}/edit, or the ID itself
+pub fn spreadsheet_id(link: &str) -> Option<String> {
+    let link = link.trim();
+    let id = match link.split_once("/spreadsheets/d/") {
+        Some((_, rest)) => rest.split(['/', '?', '#']).next().unwrap_or_default(),
+        None => link,
+    };
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| id.to_string())
+}
+
+async fn access_token(account: &ServiceAccount) -> Result<String, Error> {
+    let mut token = TOKEN.lock().await;
+    if let Some((token, expires)) = token.as_ref() {
+        if Instant::now() < *expires {
+            return Ok(token.clone());
+        }
+    }
+
+    let now = Timestamp::now().unix_timestamp();
+    let claims = Claims {
+        iss: &account.client_email,
+        scope: SCOPE,
+        aud: &account.token_uri,
+        iat: now,
+        exp: now + 3600,
+    };
+    let assertion = jsonwebtoken::encode(
+        &Header::new(Algorithm::RS256),
+        &claims,
+        &EncodingKey::from_rsa_pem(account.private_key.as_bytes())?,
+    )?;
+    let response: TokenResponse = reqwest::Client::new()
+        .post(&account.token_uri)
+        .form(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", &assertion),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let expires = Instant::now() + Duration::from_secs(response.expires_in.saturating_sub(60));
+    *token = Some((response.access_token.clone(), expires));
+    Ok(response.access_token)
+}
+
+///Appends a closed poll's result as a row of the spreadsheet
+pub async fn append_result(
+    spreadsheet_id: &str,
+    poll: &Poll,
+    link: Option<String>,
+) -> Result<(), Error> {
+    let Some(account) = ACCOUNT.get() else {
+        return Ok(());
+    };
+    let token = access_token(account).await?;
+
+    let row = [
+        Timestamp::now().to_string(),
+        poll.title.clone(),
+        poll.outcome(),
+        poll.tally_text(),
+        poll.voter_count().to_string(),
+        link.unwrap_or_default(),
+    ];
+    //RAW keeps titles that start with = from being read as formulas
+    reqwest::Client::new()
+        .post(format!(
+            "{SHEETS_API}/{spreadsheet_id}/values/A1:append?valueInputOption=RAW"
+        ))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "values": [row] }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use poise::serenity_prelude::Timestamp;
+
+use crate::commands::{eph_reply, from_guild, publish_poll};
+use crate::config::can_create_polls;
+use crate::poll::{Poll, PollKind, PollOption, MAX_OPTIONS};
+use crate::{validate, Context, Error};
+
+//Button labels are capped at 80 characters
+const LABEL_LIMIT: usize = 80;
+//Kept short as every option's reason shares the embed
+const OPTION_REASON_LIMIT: usize = 200;
+
+///Parses "Tank:8, Healer:4, DPS" into options, a number after a colon being the option's slots
+fn parse_options(text: &str) -> Result<Vec<PollOption>, String> {
+    let options = text
+        .split(',')
+        .map(|option| {
+            let (label, capacity) = match option.rsplit_once(':') {
+                Some((label, slots)) => match slots.trim().parse::<u32>() {
+                    Ok(slots) if slots > 0 => (label, Some(slots)),
+                    _ => {
+                        return Err(format!(
+                            "\"{}\" doesn't have a valid number of slots",
+                            option.trim()
+                        ))
+                    }
+                },
+                None => (option, None),
+            };
+            Ok(PollOption {
+                label: validate::text("option", label, LABEL_LIMIT)?,
+                capacity,
+                votes: Vec::new(),
+                reason: None,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if options.len() < 2 || options.len() > MAX_OPTIONS {
+        return Err(format!("A poll needs between 2 and {MAX_OPTIONS} options"));
+    }
+    Ok(options)
+}
+
+///Gives each option its reason from "Tanks hold aggro | Healers keep everyone up | ...",
+///an empty reason leaving that option without one
+fn add_reasons(options: &mut [PollOption], text: &str) -> Result<(), String> {
+    let reasons: Vec<&str> = text.split('|').collect();
+    if reasons.len() != options.len() {
+        return Err(format!(
+            "There are {} reasons for {} options, give one per option separated by |",
+            reasons.len(),
+            options.len()
+        ));
+    }
+    for (option, reason) in options.iter_mut().zip(reasons) {
+        let reason = validate::optional_text("option reason", reason, OPTION_REASON_LIMIT)?;
+        option.reason = (!reason.is_empty()).then_some(reason);
+    }
+    Ok(())
+}
+
+//Starts a poll with named options, each optionally limited to a number of slots like a signup sheet
+#[poise::command(slash_command, check = "can_create_polls")]
+pub async fn signup(
+    ctx: Context<'_>,
+    #[description = "What the poll is about"] title: String,
+    #[description = "Details of the poll"] description: String,
+    #[description = "Comma separated options, with slots after a colon (e.g. Tank:2, Healer:2, DPS)"]
+    options: String,
+    #[description = "Why to pick each option, separated by | in the same order as the options"]
+    reasons: Option<String>,
+    #[description = "How many options each member can pick (default 1)"]
+    #[min = 1]
+    #[max = 15]
+    max_picks: Option<u32>,
+    #[description = "Let members change or retract their pick"] allow_vote_changes: Option<bool>,
+    #[description = "Open a discussion thread for each option, archived when the poll closes"]
+    option_threads: Option<bool>,
+    #[description = "Ask members why in a popup, reasons are shown in the results and the CSV"]
+    ask_why: Option<bool>,
+    #[description = "Close the poll automatically after this many hours"]
+    #[min = 1]
+    duration_hours: Option<u32>,
+) -> Result<(), Error> {
+    let texts = validate::text("title", &title, validate::TITLE_LIMIT).and_then(|title| {
+        validate::text("description", &description, validate::DESCRIPTION_LIMIT)
+            .map(|description| (title, description))
+    });
+    let (title, description) = match texts {
+        Ok(texts) => texts,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    let options = parse_options(&options).and_then(|mut options| {
+        if let Some(reasons) = &reasons {
+            add_reasons(&mut options, reasons)?;
+        }
+        Ok(options)
+    });
+    let options = match options {
+        Ok(options) => options,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+
+    //The options replace yes and no, so there are no reasons to vote either way
+    let mut poll = Poll::new(
+        title,
+        description,
+        String::new(),
+        String::new(),
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        ctx.channel_id().0,
+    );
+    from_guild(ctx, &mut poll);
+    poll.kind = PollKind::Options {
+        options,
+        max_choices: max_picks.unwrap_or(1),
+        rsvp: false,
+    };
+    poll.allow_changes = allow_vote_changes.unwrap_or(false);
+    poll.ask_reasons = ask_why.unwrap_or(false);
+    poll.deadline =
+        duration_hours.map(|hours| Timestamp::now().unix_timestamp() + i64::from(hours) * 3600);
+
+    publish_poll(ctx, poll, false, option_threads.unwrap_or(false)).await
+}
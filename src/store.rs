@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use poise::serenity_prelude::Timestamp;
+use serde::{Deserialize, Serialize};
+use shuttle_persist::PersistInstance;
+
+use crate::poll::Poll;
+use crate::{counters, Error};
+
+//Held from loading a poll until its changes are saved, so changes made at the same time, like
+//simultaneous votes, can't drop each other
+static POLL_LOCK: Mutex<()> = Mutex::new(());
+
+///Key pointing a crossposted copy's message ID at the original poll
+fn copy_key(message_id: u64) -> String {
+    format!("copy_{message_id}")
+}
+
+///Key a closed poll's summary is archived under, next to the poll itself
+fn archive_key(message_id: u64) -> String {
+    format!("archive_{message_id}")
+}
+
+///Key a poll's random tie-break seed is kept under until the poll closes
+fn tie_seed_key(message_id: u64) -> String {
+    format!("tie_seed_{message_id}")
+}
+
+///Key of the list of a guild's polls, so they can be looked up without loading every poll
+fn index_key(guild_id: u64) -> String {
+    format!("index_{guild_id}")
+}
+
+///A poll's entry in its guild's index
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct IndexedPoll {
+    pub message_id: u64,
+    pub title: String,
+    pub closed: bool,
+    //Entries from before these fields existed are filled in the next time their poll is saved
+    #[serde(default)]
+    pub creator_id: u64,
+    #[serde(default)]
+    pub channel_id: Option<u64>,
+}
+
+impl IndexedPoll {
+    fn of(message_id: u64, poll: &Poll) -> Self {
+        IndexedPoll {
+            message_id,
+            title: poll.title.clone(),
+            closed: poll.closed,
+            creator_id: poll.creator_id,
+            channel_id: poll.channel_id,
+        }
+    }
+}
+
+///What is kept of a poll for history once it closes, small enough to read many at once
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArchivedPoll {
+    pub title: String,
+    pub guild_id: Option<u64>,
+    //Votes per choice, in the order the poll shows them
+    pub counts: Vec<(String, usize)>,
+    pub outcome: String,
+    //Unix timestamp
+    pub closed_at: i64,
+}
+
+impl ArchivedPoll {
+    fn of(poll: &Poll) -> Self {
+        ArchivedPoll {
+            title: poll.title.clone(),
+            guild_id: poll.guild_id,
+            counts: poll
+                .vote_groups()
+                .into_iter()
+                .map(|(name, votes)| (name.to_string(), votes.len()))
+                .collect(),
+            outcome: poll.outcome(),
+            closed_at: Timestamp::now().unix_timestamp(),
+        }
+    }
+}
+
+///The summary of a closed poll, `None` while it is open
+pub fn archived(persist: &PersistInstance, message_id: u64) -> Option<ArchivedPoll> {
+    persist.load(&archive_key(message_id)).ok()
+}
+
+///Every poll of a guild, oldest first
+pub fn guild_polls(persist: &PersistInstance, guild_id: u64) -> Vec<IndexedPoll> {
+    persist.load(&index_key(guild_id)).unwrap_or_default()
+}
+
+///How many polls are open across every guild, read from every guild index. The count kept in
+///`counters` is read instead, this only backfills it
+pub fn open_poll_count(persist: &PersistInstance) -> Result<usize, Error> {
+    let guilds = persist
+        .list()?
+        .iter()
+        .filter_map(|k| k.strip_prefix("index_")?.parse().ok())
+        .collect::<Vec<u64>>();
+    Ok(guilds
+        .into_iter()
+        .map(|g| guild_polls(persist, g).iter().filter(|p| !p.closed).count())
+        .sum())
+}
+
+///Adds, updates or with `None` removes a poll's entry in its guild's index
+fn update_index(
+    persist: &PersistInstance,
+    guild_id: u64,
+    message_id: u64,
+    entry: Option<IndexedPoll>,
+) -> Result<(), Error> {
+    let mut index = guild_polls(persist, guild_id);
+    let position = index.iter().position(|p| p.message_id == message_id);
+
+    match (position, entry) {
+        //Votes save the poll without changing its entry, so most saves skip the write
+        (Some(i), Some(entry)) if index[i] == entry => return Ok(()),
+        (Some(i), Some(entry)) => index[i] = entry,
+        (Some(i), None) => {
+            index.remove(i);
+        }
+        (None, Some(entry)) => index.push(entry),
+        (None, None) => return Ok(()),
+    }
+    persist.save(&index_key(guild_id), index)?;
+    Ok(())
+}
+
+///Where polls are kept, so vote handling can run against memory in tests
+pub trait PollStore {
+    ///Loads the poll shown on a message, see `load_poll`
+    fn load(&self, message_id: u64) -> Result<(u64, Poll), Error>;
+    fn save(&self, message_id: u64, poll: &Poll) -> Result<(), Error>;
+    ///Brings the records listing a saved poll up to date, after it was closed, reopened,
+    ///renamed or moved or a closed poll's outcome changed. Votes change none of that, so they
+    ///never get here
+    fn relist(&self, _message_id: u64, _poll: &Poll) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl PollStore for PersistInstance {
+    fn load(&self, message_id: u64) -> Result<(u64, Poll), Error> {
+        load_poll(self, message_id)
+    }
+
+    fn save(&self, message_id: u64, poll: &Poll) -> Result<(), Error> {
+        save_poll(self, message_id, poll)
+    }
+
+    fn relist(&self, message_id: u64, poll: &Poll) -> Result<(), Error> {
+        list_poll(self, message_id, poll)
+    }
+}
+
+///What `update` did to a poll
+pub enum Updated<T> {
+    Saved { poll_id: u64, poll: Poll, value: T },
+    Refused(String),
+}
+
+impl<T> Updated<T> {
+    ///The saved poll, for callers with nobody to show a refusal to
+    pub fn saved(self) -> Result<(u64, Poll, T), Error> {
+        match self {
+            Updated::Saved {
+                poll_id,
+                poll,
+                value,
+            } => Ok((poll_id, poll, value)),
+            Updated::Refused(refusal) => Err(refusal.into()),
+        }
+    }
+}
+
+///What the index and the archive record of a poll, to tell when a save has to update them
+fn listing(message_id: u64, poll: &Poll) -> (IndexedPoll, Option<String>) {
+    (
+        IndexedPoll::of(message_id, poll),
+        poll.closed.then(|| poll.outcome()),
+    )
+}
+
+///Loads the poll shown on a message, changes it and saves it as one step. Every change to a
+///saved poll goes through here, so slow work like talking to Discord is done before or after.
+///A change that refuses leaves the poll as it was
+pub fn update<T>(
+    store: &(impl PollStore + ?Sized),
+    message_id: u64,
+    change: impl FnOnce(&mut Poll) -> Result<T, String>,
+) -> Result<Updated<T>, Error> {
+    //A poisoned lock only means another change panicked, the polls themselves are still fine
+    let _guard = POLL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let Ok((poll_id, mut poll)) = store.load(message_id) else {
+        return Ok(Updated::Refused("This poll doesn't exist!".to_string()));
+    };
+    let listed = listing(poll_id, &poll);
+    match change(&mut poll) {
+        Ok(value) => {
+            store.save(poll_id, &poll)?;
+            if listing(poll_id, &poll) != listed {
+                store.relist(poll_id, &poll)?;
+            }
+            Ok(Updated::Saved {
+                poll_id,
+                poll,
+                value,
+            })
+        }
+        Err(refusal) => Ok(Updated::Refused(refusal)),
+    }
+}
+
+///Keeps polls in memory, for tests and load testing without touching the real store
+#[derive(Default)]
+pub struct MemoryStore(Mutex<HashMap<u64, Poll>>);
+
+impl PollStore for MemoryStore {
+    fn load(&self, message_id: u64) -> Result<(u64, Poll), Error> {
+        let polls = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let poll = polls
+            .get(&message_id)
+            .ok_or("No poll exists with that ID")?;
+        Ok((message_id, poll.clone()))
+    }
+
+    fn save(&self, message_id: u64, poll: &Poll) -> Result<(), Error> {
+        let mut polls = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        polls.insert(message_id, poll.clone());
+        Ok(())
+    }
+}
+
+///Loads the poll shown on a message, following crossposted copies back to the original.
+///Returns the original poll's message ID alongside the poll
+pub fn load_poll(persist: &PersistInstance, message_id: u64) -> Result<(u64, Poll), Error> {
+    if let Ok(poll) = load_stored(persist, message_id) {
+        return Ok((message_id, poll));
+    }
+
+    let original: u64 = persist.load(&copy_key(message_id))?;
+    Ok((original, load_stored(persist, original)?))
+}
+
+///Loads a poll by the message ID it is stored under. Closed polls used to be moved under the
+///archive key whole, those are found there until `migrate` moves them back
+fn load_stored(persist: &PersistInstance, message_id: u64) -> Result<Poll, Error> {
+    match persist.load(&message_id.to_string()) {
+        Ok(poll) => Ok(poll),
+        Err(_) => Ok(persist.load(&archive_key(message_id))?),
+    }
+}
+
+///Saves a poll as it is, the one write on the path of a vote
+fn save_poll(persist: &PersistInstance, message_id: u64, poll: &Poll) -> Result<(), Error> {
+    persist.save(&message_id.to_string(), poll)?;
+    Ok(())
+}
+
+///Updates a poll's entry in its guild's index, and archives its summary if it is closed or
+///drops the summary if it was reopened
+pub fn list_poll(persist: &PersistInstance, message_id: u64, poll: &Poll) -> Result<(), Error> {
+    if let Some(guild_id) = poll.guild_id {
+        let entry = IndexedPoll::of(message_id, poll);
+        update_index(persist, guild_id, message_id, Some(entry))?;
+    }
+    match poll.closed {
+        true => {
+            let mut summary = ArchivedPoll::of(poll);
+            //A closed poll relisted after a tie is settled keeps when it closed
+            if let Some(archived) = archived(persist, message_id) {
+                summary.closed_at = archived.closed_at;
+            }
+            persist.save(&archive_key(message_id), summary)?;
+        }
+        //Polls that were never closed have no summary to drop
+        false => {
+            let _ = persist.remove(&archive_key(message_id));
+        }
+    }
+    Ok(())
+}
+
+///Saves a newly posted poll, lists it and counts it towards the stats
+pub fn create_poll(persist: &PersistInstance, message_id: u64, poll: &Poll) -> Result<(), Error> {
+    save_poll(persist, message_id, poll)?;
+    list_poll(persist, message_id, poll)?;
+    counters::poll_created(persist, poll.guild_id);
+    counters::poll_opened(persist);
+    Ok(())
+}
+
+pub fn save_copy(persist: &PersistInstance, message_id: u64, original: u64) -> Result<(), Error> {
+    persist.save(&copy_key(message_id), original)?;
+    Ok(())
+}
+
+///Keeps the secret seed of a poll's random tie-break apart from the poll
+pub fn save_tie_seed(persist: &PersistInstance, message_id: u64, seed: u64) -> Result<(), Error> {
+    persist.save(&tie_seed_key(message_id), seed)?;
+    Ok(())
+}
+
+///The seed of a poll's random tie-break, if one was drawn for it
+pub fn tie_seed(persist: &PersistInstance, message_id: u64) -> Option<u64> {
+    persist.load(&tie_seed_key(message_id)).ok()
+}
+
+///Forgets a crossposted copy, for when its message is gone
+pub fn delete_copy(persist: &PersistInstance, message_id: u64) -> Result<(), Error> {
+    persist.remove(&copy_key(message_id))?;
+    Ok(())
+}
+
+///Removes a poll and the records pointing its copies at it
+pub fn delete_poll(persist: &PersistInstance, message_id: u64, poll: &Poll) -> Result<(), Error> {
+    //Taken so a vote saved at the same time can't bring the poll back
+    let _guard = POLL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    for copy in &poll.copies {
+        persist.remove(&copy_key(copy.message_id))?;
+    }
+    persist.remove(&message_id.to_string())?;
+    //Only closed polls have a summary, and only polls settled by a random pick have a seed
+    let _ = persist.remove(&archive_key(message_id));
+    let _ = persist.remove(&tie_seed_key(message_id));
+
+    if let Some(guild_id) = poll.guild_id {
+        update_index(persist, guild_id, message_id, None)?;
+    }
+    if !poll.closed {
+        counters::poll_ended(persist);
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use crate::commands::{autocomplete_poll, eph_reply, from_guild, publish_poll};
+use crate::config::can_create_polls;
+use crate::poll::{Poll, PollKind, SuggestionStatus};
+use crate::{store, validate, Context, Error};
+
+//Posts a suggestion that members can vote on
+#[poise::command(slash_command, guild_only, check = "can_create_polls")]
+pub async fn suggest(
+    ctx: Context<'_>,
+    #[description = "What you are suggesting"] text: String,
+) -> Result<(), Error> {
+    let text = match validate::text("suggestion", &text, validate::DESCRIPTION_LIMIT) {
+        Ok(text) => text,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+
+    let mut poll = Poll::new(
+        format!("Suggestion from {}", ctx.author().name),
+        text,
+        "I like this".to_string(),
+        "I don't like this".to_string(),
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        ctx.channel_id().0,
+    );
+    from_guild(ctx, &mut poll);
+    poll.kind = PollKind::Suggestion {
+        status: SuggestionStatus::Pending,
+    };
+
+    publish_poll(ctx, poll, false, false).await
+}
+
+//Parent command, only its subcommands can be invoked
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    subcommands("status")
+)]
+pub async fn suggestion(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+//Sets the status shown on a suggestion
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_MESSAGES")]
+async fn status(
+    ctx: Context<'_>,
+    #[description = "The suggestion"]
+    #[autocomplete = "autocomplete_poll"]
+    suggestion_id: String,
+    #[description = "New status of the suggestion"] status: SuggestionStatus,
+) -> Result<(), Error> {
+    let persist = ctx.data().clone().persist;
+
+    let suggestion = suggestion_id
+        .parse::<u64>()
+        .ok()
+        .and_then(|id| store::load_poll(&persist, id).ok())
+        .filter(|(_, poll)| {
+            matches!(poll.kind, PollKind::Suggestion { .. })
+                && poll.guild_id == ctx.guild_id().map(|g| g.0)
+        });
+    let Some((message_id, _)) = suggestion else {
+        return eph_reply(ctx, "No suggestion exists with that ID").await;
+    };
+
+    let update = |poll: &mut Poll| {
+        poll.kind = PollKind::Suggestion { status };
+        Ok(())
+    };
+    let (_, poll, _) = store::update(&persist, message_id, update)?.saved()?;
+    poll.update_messages(&ctx.serenity_context().http, message_id)
+        .await?;
+
+    eph_reply(ctx, format!("Marked the suggestion as {}", status.as_str())).await
+}
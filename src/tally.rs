@@ -0,0 +1,81 @@
+//Pure counting of ballots, kept apart from Discord and storage so results are easy to check.
+//Options are referred to by their index
+
+///How a yes/no vote came out
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum YesNo {
+    Passed,
+    Rejected,
+    Tied,
+}
+
+pub fn yes_no(yes: usize, no: usize) -> YesNo {
+    match yes.cmp(&no) {
+        std::cmp::Ordering::Greater => YesNo::Passed,
+        std::cmp::Ordering::Less => YesNo::Rejected,
+        std::cmp::Ordering::Equal => YesNo::Tied,
+    }
+}
+
+///Share of the total as a whole percentage rounded down, 0 when nothing was counted
+pub fn percent(count: usize, total: usize) -> usize {
+    filled(count, total, 100)
+}
+
+///How many of the cells of a bar `width` long the count fills
+pub fn filled(count: usize, total: usize, width: usize) -> usize {
+    (count * width).checked_div(total).unwrap_or(0).min(width)
+}
+
+///Indexes of the options with the most votes, empty when nobody voted
+pub fn leaders<T: Ord + Default + Copy>(counts: &[T]) -> Vec<usize> {
+    let Some(&most) = counts.iter().max() else {
+        return Vec::new();
+    };
+    if most == T::default() {
+        return Vec::new();
+    }
+    (0..counts.len()).filter(|i| counts[*i] == most).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const OPTIONS: usize = 6;
+
+    fn counts() -> impl Strategy<Value = Vec<usize>> {
+        prop::collection::vec(0..20usize, 0..OPTIONS)
+    }
+
+    #[test]
+    fn yes_no_outcomes() {
+        assert_eq!(yes_no(3, 1), YesNo::Passed);
+        assert_eq!(yes_no(1, 3), YesNo::Rejected);
+        assert_eq!(yes_no(0, 0), YesNo::Tied);
+    }
+
+    proptest! {
+        #[test]
+        fn shares_stay_within_the_whole(counts in counts(), width in 1..50usize) {
+            let total: usize = counts.iter().sum();
+            for count in &counts {
+                prop_assert!(percent(*count, total) <= 100);
+                prop_assert!(filled(*count, total, width) <= width);
+            }
+            prop_assert_eq!(percent(total, total), if total == 0 { 0 } else { 100 });
+        }
+
+        #[test]
+        fn leaders_have_the_most_votes(counts in counts()) {
+            let leaders = leaders(&counts);
+            let most = counts.iter().copied().max().unwrap_or(0);
+
+            prop_assert_eq!(leaders.is_empty(), most == 0);
+            prop_assert!(leaders.iter().all(|l| counts[*l] == most));
+            prop_assert_eq!(leaders.len(), counts.iter().filter(|c| **c == most && most > 0).count());
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use poise::serenity_prelude::{ChannelId, ChannelType, Http};
+use shuttle_persist::PersistInstance;
+
+use crate::poll::Poll;
+use crate::{store, Error};
+
+//Thread names can be at most 100 characters
+const NAME_LIMIT: usize = 100;
+
+//Minutes without messages before Discord archives a thread on its own, the longest it allows
+const AUTO_ARCHIVE_MINUTES: u16 = 10080;
+
+///Opens a discussion thread for every option of a poll that was just posted, so supporters of
+///each option can argue for it separately. Discord only allows one thread to start from a
+///message, so they are standalone threads in the poll's channel linked from its embed
+pub async fn open(
+    http: &Http,
+    persist: &PersistInstance,
+    message_id: u64,
+    poll: &mut Poll,
+) -> Result<(), Error> {
+    let channel_id = ChannelId(poll.channel_id.ok_or("The poll's channel is unknown")?);
+    let link = poll.link(message_id).unwrap_or_default();
+
+    let mut threads = Vec::new();
+    for label in poll.options().iter().map(|o| &o.label) {
+        let name: String = format!("{label}: {}", poll.title)
+            .chars()
+            .take(NAME_LIMIT)
+            .collect();
+        let thread = channel_id
+            .create_private_thread(http, |t| {
+                t.name(name)
+                    .kind(ChannelType::PublicThread)
+                    .auto_archive_duration(AUTO_ARCHIVE_MINUTES)
+            })
+            .await?;
+        thread
+            .send_message(http, |m| {
+                m.content(format!("Discuss **{label}** here\n{link}"))
+                    .allowed_mentions(|a| a.empty_parse())
+            })
+            .await?;
+        threads.push(thread.id.0);
+    }
+
+    //The poll is open while the threads are created, so it is reloaded to keep early votes
+    let update = |poll: &mut Poll| {
+        poll.option_threads = threads;
+        Ok(())
+    };
+    *poll = store::update(persist, message_id, update)?.saved()?.1;
+    poll.update_messages(http, message_id).await
+}
+
+///Archives and locks a poll's option threads once it closes, or opens them again if it reopens
+pub async fn archive(http: &Http, poll: &Poll, archived: bool) {
+    for thread in &poll.option_threads {
+        //Threads deleted by hand are left alone
+        if let Err(e) = ChannelId(*thread)
+            .edit_thread(http, |t| t.archived(archived).locked(archived))
+            .await
+        {
+            tracing::warn!("Failed to update option thread {thread}: {e}");
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use poise::serenity_prelude::Timestamp;
+
+use crate::commands::{eph_reply, from_guild, publish_poll};
+use crate::config::can_create_polls;
+use crate::poll::{Poll, PollKind, PollOption, MAX_OPTIONS};
+use crate::{validate, Context, Error};
+
+///Parses a time like 2024-01-31T18:00:00Z or 2024-01-31 18:00, which is taken as UTC
+fn parse_slot(slot: &str) -> Option<Timestamp> {
+    let slot = slot.trim();
+    Timestamp::parse(slot)
+        .or_else(|_| Timestamp::parse(&format!("{}:00Z", slot.replacen(' ', "T", 1))))
+        .ok()
+}
+
+///A slot as a button label, e.g. 2024-01-31 18:00 UTC
+fn slot_label(slot: Timestamp) -> String {
+    let text = slot.to_string();
+    format!(
+        "{} UTC",
+        text.get(..16).unwrap_or(&text).replacen('T', " ", 1)
+    )
+}
+
+///Parses comma separated times into their unix timestamps, earliest first
+fn parse_slots(text: &str) -> Result<Vec<Timestamp>, String> {
+    let mut slots = text
+        .split(',')
+        .map(|slot| {
+            parse_slot(slot)
+                .ok_or_else(|| format!("\"{}\" isn't a time like 2024-01-31 18:00", slot.trim()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    slots.sort_by_key(|s| s.unix_timestamp());
+    slots.dedup();
+
+    if slots.len() < 2 || slots.len() > MAX_OPTIONS {
+        return Err(format!("A poll needs between 2 and {MAX_OPTIONS} times"));
+    }
+    Ok(slots)
+}
+
+//Finds a time for an event, the winning time can be added to calendars once the poll closes
+#[poise::command(slash_command, check = "can_create_polls")]
+pub async fn timepoll(
+    ctx: Context<'_>,
+    #[description = "What the event is"] title: String,
+    #[description = "Details of the event"] description: String,
+    #[description = "Comma separated times in UTC (e.g. 2024-01-31 18:00, 2024-02-01 18:00)"]
+    times: String,
+    #[description = "Length of the event in minutes (default 60)"]
+    #[min = 1]
+    event_minutes: Option<u32>,
+    #[description = "How many times each member can pick (default every time)"]
+    #[min = 1]
+    #[max = 15]
+    max_picks: Option<u32>,
+    #[description = "Let members change or retract their picks"] allow_vote_changes: Option<bool>,
+    #[description = "Close the poll automatically after this many hours"]
+    #[min = 1]
+    duration_hours: Option<u32>,
+) -> Result<(), Error> {
+    let texts = validate::text("title", &title, validate::TITLE_LIMIT).and_then(|title| {
+        validate::text("description", &description, validate::DESCRIPTION_LIMIT)
+            .map(|description| (title, description))
+    });
+    let (title, description) = match texts {
+        Ok(texts) => texts,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+    let slots = match parse_slots(&times) {
+        Ok(slots) => slots,
+        Err(problem) => return eph_reply(ctx, problem).await,
+    };
+
+    //The times replace yes and no, so there are no reasons to vote either way
+    let mut poll = Poll::new(
+        title,
+        description,
+        String::new(),
+        String::new(),
+        ctx.author().id.0,
+        ctx.guild_id().map(|g| g.0),
+        ctx.channel_id().0,
+    );
+    from_guild(ctx, &mut poll);
+    poll.kind = PollKind::Options {
+        options: slots
+            .iter()
+            .map(|slot| PollOption {
+                label: slot_label(*slot),
+                capacity: None,
+                votes: Vec::new(),
+                reason: None,
+            })
+            .collect(),
+        //Members usually can make several of the times
+        max_choices: max_picks.unwrap_or(slots.len() as u32),
+        rsvp: false,
+    };
+    poll.slots = slots.iter().map(|s| s.unix_timestamp()).collect();
+    poll.slot_minutes = event_minutes.unwrap_or(60);
+    poll.allow_changes = allow_vote_changes.unwrap_or(false);
+    poll.deadline =
+        duration_hours.map(|hours| Timestamp::now().unix_timestamp() + i64::from(hours) * 3600);
+
+    publish_poll(ctx, poll, false, false).await
+}
@@ -0,0 +1,60 @@
+//Discord embed limits, see https://discord.com/developers/docs/resources/channel#embed-object-embed-limits
+pub const TITLE_LIMIT: usize = 256;
+pub const DESCRIPTION_LIMIT: usize = 4096;
+//Embed fields can hold 1024 characters, the rest is room for the vote role note
+pub const REASON_LIMIT: usize = 1000;
+const EMBED_LIMIT: usize = 6000;
+
+///Breaks up @everyone and @here so poll text can't ping the whole server when it is quoted.
+///Role and user mentions are left for embeds, messages quoting poll text don't allow pings
+pub fn strip_mass_mentions(text: &str) -> String {
+    text.replace("@everyone", "@\u{200B}everyone")
+        .replace("@here", "@\u{200B}here")
+}
+
+///Cleans up a piece of poll text, or explains what is wrong with it
+pub fn text(name: &str, text: &str, limit: usize) -> Result<String, String> {
+    let text = strip_mass_mentions(text.trim());
+    let length = text.chars().count();
+
+    if length == 0 {
+        return Err(format!("The {name} can't be empty"));
+    }
+    if length > limit {
+        return Err(format!(
+            "The {name} is {length} characters long, but can be at most {limit}"
+        ));
+    }
+    Ok(text)
+}
+
+///Like `text`, but leaving the text out is fine
+pub fn optional_text(name: &str, text: &str, limit: usize) -> Result<String, String> {
+    match text.trim().is_empty() {
+        true => Ok(String::new()),
+        false => self::text(name, text, limit),
+    }
+}
+
+///Cleans up the text of a yes/no poll, where the reasons to vote can be left out, checking it fits in a single embed
+pub fn poll_text(
+    title: &str,
+    description: &str,
+    reason_to_vote_yes: &str,
+    reason_to_vote_no: &str,
+) -> Result<[String; 4], String> {
+    let texts = [
+        text("title", title, TITLE_LIMIT)?,
+        text("description", description, DESCRIPTION_LIMIT)?,
+        optional_text("reason to vote yes", reason_to_vote_yes, REASON_LIMIT)?,
+        optional_text("reason to vote no", reason_to_vote_no, REASON_LIMIT)?,
+    ];
+
+    let total: usize = texts.iter().map(|t| t.chars().count()).sum();
+    if total > EMBED_LIMIT {
+        return Err(format!(
+            "The poll is {total} characters long in total, but can be at most {EMBED_LIMIT}"
+        ));
+    }
+    Ok(texts)
+}
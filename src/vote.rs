@@ -0,0 +1,565 @@
+use std::sync::Arc;
+
+use poise::serenity_prelude::{ChannelId, Http};
+use shuttle_persist::PersistInstance;
+
+use crate::config::{self, GuildConfig, VoteMessage};
+use crate::poll::{Choice, Poll, PollKind};
+use crate::store::{self, PollStore, Updated};
+use crate::Error;
+use crate::{counters, milestone, refresh};
+
+///A click on a yes/no or sign button, as much of the interaction as voting needs
+pub struct Click<'a> {
+    pub message_id: u64,
+    pub custom_id: &'a str,
+    pub user_id: u64,
+    pub guild_id: Option<u64>,
+}
+
+pub enum Cast {
+    Voted {
+        poll_id: u64,
+        poll: Poll,
+        change: VoteChange,
+    },
+    Refused(String),
+}
+
+///What a vote did, for replying to the voter and updating everything that depends on it
+pub struct VoteChange {
+    pub previous: Option<Choice>,
+    pub new: Option<Choice>,
+    pub text: String,
+    pub threshold_reached: bool,
+    //Code the voter can check their vote with once an anonymous poll closes
+    pub receipt: Option<String>,
+    //Milestones the poll newly reached, for telling its creator
+    pub milestones: Vec<String>,
+}
+
+///What a vote on an options poll did, with the options as indexes
+pub struct OptionChange {
+    pub previous: Vec<usize>,
+    pub picks: Vec<usize>,
+    pub text: String,
+    pub receipt: Option<String>,
+    pub milestones: Vec<String>,
+}
+
+///Refuses votes on polls that ended or are locked
+fn accepts_votes(poll: &Poll) -> Result<(), String> {
+    if poll.closed {
+        return Err("This poll has ended!".to_string());
+    }
+    if poll.locked {
+        return Err("This poll is locked!".to_string());
+    }
+    Ok(())
+}
+
+///Votes a choice on a yes/no poll or petition, or explains why it can't be.
+///Voting the same choice again retracts the vote on polls that allow changes
+pub fn apply(
+    poll: &mut Poll,
+    user_id: u64,
+    choice: Choice,
+    guild_id: Option<u64>,
+) -> Result<VoteChange, String> {
+    accepts_votes(poll)?;
+    let choice_name = choice.as_str().to_lowercase();
+
+    let previous = poll.vote_of(user_id);
+    let (new, text) = match previous {
+        Some(previous) if !poll.allow_changes => {
+            return Err(match poll.kind {
+                PollKind::Petition { .. } => "You already signed!".to_string(),
+                _ => format!("You already voted {}!", previous.as_str().to_lowercase()),
+            });
+        }
+        //Clicking the same choice again retracts the vote
+        Some(previous) if previous == choice => (None, "Your vote was removed!".to_string()),
+        Some(_) => (
+            Some(choice),
+            format!("You changed your vote to {choice_name}!"),
+        ),
+        None if matches!(poll.kind, PollKind::Petition { .. }) => {
+            (Some(choice), "You signed the petition!".to_string())
+        }
+        None => (Some(choice), format!("You voted {choice_name}!")),
+    };
+
+    poll.remove_vote(user_id);
+    let mut receipt = None;
+    if let Some(new) = new {
+        poll.add_vote(user_id, new);
+        if let (true, Some(guild_id)) = (poll.federated, guild_id) {
+            poll.voter_guilds.insert(user_id, guild_id);
+        }
+        receipt = poll.issue_receipt(user_id, new.as_str());
+    }
+    Ok(VoteChange {
+        previous,
+        new,
+        text,
+        threshold_reached: petition_threshold_reached(poll),
+        receipt,
+        milestones: milestone::check(poll),
+    })
+}
+
+///Votes for one option of an options poll, refusing options that are full.
+///Picking the same option again retracts the vote on polls that allow changes
+pub fn apply_option(poll: &mut Poll, user_id: u64, index: usize) -> Result<OptionChange, String> {
+    accepts_votes(poll)?;
+    let previous = poll.option_of(user_id);
+    let Some(option) = poll.options().get(index) else {
+        return Err("That option doesn't exist!".to_string());
+    };
+
+    let text = match previous {
+        Some(previous) if !poll.allow_changes => {
+            return Err(format!(
+                "You already picked {}!",
+                poll.options()[previous].label
+            ));
+        }
+        //Clicking the same option again retracts the vote
+        Some(previous) if previous == index => "Your vote was removed!".to_string(),
+        _ if option.is_full() => return Err(format!("{} is full!", option.label)),
+        Some(_) => format!("You changed your vote to {}!", option.label),
+        None => format!("You picked {}!", option.label),
+    };
+
+    poll.remove_vote(user_id);
+    let mut picks = Vec::new();
+    let mut receipt = None;
+    if previous != Some(index) {
+        poll.add_option_vote(user_id, index);
+        let label = poll.options()[index].label.clone();
+        receipt = poll.issue_receipt(user_id, &label);
+        picks.push(index);
+    }
+    Ok(OptionChange {
+        previous: previous.into_iter().collect(),
+        picks,
+        text,
+        receipt,
+        milestones: milestone::check(poll),
+    })
+}
+
+///Replaces a member's picks on a multi-vote options poll, refusing options that are full.
+///No picks removes their votes
+pub fn apply_picks(
+    poll: &mut Poll,
+    user_id: u64,
+    mut picks: Vec<usize>,
+) -> Result<OptionChange, String> {
+    accepts_votes(poll)?;
+    let previous = poll.options_of(user_id);
+    if !previous.is_empty() && !poll.allow_changes {
+        return Err("You already voted!".to_string());
+    }
+
+    picks.retain(|i| *i < poll.options().len());
+    picks.sort_unstable();
+    picks.dedup();
+    //Options the member already holds a slot in don't count as full for them
+    let full: Vec<&str> = picks
+        .iter()
+        .map(|i| &poll.options()[*i])
+        .filter(|o| o.is_full() && !o.votes.iter().any(|v| v.0 == user_id))
+        .map(|o| o.label.as_str())
+        .collect();
+    if !full.is_empty() {
+        return Err(format!("{} is full!", full.join(", ")));
+    }
+
+    let labels = poll.labels_of(&picks);
+    let text = match picks.is_empty() {
+        true => "Your votes were removed!".to_string(),
+        false => format!("You picked {labels}!"),
+    };
+    poll.remove_vote(user_id);
+    for index in &picks {
+        poll.add_option_vote(user_id, *index);
+    }
+    let receipt = match picks.is_empty() {
+        true => None,
+        false => poll.issue_receipt(user_id, &labels),
+    };
+    Ok(OptionChange {
+        previous,
+        picks,
+        text,
+        receipt,
+        milestones: milestone::check(poll),
+    })
+}
+
+///The choice a button votes for
+pub fn button_choice(custom_id: &str) -> Option<Choice> {
+    match custom_id {
+        "poll_yes" | "poll_sign" => Some(Choice::Yes),
+        "poll_no" => Some(Choice::No),
+        _ => None,
+    }
+}
+
+///Loads the clicked poll, votes and saves it as one step
+pub fn click(store: &impl PollStore, click: &Click) -> Result<Cast, Error> {
+    let Some(choice) = button_choice(click.custom_id) else {
+        return Ok(Cast::Refused("That isn't a voting button".to_string()));
+    };
+    let updated = store::update(store, click.message_id, |poll| {
+        apply(poll, click.user_id, choice, click.guild_id)
+    })?;
+    Ok(match updated {
+        Updated::Saved {
+            poll_id,
+            poll,
+            value,
+        } => Cast::Voted {
+            poll_id,
+            poll,
+            change: value,
+        },
+        Updated::Refused(refusal) => Cast::Refused(refusal),
+    })
+}
+
+///Updates the poll's messages, petition announcement and vote roles after a saved vote
+pub async fn after(
+    http: &Arc<Http>,
+    persist: &PersistInstance,
+    poll_id: u64,
+    poll: &Poll,
+    user_id: u64,
+    change: &VoteChange,
+    channel_id: ChannelId,
+) -> Result<(), Error> {
+    match (change.previous, change.new) {
+        (None, Some(_)) => counters::vote_cast(persist, poll.guild_id),
+        (Some(_), None) => counters::vote_undone(persist, poll.guild_id),
+        _ => {}
+    }
+    if poll.live_tally() {
+        refresh::live_tally(http, persist, poll_id, poll).await?;
+    }
+    if change.threshold_reached {
+        announce_petition(http, poll, channel_id).await?;
+    }
+    milestone::announce(http, poll_id, poll, &change.milestones).await;
+
+    if let Err(e) = sync_roles(http, poll, user_id, change.previous, change.new).await {
+        tracing::warn!("Failed to update poll roles for {user_id}: {e}");
+    }
+    Ok(())
+}
+
+///Swaps the voter's poll roles from their old choice to their new one
+pub async fn sync_roles(
+    http: &Http,
+    poll: &Poll,
+    user_id: u64,
+    old: Option<Choice>,
+    new: Option<Choice>,
+) -> Result<(), Error> {
+    let Some(guild_id) = poll.guild_id else {
+        return Ok(());
+    };
+    let reason = Some("Voted on a poll");
+
+    if let Some(role) = old.and_then(|c| poll.role_for(c)) {
+        http.remove_member_role(guild_id, user_id, role, reason)
+            .await?;
+    }
+    if let Some(role) = new.and_then(|c| poll.role_for(c)) {
+        http.add_member_role(guild_id, user_id, role, reason)
+            .await?;
+    }
+    Ok(())
+}
+
+///Marks a petition actionable the first time it reaches its signature threshold
+fn petition_threshold_reached(poll: &mut Poll) -> bool {
+    let signatures = poll.yes_votes.len();
+    match &mut poll.kind {
+        PollKind::Petition {
+            threshold,
+            actionable,
+            ..
+        } if !*actionable && signatures >= *threshold as usize => {
+            *actionable = true;
+            true
+        }
+        _ => false,
+    }
+}
+
+///The guild's own text for a vote message filled in for the voter's choice, if it set one
+pub fn custom_text(
+    persist: &PersistInstance,
+    poll: &Poll,
+    message: VoteMessage,
+    choice: &str,
+) -> Option<String> {
+    let config = GuildConfig::load(persist, poll.guild_id?);
+    let template = config.vote_message(message)?;
+    let count = poll
+        .vote_groups()
+        .iter()
+        .map(|(_, votes)| votes.len())
+        .sum();
+    Some(config::fill_template(template, choice, &poll.title, count))
+}
+
+///The guild's own text for a voter who already voted and can't change it, if it set one
+pub fn already_voted_text(persist: &PersistInstance, poll: &Poll, user_id: u64) -> Option<String> {
+    if poll.allow_changes {
+        return None;
+    }
+    let picked = match poll.options().is_empty() {
+        true => poll.vote_of(user_id)?.as_str().to_string(),
+        false => poll.labels_of(&poll.options_of(user_id)),
+    };
+    if picked.is_empty() {
+        return None;
+    }
+    custom_text(persist, poll, VoteMessage::AlreadyVoted, &picked)
+}
+
+///Lets the petition's moderator role know it is ready for action
+async fn announce_petition(http: &Http, poll: &Poll, channel_id: ChannelId) -> Result<(), Error> {
+    let PollKind::Petition { notify_role, .. } = poll.kind else {
+        return Ok(());
+    };
+    let ping = notify_role.map(|r| format!("<@&{r}> ")).unwrap_or_default();
+
+    let text = format!(
+        "{ping}The petition \"{}\" reached {} signatures and is ready for action",
+        poll.title,
+        poll.yes_votes.len()
+    );
+    //Only the moderator role is pinged, not roles mentioned in the title
+    channel_id
+        .send_message(http, |m| {
+            m.content(text)
+                .allowed_mentions(|a| a.empty_parse().roles(notify_role))
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poll::{PollOption, Visibility};
+    use crate::receipt;
+    use crate::store::MemoryStore;
+
+    const POLL: u64 = 1;
+
+    fn store_with(configure: impl FnOnce(&mut Poll)) -> MemoryStore {
+        let mut poll = Poll::new(
+            "Title".to_string(),
+            "Description".to_string(),
+            "Yes".to_string(),
+            "No".to_string(),
+            100,
+            Some(10),
+            20,
+        );
+        configure(&mut poll);
+        let store = MemoryStore::default();
+        store.save(POLL, &poll).unwrap();
+        store
+    }
+
+    fn click_as(store: &MemoryStore, user_id: u64, custom_id: &str) -> Cast {
+        let input = Click {
+            message_id: POLL,
+            custom_id,
+            user_id,
+            guild_id: Some(10),
+        };
+        click(store, &input).unwrap()
+    }
+
+    fn stored(store: &MemoryStore) -> Poll {
+        store.load(POLL).unwrap().1
+    }
+
+    fn refusal(cast: Cast) -> Option<String> {
+        match cast {
+            Cast::Refused(refusal) => Some(refusal),
+            Cast::Voted { .. } => None,
+        }
+    }
+
+    #[test]
+    fn counts_a_vote() {
+        let store = store_with(|_| {});
+        assert!(refusal(click_as(&store, 1, "poll_yes")).is_none());
+
+        let poll = stored(&store);
+        assert_eq!(poll.yes_votes.len(), 1);
+        assert_eq!(poll.vote_of(1), Some(Choice::Yes));
+    }
+
+    #[test]
+    fn refuses_duplicate_votes_without_changes() {
+        let store = store_with(|_| {});
+        click_as(&store, 1, "poll_yes");
+
+        let refused = refusal(click_as(&store, 1, "poll_no"));
+        assert_eq!(refused.as_deref(), Some("You already voted yes!"));
+        let poll = stored(&store);
+        assert_eq!((poll.yes_votes.len(), poll.no_votes.len()), (1, 0));
+    }
+
+    #[test]
+    fn changes_and_retracts_votes_when_allowed() {
+        let store = store_with(|p| p.allow_changes = true);
+        click_as(&store, 1, "poll_yes");
+        click_as(&store, 1, "poll_no");
+        assert_eq!(stored(&store).vote_of(1), Some(Choice::No));
+
+        click_as(&store, 1, "poll_no");
+        assert_eq!(stored(&store).vote_of(1), None);
+    }
+
+    #[test]
+    fn refuses_votes_on_closed_and_locked_polls() {
+        let store = store_with(|p| p.closed = true);
+        assert!(refusal(click_as(&store, 1, "poll_yes")).is_some());
+
+        let store = store_with(|p| p.locked = true);
+        assert!(refusal(click_as(&store, 1, "poll_yes")).is_some());
+        assert!(stored(&store).yes_votes.is_empty());
+    }
+
+    #[test]
+    fn closing_keeps_the_votes_cast_before() {
+        let store = store_with(|_| {});
+        click_as(&store, 1, "poll_yes");
+        click_as(&store, 2, "poll_yes");
+        click_as(&store, 3, "poll_no");
+
+        let mut poll = stored(&store);
+        poll.closed = true;
+        store.save(POLL, &poll).unwrap();
+
+        assert!(refusal(click_as(&store, 4, "poll_no")).is_some());
+        let poll = stored(&store);
+        assert!(poll.passed());
+        assert_eq!(poll.tally_text(), "Yes: 2 No: 1");
+    }
+
+    #[test]
+    fn receipts_verify_only_the_counted_choice() {
+        let store = store_with(|p| {
+            p.visibility = Visibility::Anonymous;
+            p.allow_changes = true;
+        });
+        let Cast::Voted { change, .. } = click_as(&store, 1, "poll_yes") else {
+            panic!("the vote was refused");
+        };
+        let code = change.receipt.expect("anonymous votes get a receipt");
+        let poll = stored(&store);
+        assert_eq!(
+            receipt::counted_choice(&poll, &code).as_deref(),
+            Some("Yes")
+        );
+        assert_eq!(receipt::counted_choice(&poll, "not a receipt"), None);
+
+        //Changing the vote invalidates the old receipt
+        click_as(&store, 1, "poll_no");
+        assert_eq!(receipt::counted_choice(&stored(&store), &code), None);
+    }
+
+    fn with_options(poll: &mut Poll, max_choices: u32) {
+        let options = ["Red", "Green", "Blue", "Yellow"]
+            .into_iter()
+            .map(|label| PollOption {
+                label: label.to_string(),
+                capacity: None,
+                votes: Vec::new(),
+                reason: None,
+            })
+            .collect();
+        poll.kind = PollKind::Options {
+            options,
+            max_choices,
+            rsvp: false,
+        };
+    }
+
+    #[test]
+    fn simultaneous_votes_are_all_counted() {
+        let store = store_with(|_| {});
+        let options = store_with(|p| with_options(p, 1));
+        let picks = store_with(|p| with_options(p, 4));
+        std::thread::scope(|scope| {
+            for user_id in 0..64 {
+                let (store, options, picks) = (&store, &options, &picks);
+                scope.spawn(move || {
+                    let button = if user_id % 2 == 0 {
+                        "poll_yes"
+                    } else {
+                        "poll_no"
+                    };
+                    click_as(store, user_id, button);
+
+                    let index = user_id as usize % 4;
+                    store::update(options, POLL, |p| apply_option(p, user_id, index)).unwrap();
+                    store::update(picks, POLL, |p| apply_picks(p, user_id, vec![0, index]))
+                        .unwrap();
+                });
+            }
+        });
+
+        let poll = stored(&store);
+        assert_eq!((poll.yes_votes.len(), poll.no_votes.len()), (32, 32));
+        let counts = |store: &MemoryStore| -> Vec<usize> {
+            stored(store)
+                .options()
+                .iter()
+                .map(|o| o.votes.len())
+                .collect()
+        };
+        assert_eq!(counts(&options), [16, 16, 16, 16]);
+        assert_eq!(counts(&picks), [64, 16, 16, 16]);
+    }
+
+    #[test]
+    fn full_options_refuse_votes() {
+        let store = store_with(|p| {
+            with_options(p, 1);
+            if let PollKind::Options { options, .. } = &mut p.kind {
+                options[0].capacity = Some(1);
+            }
+        });
+        let vote = |user_id| store::update(&store, POLL, |p| apply_option(p, user_id, 0));
+
+        assert!(matches!(vote(1).unwrap(), Updated::Saved { .. }));
+        let Updated::Refused(refusal) = vote(2).unwrap() else {
+            panic!("the full option took a vote");
+        };
+        assert_eq!(refusal, "Red is full!");
+        assert_eq!(stored(&store).options()[0].votes.len(), 1);
+    }
+
+    #[test]
+    fn same_user_clicking_at_once_votes_once() {
+        let store = store_with(|_| {});
+        std::thread::scope(|scope| {
+            for _ in 0..16 {
+                scope.spawn(|| click_as(&store, 1, "poll_yes"));
+            }
+        });
+
+        assert_eq!(stored(&store).yes_votes.len(), 1);
+    }
+}
@@ -0,0 +1,256 @@
+use poise::serenity_prelude::{
+    ButtonStyle, CreateActionRow, Http, InteractionResponseType, MessageComponentInteraction,
+    Timestamp,
+};
+use shuttle_persist::PersistInstance;
+
+use crate::handler::eph_text;
+use crate::poll::{Poll, PollVote};
+use crate::Error;
+use crate::{prefs, store, tally};
+
+//Mentions are short, so a page stays well under the message length limit
+const PAGE_SIZE: usize = 25;
+
+///Mentions of the voters who didn't hide their name, and how many did
+fn mentions(votes: &[PollVote]) -> (Vec<String>, usize) {
+    let (hidden, shown): (Vec<&PollVote>, Vec<&PollVote>) =
+        votes.iter().partition(|v| prefs::is_hidden(v.0));
+    let mentions = shown.iter().map(|v| format!("<@{}>", v.0)).collect();
+    (mentions, hidden.len())
+}
+
+///Each choice's heading followed by a mention of each of its voters
+fn voter_lines(poll: &Poll) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (name, votes) in poll.vote_groups() {
+        lines.push(format!("**{name}** ({})", votes.len()));
+        let (mentions, hidden) = mentions(votes);
+        lines.extend(mentions);
+        lines.extend(prefs::hidden_note(hidden));
+    }
+    lines
+}
+
+///The text of one page of voters and the buttons to flip to its neighbours.
+///Pages past the end show the last page
+pub fn page(poll: &Poll, poll_id: u64, page: usize) -> (String, CreateActionRow) {
+    let lines = voter_lines(poll);
+    let pages = (lines.len() + PAGE_SIZE - 1) / PAGE_SIZE;
+    let page = page.min(pages - 1);
+
+    let text = format!(
+        "{}\n\nPage {}/{pages}",
+        lines[page * PAGE_SIZE..]
+            .iter()
+            .take(PAGE_SIZE)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n"),
+        page + 1
+    );
+
+    let mut row = CreateActionRow::default();
+    row.create_button(|b| {
+        b.custom_id(format!("voters_{poll_id}_{}", page.saturating_sub(1)))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0)
+    })
+    .create_button(|b| {
+        b.custom_id(format!("voters_{poll_id}_{}", page + 1))
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 == pages)
+    });
+    (text, row)
+}
+
+///Answers a results click on a public poll with the first page of its voters
+pub async fn show(
+    interaction: &MessageComponentInteraction,
+    http: &Http,
+    poll_id: u64,
+    poll: &Poll,
+    results: String,
+) -> Result<(), Error> {
+    let (text, row) = page(poll, poll_id, 0);
+    interaction
+        .create_interaction_response(http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.ephemeral(true)
+                        .content(format!("{results}\n\n{text}"))
+                        .allowed_mentions(|m| m.empty_parse())
+                        .components(|c| c.add_action_row(row))
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+///Flips the voter list to the page in a `voters_{poll}_{page}` button
+pub async fn flip(
+    interaction: &MessageComponentInteraction,
+    http: &Http,
+    persist: &PersistInstance,
+    args: &str,
+) -> Result<(), Error> {
+    let Some((poll_id, page_number)) = args.split_once('_') else {
+        return Ok(());
+    };
+    let (Ok(poll_id), Ok(page_number)) = (poll_id.parse(), page_number.parse()) else {
+        return Ok(());
+    };
+    let Ok((poll_id, poll)) = store::load_poll(persist, poll_id) else {
+        return eph_text(interaction, "This poll doesn't exist!", http).await;
+    };
+    if !poll.can_see_voters(interaction.user.id.0) {
+        return eph_text(interaction, "This poll's voters are private", http).await;
+    }
+
+    let (text, row) = page(&poll, poll_id, page_number);
+    interaction
+        .create_interaction_response(http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.content(text)
+                        .allowed_mentions(|m| m.empty_parse())
+                        .components(|c| c.add_action_row(row))
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+///A menu to pick an option of an options poll to see the details of
+fn drill_menu(poll: &Poll, poll_id: u64) -> CreateActionRow {
+    let mut row = CreateActionRow::default();
+    row.create_select_menu(|m| {
+        m.custom_id(format!("drill_{poll_id}"))
+            .placeholder("Pick an option for its details")
+            .options(|o| {
+                for (i, option) in poll.options().iter().enumerate() {
+                    o.create_option(|opt| opt.label(&option.label).value(i));
+                }
+                o
+            })
+    });
+    row
+}
+
+///An option's count, share and recent votes, with its voters if the viewer may see them
+fn option_details(poll: &Poll, index: usize, viewer: u64) -> String {
+    let option = &poll.options()[index];
+    let total: usize = poll.options().iter().map(|o| o.votes.len()).sum();
+    let votes = option.votes.len();
+    let share = tally::percent(votes, total);
+    let voters = tally::percent(votes, poll.voter_count());
+
+    let now = Timestamp::now().unix_timestamp();
+    let within = |seconds: i64| {
+        option
+            .votes
+            .iter()
+            .filter(|v| {
+                poll.voted_at
+                    .get(&v.0)
+                    .map_or(false, |at| now - at <= seconds)
+            })
+            .count()
+    };
+
+    let mut text = format!(
+        "**{}**: {}\n{share}% of all votes, picked by {voters}% of voters\nLast hour: {}, last day: {}",
+        option.label,
+        option.count_text(),
+        within(3600),
+        within(24 * 3600)
+    );
+    if poll.can_see_voters(viewer) && votes > 0 {
+        let (mentions, hidden) = mentions(&option.votes);
+        text.push('\n');
+        text.push_str(
+            &mentions
+                .iter()
+                .take(PAGE_SIZE)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        if mentions.len() > PAGE_SIZE {
+            text.push_str(&format!(" and {} more", mentions.len() - PAGE_SIZE));
+        }
+        if let Some(note) = prefs::hidden_note(hidden) {
+            text.push_str(&format!("\n{note}"));
+        }
+    }
+    text
+}
+
+///Answers a results click on an options poll with its tally and a menu to drill into each option
+pub async fn show_options(
+    interaction: &MessageComponentInteraction,
+    http: &Http,
+    poll_id: u64,
+    poll: &Poll,
+    results: String,
+) -> Result<(), Error> {
+    interaction
+        .create_interaction_response(http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.ephemeral(true)
+                        .content(results)
+                        .components(|c| c.add_action_row(drill_menu(poll, poll_id)))
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+///Shows the option picked in a `drill_{poll}` menu
+pub async fn drill(
+    interaction: &MessageComponentInteraction,
+    http: &Http,
+    persist: &PersistInstance,
+    poll_id: &str,
+) -> Result<(), Error> {
+    let Ok(poll_id) = poll_id.parse() else {
+        return Ok(());
+    };
+    let Ok((poll_id, poll)) = store::load_poll(persist, poll_id) else {
+        return eph_text(interaction, "This poll doesn't exist!", http).await;
+    };
+    let Some(index) = interaction
+        .data
+        .values
+        .first()
+        .and_then(|v| v.parse().ok())
+        .filter(|i| *i < poll.options().len())
+    else {
+        return Ok(());
+    };
+    let roles: Vec<u64> = interaction
+        .member
+        .as_ref()
+        .map(|m| m.roles.iter().map(|r| r.0).collect())
+        .unwrap_or_default();
+    if !poll.can_view_results(interaction.user.id.0, &roles) {
+        let text = "The results of this poll are restricted until it closes";
+        return eph_text(interaction, text, http).await;
+    }
+
+    let text = option_details(&poll, index, interaction.user.id.0);
+    interaction
+        .create_interaction_response(http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.content(text)
+                        .allowed_mentions(|m| m.empty_parse())
+                        .components(|c| c.add_action_row(drill_menu(&poll, poll_id)))
+                })
+        })
+        .await?;
+    Ok(())
+}